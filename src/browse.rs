@@ -0,0 +1,317 @@
+use crate::buffer::Buffer;
+use crate::input::{editorChoose, editorPrompt};
+use crate::Terminal;
+
+use std::env;
+use std::fs;
+use std::fs::OpenOptions;
+use std::io;
+use std::io::Error;
+use std::io::ErrorKind::Other;
+use std::path::{Path, PathBuf};
+
+// what an entry in a directory is
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Kind {
+    Dir,
+    File,
+    Link,
+    Other,
+}
+
+struct Entry {
+    name: String,
+    kind: Kind,
+    size: u64,
+}
+
+// the directory a listing buffer shows, with the entry on each of its lines
+pub(crate) struct Listing {
+    pub(crate) dir: String, //full path, ending in /
+    entries: Vec<Entry>,
+}
+
+// 512, 2.1K, 13M ...
+fn humanSize(size: u64) -> String {
+    let mut size = size as f64;
+    for unit in ["", "K", "M", "G", "T"] {
+        if size < 1024.0 || unit == "T" {
+            return match unit {
+                "" => format!("{}", size),
+                _ if size < 10.0 => format!("{:.1}{}", size, unit),
+                _ => format!("{:.0}{}", size, unit),
+            };
+        }
+        size /= 1024.0;
+    }
+    String::new()
+}
+
+// entries of a directory - subdirectories first, with .. at the top unless it's /
+fn readDir(dir: &Path) -> io::Result<Vec<Entry>> {
+    let mut entries = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        let kind = if file_type.is_dir() {
+            Kind::Dir
+        } else if file_type.is_file() {
+            Kind::File
+        } else if file_type.is_symlink() {
+            Kind::Link
+        } else {
+            Kind::Other
+        };
+        let size = if kind == Kind::File { entry.metadata()?.len() } else { 0 };
+        entries.push(Entry { name: entry.file_name().to_string_lossy().to_string(), kind, size });
+    }
+    entries.sort_by(|a, b| (a.kind != Kind::Dir, &a.name).cmp(&(b.kind != Kind::Dir, &b.name)));
+    if dir.parent().is_some() {
+        entries.insert(0, Entry { name: String::from(".."), kind: Kind::Dir, size: 0 });
+    }
+    Ok(entries)
+}
+
+// `dir  -  src/` or `file  2.1K  main.rs`
+fn entryLine(entry: &Entry) -> String {
+    let (kind, size) = match entry.kind {
+        Kind::Dir => ("dir", String::from("-")),
+        Kind::File => ("file", humanSize(entry.size)),
+        Kind::Link => ("link", String::from("-")),
+        Kind::Other => ("?", String::from("-")),
+    };
+    let slash = if entry.kind == Kind::Dir { "/" } else { "" };
+    format!("{:<4} {:>6}  {}{}", kind, size, entry.name, slash)
+}
+
+// a path relative to where the editor was started, when it's under there
+fn displayPath(path: &Path) -> String {
+    let relative = env::current_dir().ok().and_then(|cwd| path.strip_prefix(cwd).ok().map(Path::to_path_buf));
+    relative.unwrap_or_else(|| path.to_path_buf()).to_string_lossy().to_string()
+}
+
+// a read-only buffer listing `dir`, with the cursor on `focus` if it's there
+fn listingBuffer(terminal: &Terminal, dir: &Path, focus: Option<&str>) -> io::Result<Buffer> {
+    let dir = match fs::canonicalize(dir) {
+        Ok(dir) => dir,
+        Err(e) => return Err(Error::new(Other, format!("{}: {}", dir.display(), e))),
+    };
+    let entries = match readDir(&dir) {
+        Ok(entries) => entries,
+        Err(e) => return Err(Error::new(Other, format!("{}: {}", dir.display(), e))),
+    };
+    let mut buffer = terminal.newBuffer();
+    buffer.content = entries.iter().map(entryLine).collect();
+    if buffer.content.is_empty() {
+        buffer.content.push(String::new());
+    }
+    buffer.options.readonly = true;
+    if let Some(y) = focus.and_then(|name| entries.iter().position(|entry| entry.name == name)) {
+        buffer.curs_y = y as i32;
+    }
+    let mut name = dir.to_string_lossy().to_string();
+    if !name.ends_with('/') {
+        name.push('/');
+    }
+    buffer.listing = Some(Listing { dir: name, entries });
+    Ok(buffer)
+}
+
+// show a listing of `dir` in the focused window - a listing already there is replaced, like following a link
+fn editorShowListing(terminal: &mut Terminal, dir: &Path, focus: Option<&str>) -> io::Result<()> {
+    let buffer = listingBuffer(terminal, dir, focus)?;
+    if terminal.buf().listing.is_some() {
+        *terminal.bufMut() = buffer;
+    } else {
+        terminal.addBuffer(buffer);
+    }
+    Ok(())
+}
+
+pub(crate) fn editorBrowse(terminal: &mut Terminal, dir: &str) -> io::Result<()> {
+    editorShowListing(terminal, Path::new(dir), None)
+}
+
+// :Explore with no directory - the one the current file is in
+pub(crate) fn currentDir(terminal: &Terminal) -> String {
+    let buffer = terminal.buf();
+    if let Some(listing) = &buffer.listing {
+        return listing.dir.clone();
+    }
+    match Path::new(&buffer.fp).parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_string_lossy().to_string(),
+        _ => String::from("."),
+    }
+}
+
+// the listing's directory and the entry under the cursor
+fn currEntry(terminal: &Terminal) -> io::Result<(PathBuf, &Entry)> {
+    let buffer = terminal.buf();
+    let listing = match &buffer.listing {
+        Some(listing) => listing,
+        None => return Err(Error::new(Other, "Not a directory listing")),
+    };
+    match listing.entries.get(buffer.curs_y as usize) {
+        Some(entry) => Ok((PathBuf::from(&listing.dir), entry)),
+        None => Err(Error::new(Other, "Empty directory")),
+    }
+}
+
+// an entry that can be renamed or deleted - not ..
+fn currFile(terminal: &Terminal) -> io::Result<(PathBuf, String, Kind)> {
+    let (dir, entry) = currEntry(terminal)?;
+    if entry.name == ".." {
+        return Err(Error::new(Other, "Can't do that to .."));
+    }
+    Ok((dir, entry.name.clone(), entry.kind))
+}
+
+// list the same directory again, keeping the cursor where it was or moving it to `focus`
+fn editorRelist(terminal: &mut Terminal, focus: Option<&str>) -> io::Result<()> {
+    let (dir, _) = currEntry(terminal)?;
+    let y = terminal.buf().curs_y;
+    let mut buffer = listingBuffer(terminal, &dir, focus)?;
+    if focus.is_none() {
+        buffer.curs_y = y;
+        buffer.clampCursor();
+    }
+    *terminal.bufMut() = buffer;
+    Ok(())
+}
+
+// Enter - go into a directory or open a file
+pub(crate) fn editorBrowseOpen(terminal: &mut Terminal) -> io::Result<()> {
+    let (dir, entry) = currEntry(terminal)?;
+    if entry.name == ".." {
+        return editorBrowseUp(terminal);
+    }
+    let path = dir.join(&entry.name);
+    //links go wherever they point
+    if path.is_dir() {
+        editorShowListing(terminal, &path, None)
+    } else {
+        terminal.editorOpenFile(&displayPath(&path))
+    }
+}
+
+// - : the directory above, with the cursor on the one we came from
+pub(crate) fn editorBrowseUp(terminal: &mut Terminal) -> io::Result<()> {
+    let (dir, _) = currEntry(terminal)?;
+    let name = dir.file_name().map(|name| name.to_string_lossy().to_string());
+    match dir.parent() {
+        Some(parent) => editorShowListing(terminal, parent, name.as_deref()),
+        None => Ok(()),
+    }
+}
+
+// % - make an empty file and open it
+pub(crate) fn editorBrowseNewFile(terminal: &mut Terminal) -> io::Result<()> {
+    let (dir, _) = currEntry(terminal)?;
+    let name = match editorPrompt(terminal, "New file: ")? {
+        Some(name) if !name.is_empty() => name,
+        _ => return Ok(()),
+    };
+    let path = dir.join(&name);
+    if let Err(e) = OpenOptions::new().write(true).create_new(true).open(&path) {
+        return Err(Error::new(Other, format!("{}: {}", name, e)));
+    }
+    editorRelist(terminal, Some(&name))?;
+    terminal.editorOpenFile(&displayPath(&path))
+}
+
+// d - make a directory
+pub(crate) fn editorBrowseNewDir(terminal: &mut Terminal) -> io::Result<()> {
+    let (dir, _) = currEntry(terminal)?;
+    let name = match editorPrompt(terminal, "New directory: ")? {
+        Some(name) if !name.is_empty() => name,
+        _ => return Ok(()),
+    };
+    if let Err(e) = fs::create_dir(dir.join(&name)) {
+        return Err(Error::new(Other, format!("{}: {}", name, e)));
+    }
+    editorRelist(terminal, Some(&name))
+}
+
+// R - rename the entry under the cursor. Buffers with the file open follow it
+pub(crate) fn editorBrowseRename(terminal: &mut Terminal) -> io::Result<()> {
+    let (dir, name, _) = currFile(terminal)?;
+    let new_name = match editorPrompt(terminal, &format!("Rename {} to: ", name))? {
+        Some(new_name) if !new_name.is_empty() && new_name != name => new_name,
+        _ => return Ok(()),
+    };
+    let (from, to) = (dir.join(&name), dir.join(&new_name));
+    if to.exists() {
+        return Err(Error::new(Other, format!("{} already exists", new_name)));
+    }
+    let open: Vec<usize> = (0..terminal.buffers.len())
+        .filter(|i| fs::canonicalize(&terminal.buffers[*i].fp).is_ok_and(|fp| fp == from))
+        .collect();
+    if let Err(e) = fs::rename(&from, &to) {
+        return Err(Error::new(Other, format!("{}: {}", name, e)));
+    }
+    for i in open {
+        terminal.buffers[i].fp = displayPath(&to);
+    }
+    editorRelist(terminal, Some(&new_name))
+}
+
+// D - delete the entry under the cursor, after asking. Directories have to be empty
+pub(crate) fn editorBrowseDelete(terminal: &mut Terminal) -> io::Result<()> {
+    let (dir, name, kind) = currFile(terminal)?;
+    let msg = format!("Delete {}? [y]es or [n]o", name);
+    if editorChoose(terminal, &msg, "yn")? != Some('y') {
+        return Ok(());
+    }
+    let path = dir.join(&name);
+    let result = if kind == Kind::Dir { fs::remove_dir(&path) } else { fs::remove_file(&path) };
+    if let Err(e) = result {
+        return Err(Error::new(Other, format!("{}: {}", name, e)));
+    }
+    editorRelist(terminal, None)?;
+    terminal.setMessage(&format!("Deleted {}", name));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formatsSizes() {
+        assert_eq!(humanSize(0), "0");
+        assert_eq!(humanSize(1023), "1023");
+        assert_eq!(humanSize(1024), "1.0K");
+        assert_eq!(humanSize(2150), "2.1K");
+        assert_eq!(humanSize(10 * 1024), "10K");
+        assert_eq!(humanSize(13 * 1024 * 1024), "13M");
+        assert_eq!(humanSize(5000 << 40), "5000T");
+    }
+
+    #[test]
+    fn formatsEntries() {
+        let dir = Entry { name: String::from("src"), kind: Kind::Dir, size: 0 };
+        assert_eq!(entryLine(&dir), "dir       -  src/");
+        let file = Entry { name: String::from("main.rs"), kind: Kind::File, size: 2150 };
+        assert_eq!(entryLine(&file), "file   2.1K  main.rs");
+        let link = Entry { name: String::from("ln"), kind: Kind::Link, size: 0 };
+        assert_eq!(entryLine(&link), "link      -  ln");
+    }
+
+    #[test]
+    fn readsDirectoriesFirst() {
+        let root = env::temp_dir().join(format!("rustwrd-browse-{}", std::process::id()));
+        for dir in ["b", "z"] {
+            fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        for name in ["a.txt", "c.txt"] {
+            fs::write(root.join(name), "abc").unwrap();
+        }
+        let entries = readDir(&root);
+        fs::remove_dir_all(&root).unwrap();
+        let entries = entries.unwrap();
+        let names: Vec<&str> = entries.iter().map(|entry| entry.name.as_str()).collect();
+        assert_eq!(names, ["..", "b", "z", "a.txt", "c.txt"]);
+        assert_eq!(entries[3].kind, Kind::File);
+        assert_eq!(entries[3].size, 3);
+    }
+}
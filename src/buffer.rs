@@ -0,0 +1,403 @@
+use crate::browse::Listing;
+use crate::disk::{diskStamp, DiskStamp};
+use crate::encoding::{decode, encodeLines, Encoding, LineEnds};
+use crate::hex::{hexLines, parseHex};
+use crate::large::{writeLarge, LargeFile};
+use crate::mark::adjustMarks;
+use crate::motion::Pos;
+use crate::options::BufferOptions;
+use crate::swap::Swap;
+use crate::undo::{Edit, UndoHistory};
+use crate::visual::Selection;
+
+use nix::unistd::{access, AccessFlags};
+use std::collections::HashMap;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::ErrorKind::Other;
+use std::io::{BufRead, Error, ErrorKind, Write};
+use std::path::Path;
+
+// a file (or scratch text) loaded into the editor
+pub(crate) struct Buffer {
+    pub(crate) content: Vec<String>, //the text content we are working on
+    pub(crate) fp: String,           //file this buffer is saved to - empty if it has none
+    pub(crate) curs_x: i32,          //horizontal position of the cursor (in characters)
+    pub(crate) curs_y: i32,          //vertical position of the cursor
+    pub(crate) v_offset: i32,        // vertical scrolling padding
+    pub(crate) h_offset: i32,        //first screen column shown when lines don't wrap
+    pub(crate) undo: UndoHistory,
+    saved_state: u64, //undo state the file on disk matches
+    pub(crate) last_visual: Option<Selection>, //for gv and the '< '> marks
+    pub(crate) marks: HashMap<char, Pos>,      //set with m - file marks A-Z are kept in the buffer they're in
+    pub(crate) options: BufferOptions,
+    pub(crate) changes: u64,         //goes up with every edit - tells the swap file when it's behind
+    pub(crate) swap: Option<Swap>,   //crash recovery copy kept while the buffer has unsaved changes
+    pub(crate) disk: Option<DiskStamp>, //the file as it was when last read or written - None if it wasn't there
+    pub(crate) disk_kept: Option<DiskStamp>, //a change made outside the editor the user chose to ignore
+    pub(crate) large: Option<LargeFile>, //set when only part of the file is loaded
+    pub(crate) hex: bool, //a binary file shown as a hex dump - what's written is the bytes it lists
+    pub(crate) encoding: Encoding, //what the file is written in
+    pub(crate) line_ends: LineEnds, //and how its lines end
+    pub(crate) listing: Option<Listing>, //set when the buffer lists a directory rather than holding a file
+}
+
+// number of characters in a line - the cursor counts characters, not bytes
+pub(crate) fn lineLen(line: &str) -> i32 {
+    line.chars().count() as i32
+}
+
+// byte offset of the character at column `cx`
+pub(crate) fn byteIdx(line: &str, cx: i32) -> usize {
+    line.char_indices().nth(cx.max(0) as usize).map_or(line.len(), |(i, _)| i)
+}
+
+// screen column character `cx` starts at, with tabs taking up to `tabstop` columns
+pub(crate) fn displayCol(line: &str, cx: i32, tabstop: usize) -> i32 {
+    let mut col = 0;
+    let mut chars = line.chars();
+    for _ in 0..cx.max(0) {
+        match chars.next() {
+            Some('\t') => col += tabstop - col % tabstop,
+            _ => col += 1, //past the end every column is one character
+        }
+    }
+    col as i32
+}
+
+// leading spaces and tabs of a line
+pub(crate) fn indentOf(line: &str) -> &str {
+    let end = line.find(|c: char| c != ' ' && c != '\t').unwrap_or(line.len());
+    &line[..end]
+}
+
+// false for a file we aren't allowed to write - a file that doesn't exist yet can be
+pub(crate) fn isWritable(fp: &str) -> bool {
+    !Path::new(fp).exists() || access(fp, AccessFlags::W_OK).is_ok()
+}
+
+// lines of a file, the encoding it's in and how its lines end - one empty line if it doesn't exist. Text that
+// isn't UTF-8 is read as `legacy`. A binary file comes back as a hex dump, with None
+fn readLines(fp: &str, legacy: Encoding) -> io::Result<(Vec<String>, Option<Encoding>, LineEnds)> {
+    let bytes = match fs::read(fp) {
+        Ok(bytes) => bytes,
+        //a new file
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            return Ok((vec![String::new()], Some(Encoding::Utf8), LineEnds::UNIX));
+        }
+        Err(e) => return Err(Error::new(Other, format!("{}: {}", fp, e))),
+    };
+    let (text, encoding) = match decode(&bytes, legacy) {
+        Some(decoded) => decoded,
+        None => return Ok((hexLines(&bytes), None, LineEnds::UNIX)),
+    };
+    let mut lines: Vec<String> = text.lines().map(String::from).collect();
+    if lines.is_empty() {
+        lines.push(String::new());
+    }
+    Ok((lines, Some(encoding), LineEnds::of(&text)))
+}
+
+// save lines to a file in `encoding`, ending them the way `ends` says - returns how many bytes were written
+pub(crate) fn writeLines(fp: &str, lines: &[String], encoding: Encoding, ends: LineEnds) -> io::Result<usize> {
+    let bytes = match encodeLines(lines, encoding, ends) {
+        Ok(bytes) => bytes,
+        Err(e) => return Err(Error::new(Other, format!("Not written: {} (:convert to another encoding)", e))),
+    };
+    writeBytes(fp, &bytes)
+}
+
+// save bytes to a file as they are
+pub(crate) fn writeBytes(fp: &str, bytes: &[u8]) -> io::Result<usize> {
+    match fs::write(fp, bytes) {
+        Ok(()) => Ok(bytes.len()),
+        Err(e) => Err(Error::new(Other, format!("{}: {}", fp, e))),
+    }
+}
+
+impl Buffer {
+    pub(crate) fn new() -> Buffer {
+        Buffer {
+            content: vec![String::new()],
+            fp: String::new(),
+            curs_x: 0,
+            curs_y: 0,
+            v_offset: 0,
+            h_offset: 0,
+            undo: UndoHistory::default(),
+            saved_state: 0,
+            last_visual: None,
+            marks: HashMap::new(),
+            options: BufferOptions::default(),
+            changes: 0,
+            swap: None,
+            disk: None,
+            disk_kept: None,
+            large: None,
+            hex: false,
+            encoding: Encoding::Utf8,
+            line_ends: LineEnds::UNIX,
+            listing: None,
+        }
+    }
+
+    // read from file - a file that doesn't exist yet gives an empty buffer that will create it.
+    // Text that isn't UTF-8 is read as `legacy`
+    pub(crate) fn open(fp: &str, legacy: Encoding) -> io::Result<Buffer> {
+        let mut buffer = Buffer::new();
+        buffer.fp = String::from(fp);
+        let encoding;
+        (buffer.content, encoding, buffer.line_ends) = readLines(fp, legacy)?;
+        buffer.hex = encoding.is_none();
+        buffer.encoding = encoding.unwrap_or(Encoding::Utf8);
+        buffer.disk = diskStamp(fp);
+        Ok(buffer)
+    }
+
+    // replace the text with what's in the file now, as one change that can be undone
+    pub(crate) fn reload(&mut self, legacy: Encoding) -> io::Result<()> {
+        let (mut content, encoding, line_ends) = readLines(&self.fp, legacy)?;
+        //a file that turned into text (or stopped being text) is shown the new way from now on
+        self.hex = encoding.is_none();
+        self.encoding = encoding.unwrap_or(self.encoding);
+        self.line_ends = line_ends;
+        //only the lines that differ are replaced, so marks elsewhere stay put
+        let prefix = self.content.iter().zip(&content).take_while(|(a, b)| a == b).count();
+        let rest = self.content[prefix..].iter().rev().zip(content[prefix..].iter().rev());
+        let suffix = rest.take_while(|(a, b)| a == b).count();
+        let end = self.content.len() - suffix;
+        content.truncate(content.len() - suffix);
+        self.undo.seal();
+        self.replaceLines(prefix, end, content.split_off(prefix));
+        self.undo.seal();
+        self.saved_state = self.undo.state();
+        self.disk = diskStamp(&self.fp);
+        self.disk_kept = None;
+        self.clampNormal();
+        Ok(())
+    }
+
+    pub(crate) fn write(&mut self) -> io::Result<usize> {
+        if self.fp.is_empty() {
+            return Err(Error::new(Other, "No file name"));
+        }
+        if self.large.is_some() {
+            let written = writeLarge(self)?;
+            self.saved_state = self.undo.state();
+            return Ok(written);
+        }
+        let written = if self.hex {
+            writeBytes(&self.fp, &parseHex(&self.content)?)?
+        } else {
+            writeLines(&self.fp, &self.content, self.encoding, self.line_ends)?
+        };
+        self.undo.seal();
+        self.saved_state = self.undo.state();
+        self.disk = diskStamp(&self.fp);
+        self.disk_kept = None;
+        Ok(written)
+    }
+
+    // pick up a history saved in an earlier session - the text is what it was when that was saved
+    pub(crate) fn restoreUndo(&mut self, history: UndoHistory) {
+        self.undo = history;
+        self.saved_state = self.undo.state();
+    }
+
+    // have the file written in another encoding from now on - the buffer counts as changed until it is
+    pub(crate) fn convert(&mut self, encoding: Encoding) -> io::Result<()> {
+        if self.hex || self.large.is_some() {
+            return Err(Error::new(Other, "Only text that's loaded whole can be converted"));
+        }
+        encodeLines(&self.content, encoding, self.line_ends)?;
+        if encoding != self.encoding {
+            self.encoding = encoding;
+            self.saved_state = u64::MAX;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn isDirty(&self) -> bool {
+        let edited = self.large.as_ref().is_some_and(|large| large.isEdited());
+        edited || self.undo.state() != self.saved_state || !self.undo.isSealed()
+    }
+
+    // let go of the undo steps that reach outside lines `start..end` - a buffer with unsaved changes stays
+    // that way even if the steps that made them are gone
+    pub(crate) fn forgetUndo(&mut self, start: usize, end: usize) {
+        let dirty = self.isDirty();
+        self.undo.forgetOutside(start, end);
+        self.saved_state = if dirty { u64::MAX } else { self.undo.state() };
+    }
+
+    // name to show the user
+    pub(crate) fn name(&self) -> &str {
+        if let Some(listing) = &self.listing {
+            listing.dir.as_str()
+        } else if self.fp.is_empty() {
+            "[No Name]"
+        } else {
+            self.fp.as_str()
+        }
+    }
+
+    pub(crate) fn currRow(&self) -> &str {
+        self.content.get(self.curs_y as usize).map_or("", |row| row.as_str())
+    }
+
+    /*==============Editing===============*/
+    // every change to the text goes through here so it can be undone
+    pub(crate) fn replaceLines(&mut self, start: usize, end: usize, new: Vec<String>) {
+        let old: Vec<String> = self.content.splice(start..end, new.clone()).collect();
+        adjustMarks(&mut self.marks, start, old.len(), new.len());
+        if let Some(large) = self.large.as_mut() {
+            large.adjust(start, old.len(), new.len());
+        }
+        self.changes += 1;
+        if self.content.is_empty() {
+            self.content.push(String::new());
+        }
+        if old == new {
+            return;
+        }
+        self.undo.record(Edit { line: start, old, new }, (self.curs_x, self.curs_y));
+    }
+
+    pub(crate) fn setLine(&mut self, y: usize, text: String) {
+        self.replaceLines(y, y + 1, vec![text]);
+    }
+
+    // type `text` (without line breaks) in at the cursor
+    pub(crate) fn insertStr(&mut self, text: &str) {
+        let mut row = self.currRow().to_string();
+        row.insert_str(byteIdx(&row, self.curs_x), text);
+        self.setLine(self.curs_y as usize, row);
+        self.curs_x += lineLen(text);
+    }
+
+    pub(crate) fn insertChar(&mut self, c: char) {
+        let mut row = self.currRow().to_string();
+        row.insert(byteIdx(&row, self.curs_x), c);
+        self.setLine(self.curs_y as usize, row);
+        self.curs_x += 1;
+    }
+
+    // split the current line at the cursor
+    pub(crate) fn insertNewline(&mut self) {
+        let row = self.currRow().to_string();
+        let (before, after) = row.split_at(byteIdx(&row, self.curs_x));
+        let y = self.curs_y as usize;
+        self.replaceLines(y, y + 1, vec![before.to_string(), after.to_string()]);
+        self.curs_y += 1;
+        self.curs_x = 0;
+    }
+
+    // backspace - joins with the previous line at the start of a line
+    pub(crate) fn deleteCharBefore(&mut self) {
+        let y = self.curs_y as usize;
+        if self.curs_x > 0 {
+            let mut row = self.currRow().to_string();
+            row.remove(byteIdx(&row, self.curs_x - 1));
+            self.setLine(y, row);
+            self.curs_x -= 1;
+        } else if y > 0 {
+            let prev = self.content[y - 1].clone();
+            let joined = format!("{}{}", prev, self.currRow());
+            self.replaceLines(y - 1, y + 1, vec![joined]);
+            self.curs_y -= 1;
+            self.curs_x = lineLen(&prev);
+        }
+    }
+
+    // delete the character under the cursor
+    pub(crate) fn deleteChar(&mut self) {
+        let mut row = self.currRow().to_string();
+        if self.curs_x < lineLen(&row) {
+            row.remove(byteIdx(&row, self.curs_x));
+            self.setLine(self.curs_y as usize, row);
+        }
+    }
+
+    // start a new empty line below (or above) the cursor and move onto it
+    pub(crate) fn openLine(&mut self, below: bool) {
+        let y = if below { self.curs_y as usize + 1 } else { self.curs_y as usize };
+        self.replaceLines(y, y, vec![String::new()]);
+        self.curs_y = y as i32;
+        self.curs_x = 0;
+    }
+
+    pub(crate) fn clearLine(&mut self) {
+        self.setLine(self.curs_y as usize, String::new());
+        self.curs_x = 0;
+    }
+
+    /*==============Undo===============*/
+    fn applyEdits(&mut self, edits: &[Edit], reverse: bool) {
+        self.changes += 1;
+        if reverse {
+            for edit in edits.iter().rev() {
+                let end = edit.line + edit.new.len();
+                self.content.splice(edit.line..end, edit.old.clone());
+                adjustMarks(&mut self.marks, edit.line, edit.new.len(), edit.old.len());
+                if let Some(large) = self.large.as_mut() {
+                    large.adjust(edit.line, edit.new.len(), edit.old.len());
+                }
+            }
+        } else {
+            for edit in edits {
+                let end = edit.line + edit.old.len();
+                self.content.splice(edit.line..end, edit.new.clone());
+                adjustMarks(&mut self.marks, edit.line, edit.old.len(), edit.new.len());
+                if let Some(large) = self.large.as_mut() {
+                    large.adjust(edit.line, edit.old.len(), edit.new.len());
+                }
+            }
+        }
+        if self.content.is_empty() {
+            self.content.push(String::new());
+        }
+    }
+
+    pub(crate) fn undo(&mut self) -> bool {
+        match self.undo.popUndo() {
+            Some(step) => {
+                self.applyEdits(&step.edits, true);
+                self.curs_x = step.cursor.0;
+                self.curs_y = step.cursor.1;
+                self.clampCursor();
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub(crate) fn redo(&mut self) -> bool {
+        match self.undo.popRedo() {
+            Some(step) => {
+                self.applyEdits(&step.edits, false);
+                if let Some(edit) = step.edits.last() {
+                    self.curs_y = edit.line as i32;
+                    self.curs_x = 0;
+                }
+                self.clampCursor();
+                true
+            }
+            None => false,
+        }
+    }
+
+    // in normal mode the cursor sits on a character, never past the end of the line
+    pub(crate) fn clampNormal(&mut self) {
+        self.clampCursor();
+        let last = lineLen(self.currRow()) - 1;
+        self.curs_x = self.curs_x.min(last.max(0));
+    }
+
+    // keep the cursor inside the text after it changed underneath us
+    pub(crate) fn clampCursor(&mut self) {
+        let last = self.content.len() as i32 - 1;
+        self.curs_y = self.curs_y.clamp(0, last.max(0));
+        self.curs_x = self.curs_x.clamp(0, lineLen(self.currRow()));
+    }
+}
@@ -0,0 +1,116 @@
+//what --help prints
+pub(crate) const USAGE: &str = "\
+Usage: rustwrd [options] [file ...]
+
+Options:
+  +N            Start on line N of the first file
+  +             Start on the last line
+  +/pattern     Start on the first line matching pattern
+  -c command    Run a : command once the files are loaded (can be given more than once)
+  -R            Open the files read-only, to page through them
+  --            Everything after this is a file name
+  -h, --help    Show this help
+  --version     Show the version
+";
+
+// what to do with the editor, from the command line
+#[derive(Default, Debug)]
+pub(crate) struct Cli {
+    pub(crate) files: Vec<String>,
+    pub(crate) commands: Vec<String>, //: command lines run after loading, in the order they were given
+    pub(crate) readonly: bool,
+}
+
+// what the command line asks for
+#[derive(Debug)]
+pub(crate) enum Parsed {
+    Run(Cli),
+    Help,
+    Version,
+}
+
+// make sense of the arguments after the program name
+pub(crate) fn parseArgs(args: impl IntoIterator<Item = String>) -> Result<Parsed, String> {
+    let mut cli = Cli::default();
+    let mut args = args.into_iter();
+    let mut files_only = false;
+    while let Some(arg) = args.next() {
+        if files_only || arg == "-" || !(arg.starts_with('-') || arg.starts_with('+')) {
+            cli.files.push(arg);
+            continue;
+        }
+        match arg.as_str() {
+            "--" => files_only = true,
+            "-h" | "--help" => return Ok(Parsed::Help),
+            "--version" => return Ok(Parsed::Version),
+            "-R" | "--readonly" => cli.readonly = true,
+            "-c" => match args.next() {
+                Some(command) => cli.commands.push(command),
+                None => return Err(String::from("-c needs a command after it")),
+            },
+            "+" => cli.commands.push(String::from("$")),
+            _ => match arg.strip_prefix('+') {
+                Some(line) if line.chars().all(|c| c.is_ascii_digit()) => cli.commands.push(line.to_string()),
+                //the pattern goes in an address, so its slashes need escaping
+                Some(pattern) if pattern.starts_with('/') => {
+                    cli.commands.push(format!("/{}/", pattern[1..].replace('/', "\\/")))
+                }
+                Some(_) => return Err(format!("Bad start position: {}", arg)),
+                //-cCOMMAND works too
+                None => match arg.strip_prefix("-c") {
+                    Some(command) => cli.commands.push(command.to_string()),
+                    None => return Err(format!("Unknown option: {}", arg)),
+                },
+            },
+        }
+    }
+    Ok(Parsed::Run(cli))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Parsed, String> {
+        parseArgs(args.iter().map(|arg| arg.to_string()))
+    }
+
+    fn run(args: &[&str]) -> Cli {
+        match parse(args) {
+            Ok(Parsed::Run(cli)) => cli,
+            other => panic!("expected files to open, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn readsFilesAndOptions() {
+        let cli = run(&["a.txt", "-R", "-c", "set ts=4", "-cw", "b.txt"]);
+        assert_eq!(cli.files, ["a.txt", "b.txt"]);
+        assert_eq!(cli.commands, ["set ts=4", "w"]);
+        assert!(cli.readonly);
+        assert!(!run(&[]).readonly);
+    }
+
+    #[test]
+    fn readsStartPositions() {
+        assert_eq!(run(&["+12", "f"]).commands, ["12"]);
+        assert_eq!(run(&["+"]).commands, ["$"]);
+        assert_eq!(run(&["+/a/b"]).commands, ["/a\\/b/"]);
+        assert_eq!(parse(&["+x"]).unwrap_err(), "Bad start position: +x");
+    }
+
+    #[test]
+    fn dashesEndTheOptions() {
+        assert_eq!(run(&["--", "-R", "+5"]).files, ["-R", "+5"]);
+        assert_eq!(run(&["-"]).files, ["-"]);
+        assert_eq!(parse(&["-x"]).unwrap_err(), "Unknown option: -x");
+        assert_eq!(parse(&["-c"]).unwrap_err(), "-c needs a command after it");
+    }
+
+    #[test]
+    fn helpAndVersionWin() {
+        assert!(matches!(parse(&["a.txt", "--help", "-x"]), Ok(Parsed::Help)));
+        assert!(matches!(parse(&["-h"]), Ok(Parsed::Help)));
+        assert!(matches!(parse(&["--version"]), Ok(Parsed::Version)));
+    }
+}
@@ -0,0 +1,535 @@
+use crate::input::{
+    ARROW_DOWN, ARROW_LEFT, ARROW_RIGHT, ARROW_UP, BACKSPACE_KEY, CTRL_KEY, END_KEY, ENTER_KEY, HOME_KEY,
+};
+use crate::mark::isMarkName;
+use crate::motion::{FindChar, Motion, Pos};
+use crate::operator::{ForceKind, Operator};
+use crate::register::isRegisterName;
+use crate::textobject::{objectFromChar, TextObject};
+use crate::visual::VisualKind;
+
+// where i/a/I/A/o/O start inserting
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum InsertAt {
+    Cursor,    // i
+    After,     // a
+    LineStart, // I
+    LineEnd,   // A
+    LineBelow, // o
+    LineAbove, // O
+}
+
+// what an operator acts on
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum OpTarget {
+    Motion(Motion),
+    Object(TextObject),
+    Lines, //doubled operators like dd and >> work on whole lines
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum Action {
+    Move(Motion),
+    Insert(InsertAt),
+    Operate {
+        op: Operator,
+        force: Option<ForceKind>,
+        target: OpTarget,
+    },
+    ToggleCaseChar, // ~
+    Put { before: bool }, // p and P
+    Visual(VisualKind),   // v V and Ctrl-V
+    Reselect,             // gv
+    RepeatChange,         // .
+    RecordMacro(char),    // q and a register
+    PlayMacro(char),      // @ and a register, @@ plays the last one again
+    SetMark(char),        // m and a mark name
+    JumpOlder,            // Ctrl-O
+    JumpNewer,            // Ctrl-I (Tab)
+    Undo,
+    Redo,
+    CommandLine, // :
+}
+
+impl Action {
+    // does this change the text - those are what `.` repeats
+    pub(crate) fn isChange(self) -> bool {
+        match self {
+            Action::Operate { op, .. } => op != Operator::Yank,
+            Action::Insert(_) | Action::ToggleCaseChar | Action::Put { .. } => true,
+            _ => false,
+        }
+    }
+}
+
+// a complete normal mode command, like `3w` or `"a2yy`
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) struct NormalCmd {
+    pub(crate) count: Option<usize>,
+    pub(crate) register: Option<char>,
+    pub(crate) action: Action,
+}
+
+// what a key does in visual mode
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum VisualAction {
+    Move(Motion),
+    Object(TextObject),            // iw, a( ...
+    Operate(Operator),             // d y c > < ~ u U
+    OperateLines(Operator),        // D X Y C S R work on whole lines whatever the selection is
+    Switch(VisualKind),            // v V Ctrl-V
+    SwapEnds { same_line: bool },  // o and O
+    BlockInsert { append: bool },  // I and A in block mode
+    CommandLine,                   // :
+}
+
+impl VisualAction {
+    pub(crate) fn isChange(self) -> bool {
+        match self {
+            VisualAction::Operate(op) | VisualAction::OperateLines(op) => op != Operator::Yank,
+            VisualAction::BlockInsert { .. } => true,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) struct VisualCmd {
+    pub(crate) count: Option<usize>,
+    pub(crate) register: Option<char>,
+    pub(crate) action: VisualAction,
+}
+
+// how the last change gets done again
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum Repeat {
+    Normal(NormalCmd),
+    //visual changes work on the same amount of text starting at the cursor.
+    //`extent` is how far the selection reached - in lines, and in columns unless
+    //it was charwise over several lines, where x is the column it ended on
+    Visual {
+        cmd: VisualCmd,
+        kind: VisualKind,
+        extent: Pos,
+        to_eol: bool,
+    },
+}
+
+// the last change, so `.` can make it again
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub(crate) struct Change {
+    pub(crate) repeat: Repeat,
+    pub(crate) inserted: Vec<(i32, String)>, //keys typed in insert mode after the command, with the text each typed
+}
+
+// result of trying to make sense of the keys typed so far
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum Parse<T> {
+    Incomplete, //need more keys
+    Invalid,    //these keys don't mean anything - throw them away
+    Complete(T),
+}
+
+// the character a key types, if it's a plain one
+pub(crate) fn keyChar(key: i32) -> Option<char> {
+    if (0..256).contains(&key) {
+        Some(key as u8 as char)
+    } else {
+        None
+    }
+}
+
+//counts stop growing here, so they still fit in a cursor position
+pub(crate) const MAX_COUNT: usize = i32::MAX as usize;
+
+// leading count like the 12 in `12j`. A 0 on its own is a motion, not a count
+pub(crate) fn parseCount(keys: &[i32]) -> (Option<usize>, &[i32]) {
+    let mut count: Option<usize> = None;
+    let mut used = 0;
+    for key in keys {
+        match keyChar(*key) {
+            Some(c) if c.is_ascii_digit() && (c != '0' || count.is_some()) => {
+                let digit = c as usize - '0' as usize;
+                count = Some(count.unwrap_or(0).saturating_mul(10).saturating_add(digit).min(MAX_COUNT));
+                used += 1;
+            }
+            _ => break,
+        }
+    }
+    (count, &keys[used..])
+}
+
+// a motion at the start of `keys` along with how many keys it took up
+pub(crate) fn parseMotion(keys: &[i32]) -> Parse<(Motion, usize)> {
+    let key = match keys.first() {
+        Some(key) => *key,
+        None => return Parse::Incomplete,
+    };
+    let motion = match key {
+        ARROW_LEFT!() | BACKSPACE_KEY!() => Motion::Left,
+        ARROW_RIGHT!() => Motion::Right,
+        ARROW_UP!() => Motion::Up,
+        ARROW_DOWN!() | ENTER_KEY!() => Motion::Down,
+        HOME_KEY!() => Motion::LineStart,
+        END_KEY!() => Motion::LineEnd,
+        _ if key == CTRL_KEY!(b'n') as i32 || key == CTRL_KEY!(b'j') as i32 => Motion::Down,
+        _ => match keyChar(key) {
+            Some('h') => Motion::Left,
+            Some('l') | Some(' ') => Motion::Right,
+            Some('k') => Motion::Up,
+            Some('j') => Motion::Down,
+            Some('w') => Motion::WordForward { big: false },
+            Some('W') => Motion::WordForward { big: true },
+            Some('b') => Motion::WordBackward { big: false },
+            Some('B') => Motion::WordBackward { big: true },
+            Some('e') => Motion::WordEnd { big: false },
+            Some('E') => Motion::WordEnd { big: true },
+            Some('0') => Motion::LineStart,
+            Some('^') => Motion::FirstNonBlank,
+            Some('$') => Motion::LineEnd,
+            Some('G') => Motion::FileEnd,
+            Some('}') => Motion::ParagraphForward,
+            Some('{') => Motion::ParagraphBackward,
+            Some(';') => Motion::RepeatFind { reverse: false },
+            Some(',') => Motion::RepeatFind { reverse: true },
+            Some('%') => Motion::MatchBracket,
+            Some('g') => {
+                return match keys.get(1).copied().and_then(keyChar) {
+                    None if keys.len() == 1 => Parse::Incomplete,
+                    Some('g') => Parse::Complete((Motion::FileStart, 2)),
+                    _ => Parse::Invalid,
+                };
+            }
+            Some(c @ ('\'' | '`')) => {
+                return match keys.get(1).copied().and_then(keyChar) {
+                    None if keys.len() == 1 => Parse::Incomplete,
+                    Some(name) if isMarkName(name) || name == '<' || name == '>' => {
+                        Parse::Complete((Motion::ToMark { name, exact: c == '`' }, 2))
+                    }
+                    _ => Parse::Invalid,
+                };
+            }
+            Some(c @ ('f' | 't' | 'F' | 'T')) => {
+                let target = match keys.get(1) {
+                    Some(k) => keyChar(*k),
+                    None => return Parse::Incomplete,
+                };
+                return match target {
+                    Some(t) if t != '\x1b' => Parse::Complete((
+                        Motion::Find(FindChar {
+                            c: t,
+                            forward: c == 'f' || c == 't',
+                            till: c == 't' || c == 'T',
+                        }),
+                        2,
+                    )),
+                    _ => Parse::Invalid,
+                };
+            }
+            _ => return Parse::Invalid,
+        },
+    };
+    Parse::Complete((motion, 1))
+}
+
+// an operator at the start of `keys` along with how many keys it took up
+fn parseOperator(keys: &[i32]) -> Parse<(Operator, usize)> {
+    match keys.first().copied().and_then(keyChar) {
+        Some('d') => Parse::Complete((Operator::Delete, 1)),
+        Some('c') => Parse::Complete((Operator::Change, 1)),
+        Some('y') => Parse::Complete((Operator::Yank, 1)),
+        Some('>') => Parse::Complete((Operator::Indent, 1)),
+        Some('<') => Parse::Complete((Operator::Dedent, 1)),
+        Some('g') => match keys.get(1).copied().and_then(keyChar) {
+            None if keys.len() == 1 => Parse::Incomplete,
+            Some('u') => Parse::Complete((Operator::Lowercase, 2)),
+            Some('U') => Parse::Complete((Operator::Uppercase, 2)),
+            Some('~') => Parse::Complete((Operator::ToggleCase, 2)),
+            _ => Parse::Invalid,
+        },
+        _ => Parse::Invalid,
+    }
+}
+
+// multiply two optional counts together, like the 2 and 3 in `2d3w`
+fn combineCounts(a: Option<usize>, b: Option<usize>) -> Option<usize> {
+    match (a, b) {
+        (None, None) => None,
+        (a, b) => Some(a.unwrap_or(1).saturating_mul(b.unwrap_or(1)).min(MAX_COUNT)),
+    }
+}
+
+// everything after an operator: [v|V|Ctrl-V] [count] (motion | text object | the operator again)
+fn parseOperatorTarget(op: Operator, op_keys: &[i32], keys: &[i32]) -> Parse<(Option<ForceKind>, Option<usize>, OpTarget)> {
+    let (force, keys) = match keys.first().copied() {
+        Some(k) if k == b'v' as i32 => (Some(ForceKind::Charwise), &keys[1..]),
+        Some(k) if k == b'V' as i32 => (Some(ForceKind::Linewise), &keys[1..]),
+        Some(k) if k == CTRL_KEY!(b'v') as i32 => (Some(ForceKind::Blockwise), &keys[1..]),
+        _ => (None, keys),
+    };
+    let (count, keys) = parseCount(keys);
+    if keys.is_empty() {
+        return Parse::Incomplete;
+    }
+
+    //doubled operator - gu can be doubled as guu or gugu
+    let doubled = keys.starts_with(op_keys) || (op_keys.len() == 2 && keys.first() == op_keys.get(1));
+    if doubled {
+        return Parse::Complete((force, count, OpTarget::Lines));
+    }
+    if op_keys.len() == 2 && keys == &op_keys[..1] {
+        return Parse::Incomplete; //could still be gugu
+    }
+
+    if let Some(c @ ('i' | 'a')) = keyChar(keys[0]) {
+        return match keys.get(1).copied().and_then(keyChar) {
+            None => Parse::Incomplete,
+            Some(o) => match objectFromChar(o, c == 'i') {
+                Some(object) => Parse::Complete((force, count, OpTarget::Object(object))),
+                None => Parse::Invalid,
+            },
+        };
+    }
+
+    match parseMotion(keys) {
+        Parse::Complete((motion, _)) => {
+            //cw on a word acts like ce, which is what everyone means by it
+            let motion = match (op, motion) {
+                (Operator::Change, Motion::WordForward { big }) => Motion::WordEnd { big },
+                _ => motion,
+            };
+            Parse::Complete((force, count, OpTarget::Motion(motion)))
+        }
+        Parse::Incomplete => Parse::Incomplete,
+        Parse::Invalid => Parse::Invalid,
+    }
+}
+
+// shorthand commands that are really an operator and a motion
+fn parseShorthand(c: char) -> Option<(Operator, OpTarget)> {
+    match c {
+        'D' => Some((Operator::Delete, OpTarget::Motion(Motion::LineEnd))),
+        'C' => Some((Operator::Change, OpTarget::Motion(Motion::LineEnd))),
+        'Y' => Some((Operator::Yank, OpTarget::Lines)),
+        'x' => Some((Operator::Delete, OpTarget::Motion(Motion::Right))),
+        'X' => Some((Operator::Delete, OpTarget::Motion(Motion::Left))),
+        's' => Some((Operator::Change, OpTarget::Motion(Motion::Right))),
+        'S' => Some((Operator::Change, OpTarget::Lines)),
+        _ => None,
+    }
+}
+
+// count and register that can come before a command, like the 2"a in `2"ayy`.
+// The register can go before or after the count
+fn parsePrefix(keys: &[i32]) -> Parse<(Option<usize>, Option<char>, &[i32])> {
+    let (count, rest) = parseCount(keys);
+    let (register, rest) = match rest.first().copied().and_then(keyChar) {
+        Some('"') => match rest.get(1).copied().and_then(keyChar) {
+            None if rest.len() == 1 => return Parse::Incomplete,
+            Some(c) if isRegisterName(c) => (Some(c), &rest[2..]),
+            _ => return Parse::Invalid,
+        },
+        _ => (None, rest),
+    };
+    let (count2, rest) = parseCount(rest);
+    Parse::Complete((combineCounts(count, count2), register, rest))
+}
+
+// turn the keys typed in normal mode into a command
+pub(crate) fn parseNormal(keys: &[i32]) -> Parse<NormalCmd> {
+    let (count, register, rest) = match parsePrefix(keys) {
+        Parse::Complete(prefix) => prefix,
+        Parse::Incomplete => return Parse::Incomplete,
+        Parse::Invalid => return Parse::Invalid,
+    };
+    let key = match rest.first() {
+        Some(key) => *key,
+        None => return Parse::Incomplete,
+    };
+
+    if let Some((op, target)) = keyChar(key).and_then(parseShorthand) {
+        return Parse::Complete(NormalCmd { count, register, action: Action::Operate { op, force: None, target } });
+    }
+    match parseOperator(rest) {
+        Parse::Complete((op, used)) => {
+            return match parseOperatorTarget(op, &rest[..used], &rest[used..]) {
+                Parse::Complete((force, count2, target)) => Parse::Complete(NormalCmd {
+                    count: combineCounts(count, count2),
+                    register,
+                    action: Action::Operate { op, force, target },
+                }),
+                Parse::Incomplete => Parse::Incomplete,
+                Parse::Invalid => Parse::Invalid,
+            };
+        }
+        Parse::Incomplete => {
+            //a lone g could still turn into gg
+            if rest.len() == 1 {
+                return Parse::Incomplete;
+            }
+        }
+        Parse::Invalid => {}
+    }
+
+    let action = if key == CTRL_KEY!(b'r') as i32 {
+        Some(Action::Redo)
+    } else if key == CTRL_KEY!(b'v') as i32 {
+        Some(Action::Visual(VisualKind::Block))
+    } else if rest == [b'g' as i32, b'v' as i32] {
+        Some(Action::Reselect)
+    } else if key == CTRL_KEY!(b'o') as i32 {
+        Some(Action::JumpOlder)
+    } else if key == CTRL_KEY!(b'i') as i32 {
+        Some(Action::JumpNewer)
+    } else if key == b'm' as i32 {
+        match rest.get(1).copied().and_then(keyChar) {
+            Some(name) if isMarkName(name) => Some(Action::SetMark(name)),
+            None if rest.len() == 1 => return Parse::Incomplete,
+            _ => return Parse::Invalid,
+        }
+    } else if key == b'q' as i32 || key == b'@' as i32 {
+        let name = match rest.get(1).copied().and_then(keyChar) {
+            Some(name) => name,
+            None if rest.len() == 1 => return Parse::Incomplete,
+            None => return Parse::Invalid,
+        };
+        match (key == b'q' as i32, name) {
+            (true, c) if c.is_ascii_alphabetic() => Some(Action::RecordMacro(c)),
+            (false, c) if c == '@' || isRegisterName(c) => Some(Action::PlayMacro(c)),
+            _ => return Parse::Invalid,
+        }
+    } else {
+        match keyChar(key) {
+            Some('i') => Some(Action::Insert(InsertAt::Cursor)),
+            Some('a') => Some(Action::Insert(InsertAt::After)),
+            Some('I') => Some(Action::Insert(InsertAt::LineStart)),
+            Some('A') => Some(Action::Insert(InsertAt::LineEnd)),
+            Some('o') => Some(Action::Insert(InsertAt::LineBelow)),
+            Some('O') => Some(Action::Insert(InsertAt::LineAbove)),
+            Some('~') => Some(Action::ToggleCaseChar),
+            Some('p') => Some(Action::Put { before: false }),
+            Some('P') => Some(Action::Put { before: true }),
+            Some('u') => Some(Action::Undo),
+            Some(':') => Some(Action::CommandLine),
+            Some('.') => Some(Action::RepeatChange),
+            Some('v') => Some(Action::Visual(VisualKind::Char)),
+            Some('V') => Some(Action::Visual(VisualKind::Line)),
+            _ => None,
+        }
+    };
+    if let Some(action) = action {
+        return Parse::Complete(NormalCmd { count, register, action });
+    }
+    match parseMotion(rest) {
+        Parse::Complete((motion, _)) => Parse::Complete(NormalCmd { count, register, action: Action::Move(motion) }),
+        Parse::Incomplete => Parse::Incomplete,
+        Parse::Invalid => Parse::Invalid,
+    }
+}
+
+// turn the keys typed in visual mode into a command
+pub(crate) fn parseVisual(keys: &[i32]) -> Parse<VisualCmd> {
+    let (count, register, rest) = match parsePrefix(keys) {
+        Parse::Complete(prefix) => prefix,
+        Parse::Incomplete => return Parse::Incomplete,
+        Parse::Invalid => return Parse::Invalid,
+    };
+    let key = match rest.first() {
+        Some(key) => *key,
+        None => return Parse::Incomplete,
+    };
+    let complete = |action| Parse::Complete(VisualCmd { count, register, action });
+
+    if key == CTRL_KEY!(b'v') as i32 {
+        return complete(VisualAction::Switch(VisualKind::Block));
+    }
+    let action = match keyChar(key) {
+        Some('v') => VisualAction::Switch(VisualKind::Char),
+        Some('V') => VisualAction::Switch(VisualKind::Line),
+        Some('o') => VisualAction::SwapEnds { same_line: false },
+        Some('O') => VisualAction::SwapEnds { same_line: true },
+        Some('d' | 'x') => VisualAction::Operate(Operator::Delete),
+        Some('y') => VisualAction::Operate(Operator::Yank),
+        Some('c' | 's') => VisualAction::Operate(Operator::Change),
+        Some('>') => VisualAction::Operate(Operator::Indent),
+        Some('<') => VisualAction::Operate(Operator::Dedent),
+        Some('~') => VisualAction::Operate(Operator::ToggleCase),
+        Some('u') => VisualAction::Operate(Operator::Lowercase),
+        Some('U') => VisualAction::Operate(Operator::Uppercase),
+        Some('D' | 'X') => VisualAction::OperateLines(Operator::Delete),
+        Some('Y') => VisualAction::OperateLines(Operator::Yank),
+        Some('C' | 'S' | 'R') => VisualAction::OperateLines(Operator::Change),
+        Some('I') => VisualAction::BlockInsert { append: false },
+        Some('A') => VisualAction::BlockInsert { append: true },
+        Some(':') => VisualAction::CommandLine,
+        Some(c @ ('i' | 'a')) => {
+            return match rest.get(1).copied().and_then(keyChar) {
+                None => Parse::Incomplete,
+                Some(o) => match objectFromChar(o, c == 'i') {
+                    Some(object) => complete(VisualAction::Object(object)),
+                    None => Parse::Invalid,
+                },
+            };
+        }
+        //g is either a case operator or gg
+        Some('g') => match rest.get(1).copied().and_then(keyChar) {
+            None => return Parse::Incomplete,
+            Some('u') => VisualAction::Operate(Operator::Lowercase),
+            Some('U') => VisualAction::Operate(Operator::Uppercase),
+            Some('~') => VisualAction::Operate(Operator::ToggleCase),
+            _ => match parseMotion(rest) {
+                Parse::Complete((motion, _)) => VisualAction::Move(motion),
+                _ => return Parse::Invalid,
+            },
+        },
+        _ => match parseMotion(rest) {
+            Parse::Complete((motion, _)) => VisualAction::Move(motion),
+            Parse::Incomplete => return Parse::Incomplete,
+            Parse::Invalid => return Parse::Invalid,
+        },
+    };
+    complete(action)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(text: &str) -> Vec<i32> {
+        text.bytes().map(|b| b as i32).collect()
+    }
+
+    #[test]
+    fn readsCounts() {
+        let typed = keys("12j");
+        assert_eq!(parseCount(&typed), (Some(12), &typed[2..]));
+        //a 0 on its own is a motion
+        let typed = keys("0");
+        assert_eq!(parseCount(&typed), (None, &typed[..]));
+        let typed = keys("10");
+        assert_eq!(parseCount(&typed).0, Some(10));
+    }
+
+    #[test]
+    fn countsStopGrowing() {
+        let typed = keys("99999999999999999999999j");
+        assert_eq!(parseCount(&typed).0, Some(MAX_COUNT));
+        assert_eq!(combineCounts(Some(MAX_COUNT), Some(3)), Some(MAX_COUNT));
+        assert_eq!(combineCounts(None, Some(3)), Some(3));
+        assert_eq!(combineCounts(Some(2), Some(3)), Some(6));
+        assert_eq!(combineCounts(None, None), None);
+    }
+
+    #[test]
+    fn readsMotions() {
+        assert_eq!(parseMotion(&keys("w")), Parse::Complete((Motion::WordForward { big: false }, 1)));
+        assert_eq!(parseMotion(&keys("g")), Parse::Incomplete);
+        assert_eq!(parseMotion(&keys("gg")), Parse::Complete((Motion::FileStart, 2)));
+        assert_eq!(parseMotion(&keys("gx")), Parse::Invalid);
+        assert_eq!(parseMotion(&keys("`a")), Parse::Complete((Motion::ToMark { name: 'a', exact: true }, 2)));
+        let find = FindChar { c: 'x', forward: false, till: true };
+        assert_eq!(parseMotion(&keys("Tx")), Parse::Complete((Motion::Find(find), 2)));
+        assert_eq!(parseMotion(&keys("f\x1b")), Parse::Invalid);
+        assert_eq!(parseMotion(&[]), Parse::Incomplete);
+    }
+}
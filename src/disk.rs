@@ -0,0 +1,135 @@
+use crate::buffer::Buffer;
+use crate::encoding::Encoding;
+use crate::input::editorChoose;
+use crate::swap::{diffLines, editorShowDiff};
+use crate::undo::bytesHash;
+use crate::Terminal;
+
+use std::fs;
+use std::io;
+use std::io::Error;
+use std::io::ErrorKind::Other;
+use std::time::SystemTime;
+
+//asks the terminal to tell us when its window gains or loses focus, and to stop again
+pub(crate) const FOCUS_EVENTS_ON: &[u8] = b"\x1b[?1004h";
+pub(crate) const FOCUS_EVENTS_OFF: &[u8] = b"\x1b[?1004l";
+//what it sends when it does
+pub(crate) const FOCUS_IN: &[u8] = b"\x1b[I";
+pub(crate) const FOCUS_OUT: &[u8] = b"\x1b[O";
+
+// what a file on disk looked like at some point - to tell when another program changes it
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) struct DiskStamp {
+    modified: Option<SystemTime>,
+    size: u64,
+    hash: u64,
+}
+
+// the file as it is now - None if it isn't there or can't be read
+pub(crate) fn diskStamp(fp: &str) -> Option<DiskStamp> {
+    let meta = fs::metadata(fp).ok()?;
+    let bytes = fs::read(fp).ok()?;
+    Some(DiskStamp { modified: meta.modified().ok(), size: meta.len(), hash: bytesHash(&bytes) })
+}
+
+// the file as it is now, if it's different from when the buffer last read or wrote it.
+// A file that was only touched counts as the same one
+fn diskChange(buffer: &mut Buffer) -> Option<DiskStamp> {
+    //a large file would take too long to read every time
+    if buffer.fp.is_empty() || buffer.large.is_some() {
+        return None;
+    }
+    //a file that's been deleted will just be written again
+    let meta = fs::metadata(&buffer.fp).ok()?;
+    if let Some(known) = buffer.disk {
+        if meta.len() == known.size && meta.modified().ok() == known.modified {
+            return None; //no need to read it
+        }
+    }
+    let now = diskStamp(&buffer.fp)?;
+    match buffer.disk {
+        Some(known) if known.hash == now.hash => {
+            buffer.disk = Some(now);
+            None
+        }
+        _ => Some(now),
+    }
+}
+
+// diff from the buffer's text to what's in the file now
+fn diskDiff(buffer: &Buffer, legacy: Encoding) -> io::Result<Vec<String>> {
+    let on_disk = Buffer::open(&buffer.fp, legacy)?;
+    let (old, new) = (format!("{} (buffer)", buffer.fp), format!("{} (on disk)", buffer.fp));
+    Ok(diffLines(&old, &new, &buffer.content, &on_disk.content))
+}
+
+// ask about every buffer whose file another program has changed - reload it, keep the buffer as it is,
+// or look at the differences first. Returns true if anything was asked
+pub(crate) fn editorCheckDisk(terminal: &mut Terminal) -> io::Result<bool> {
+    let mut asked = false;
+    for buf in 0..terminal.buffers.len() {
+        let now = match diskChange(&mut terminal.buffers[buf]) {
+            Some(now) if terminal.buffers[buf].disk_kept != Some(now) => now,
+            _ => continue,
+        };
+        asked = true;
+        let buffer = &terminal.buffers[buf];
+        let unsaved = if buffer.isDirty() { " (the buffer has unsaved changes)" } else { "" };
+        let msg = format!("\"{}\" changed on disk{}: [r]eload, [k]eep the buffer or di[f]f?", buffer.fp, unsaved);
+        let mut shown_diff = false;
+        loop {
+            match editorChoose(terminal, &msg, "rkf")? {
+                Some('r') => {
+                    let legacy = terminal.options.legacyencoding;
+                    terminal.buffers[buf].reload(legacy)?;
+                    let fp = &terminal.buffers[buf].fp;
+                    let msg = format!("\"{}\" reloaded - u takes the buffer back to what it was", fp);
+                    terminal.setMessage(&msg);
+                    break;
+                }
+                Some('f') if !shown_diff => {
+                    shown_diff = true;
+                    let diff = diskDiff(&terminal.buffers[buf], terminal.options.legacyencoding)?;
+                    editorShowDiff(terminal, diff)?;
+                }
+                Some('f') => {}
+                _ => {
+                    //not asked again until the file changes again
+                    terminal.buffers[buf].disk_kept = Some(now);
+                    break;
+                }
+            }
+        }
+    }
+    Ok(asked)
+}
+
+// before writing the current buffer - if another program changed the file since it was read, ask before
+// overwriting that. Errors if the user backs out
+pub(crate) fn editorCheckOverwrite(terminal: &mut Terminal) -> io::Result<()> {
+    let buf = terminal.currBuf();
+    let now = match diskChange(&mut terminal.buffers[buf]) {
+        //already told about it and chose to keep the buffer
+        Some(now) if terminal.buffers[buf].disk_kept != Some(now) => now,
+        _ => return Ok(()),
+    };
+    let fp = &terminal.buffers[buf].fp;
+    let msg = format!("\"{}\" changed on disk since it was read: [o]verwrite it, di[f]f or [c]ancel?", fp);
+    let mut shown_diff = false;
+    loop {
+        match editorChoose(terminal, &msg, "ofc")? {
+            Some('o') => {
+                terminal.buffers[buf].disk_kept = Some(now);
+                return Ok(());
+            }
+            Some('f') if !shown_diff => {
+                shown_diff = true;
+                let diff = diskDiff(&terminal.buffers[buf], terminal.options.legacyencoding)?;
+                editorShowDiff(terminal, diff)?;
+            }
+            Some('f') => {}
+            _ => return Err(Error::new(Other, "Not written")),
+        }
+    }
+}
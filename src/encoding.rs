@@ -0,0 +1,251 @@
+use crate::hex::isBinary;
+
+use std::io;
+use std::io::Error;
+use std::io::ErrorKind::Other;
+
+//byte order marks at the start of a file
+const UTF8_BOM: &[u8] = b"\xef\xbb\xbf";
+const UTF16LE_BOM: &[u8] = b"\xff\xfe";
+const UTF16BE_BOM: &[u8] = b"\xfe\xff";
+
+//what windows-1252 has in 0x80-0x9f, where latin1 has control characters - the five it leaves out stay those
+const CP1252_HIGH: [char; 32] = [
+    '\u{20ac}', '\u{81}', '\u{201a}', '\u{192}', '\u{201e}', '\u{2026}', '\u{2020}', '\u{2021}', '\u{2c6}',
+    '\u{2030}', '\u{160}', '\u{2039}', '\u{152}', '\u{8d}', '\u{17d}', '\u{8f}', '\u{90}', '\u{2018}', '\u{2019}',
+    '\u{201c}', '\u{201d}', '\u{2022}', '\u{2013}', '\u{2014}', '\u{2dc}', '\u{2122}', '\u{161}', '\u{203a}',
+    '\u{153}', '\u{9d}', '\u{17e}', '\u{178}',
+];
+
+// how the text of a file is stored as bytes
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum Encoding {
+    Utf8,
+    Utf8Bom, //UTF-8 that starts with a byte order mark
+    Utf16Le,
+    Utf16Be,
+    Latin1,
+    Cp1252,
+}
+
+// each encoding with its name and the other names it answers to
+const NAMES: [(Encoding, &str, &[&str]); 6] = [
+    (Encoding::Utf8, "utf-8", &["utf8"]),
+    (Encoding::Utf8Bom, "utf-8-bom", &["utf8-bom"]),
+    (Encoding::Utf16Le, "utf-16le", &["utf16le", "utf-16", "utf16"]),
+    (Encoding::Utf16Be, "utf-16be", &["utf16be"]),
+    (Encoding::Latin1, "latin1", &["latin-1", "iso-8859-1", "iso8859-1"]),
+    (Encoding::Cp1252, "cp1252", &["windows-1252"]),
+];
+
+impl Encoding {
+    pub(crate) fn name(self) -> &'static str {
+        NAMES.iter().find(|(encoding, _, _)| *encoding == self).map_or("", |(_, name, _)| name)
+    }
+
+    pub(crate) fn fromName(name: &str) -> Option<Encoding> {
+        let name = name.to_ascii_lowercase();
+        let found = NAMES.iter().find(|(_, full, others)| *full == name || others.contains(&name.as_str()));
+        found.map(|(encoding, _, _)| *encoding)
+    }
+
+    // one byte per character - what a file that isn't UTF-8 is read as
+    pub(crate) fn isLegacy(self) -> bool {
+        matches!(self, Encoding::Latin1 | Encoding::Cp1252)
+    }
+
+    fn bom(self) -> &'static [u8] {
+        match self {
+            Encoding::Utf8Bom => UTF8_BOM,
+            Encoding::Utf16Le => UTF16LE_BOM,
+            Encoding::Utf16Be => UTF16BE_BOM,
+            _ => b"",
+        }
+    }
+}
+
+// how the lines of a file end - kept so the file is written back the way it was read
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) struct LineEnds {
+    pub(crate) crlf: bool,
+    pub(crate) final_newline: bool, //the last line has a line ending too
+}
+
+impl LineEnds {
+    //what a new file gets
+    pub(crate) const UNIX: LineEnds = LineEnds { crlf: false, final_newline: true };
+
+    // the line endings of a file's text - the first line ending says which kind they all are
+    pub(crate) fn of(text: &str) -> LineEnds {
+        let crlf = text.find('\n').is_some_and(|i| text[..i].ends_with('\r'));
+        LineEnds { crlf, final_newline: text.ends_with('\n') }
+    }
+
+    fn ending(self) -> &'static str {
+        if self.crlf {
+            "\r\n"
+        } else {
+            "\n"
+        }
+    }
+}
+
+// a file that starts with a UTF-16 byte order mark
+pub(crate) fn isUtf16(bytes: &[u8]) -> bool {
+    bytes.starts_with(UTF16LE_BOM) || bytes.starts_with(UTF16BE_BOM)
+}
+
+fn decodeUtf16(bytes: &[u8], little_endian: bool) -> Option<String> {
+    if !bytes.len().is_multiple_of(2) {
+        return None;
+    }
+    let unit = |pair: &[u8]| {
+        let pair = [pair[0], pair[1]];
+        if little_endian {
+            u16::from_le_bytes(pair)
+        } else {
+            u16::from_be_bytes(pair)
+        }
+    };
+    let units: Vec<u16> = bytes.chunks(2).map(unit).collect();
+    String::from_utf16(&units).ok()
+}
+
+fn decodeLegacy(bytes: &[u8], legacy: Encoding) -> String {
+    bytes
+        .iter()
+        .map(|b| match (legacy, b) {
+            (Encoding::Cp1252, 0x80..=0x9f) => CP1252_HIGH[*b as usize - 0x80],
+            _ => *b as char,
+        })
+        .collect()
+}
+
+// the text of a file and the encoding it was in - a byte order mark says which, otherwise it's UTF-8 if it can
+// be and `legacy` if not. None for a binary file
+pub(crate) fn decode(bytes: &[u8], legacy: Encoding) -> Option<(String, Encoding)> {
+    if let Some(rest) = bytes.strip_prefix(UTF16LE_BOM) {
+        return decodeUtf16(rest, true).map(|text| (text, Encoding::Utf16Le));
+    }
+    if let Some(rest) = bytes.strip_prefix(UTF16BE_BOM) {
+        return decodeUtf16(rest, false).map(|text| (text, Encoding::Utf16Be));
+    }
+    if isBinary(bytes) {
+        return None;
+    }
+    if let Some(rest) = bytes.strip_prefix(UTF8_BOM) {
+        if let Ok(text) = std::str::from_utf8(rest) {
+            return Some((text.to_string(), Encoding::Utf8Bom));
+        }
+    }
+    match std::str::from_utf8(bytes) {
+        Ok(text) => Some((text.to_string(), Encoding::Utf8)),
+        Err(_) => Some((decodeLegacy(bytes, legacy), legacy)),
+    }
+}
+
+// add a character in `encoding` - false if it has no way of storing it
+fn encodeChar(c: char, encoding: Encoding, out: &mut Vec<u8>) -> bool {
+    match encoding {
+        Encoding::Utf8 | Encoding::Utf8Bom => out.extend(c.encode_utf8(&mut [0; 4]).as_bytes()),
+        Encoding::Utf16Le => c.encode_utf16(&mut [0; 2]).iter().for_each(|u| out.extend(u.to_le_bytes())),
+        Encoding::Utf16Be => c.encode_utf16(&mut [0; 2]).iter().for_each(|u| out.extend(u.to_be_bytes())),
+        Encoding::Cp1252 if ('\u{80}'..='\u{9f}').contains(&c) && !CP1252_HIGH.contains(&c) => return false,
+        Encoding::Cp1252 if !c.is_ascii() && CP1252_HIGH.contains(&c) => {
+            out.push(0x80 + CP1252_HIGH.iter().position(|h| *h == c).unwrap() as u8)
+        }
+        Encoding::Latin1 | Encoding::Cp1252 if (c as u32) < 0x100 => out.push(c as u8),
+        Encoding::Latin1 | Encoding::Cp1252 => return false,
+    }
+    true
+}
+
+// the bytes lines are saved as, ending the way `ends` says - errors on the first character `encoding` can't store
+pub(crate) fn encodeLines(lines: &[String], encoding: Encoding, ends: LineEnds) -> io::Result<Vec<u8>> {
+    let mut bytes = encoding.bom().to_vec();
+    for (i, line) in lines.iter().enumerate() {
+        for c in line.chars() {
+            if !encodeChar(c, encoding, &mut bytes) {
+                let msg = format!("Line {} has '{}', which {} can't store", i + 1, c, encoding.name());
+                return Err(Error::new(Other, msg));
+            }
+        }
+        if i + 1 < lines.len() || ends.final_newline {
+            ends.ending().chars().for_each(|c| _ = encodeChar(c, encoding, &mut bytes));
+        }
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::lines;
+
+    const DOS: LineEnds = LineEnds { crlf: true, final_newline: false };
+
+    #[test]
+    fn detectsEncodings() {
+        let decoded = |bytes: &[u8]| decode(bytes, Encoding::Latin1);
+        assert_eq!(decoded("héllo".as_bytes()), Some((String::from("héllo"), Encoding::Utf8)));
+        assert_eq!(decoded(b"\xef\xbb\xbfhi"), Some((String::from("hi"), Encoding::Utf8Bom)));
+        assert_eq!(decoded(b"\xff\xfeh\x00i\x00"), Some((String::from("hi"), Encoding::Utf16Le)));
+        assert_eq!(decoded(b"\xfe\xff\x00h\x00i"), Some((String::from("hi"), Encoding::Utf16Be)));
+        assert_eq!(decoded(b"caf\xe9"), Some((String::from("café"), Encoding::Latin1)));
+        assert_eq!(decoded(b"a\x00b"), None);
+        //half a UTF-16 character isn't text
+        assert_eq!(decoded(b"\xff\xfeh"), None);
+    }
+
+    #[test]
+    fn cp1252HasExtraCharacters() {
+        assert_eq!(decode(b"\x80 \x93x\x94", Encoding::Cp1252), Some((String::from("€ “x”"), Encoding::Cp1252)));
+        assert_eq!(decode(b"\x80", Encoding::Latin1), Some((String::from("\u{80}"), Encoding::Latin1)));
+        assert_eq!(encodeLines(&lines(&["€"]), Encoding::Cp1252, DOS).unwrap(), b"\x80");
+        //the five bytes windows-1252 leaves out stay as they were
+        assert_eq!(encodeLines(&lines(&["\u{81}"]), Encoding::Cp1252, DOS).unwrap(), b"\x81");
+        assert!(encodeLines(&lines(&["\u{80}"]), Encoding::Cp1252, DOS).is_err());
+    }
+
+    #[test]
+    fn linesRoundTrip() {
+        let text = lines(&["héllo", "", "wörld"]);
+        for encoding in NAMES.map(|(encoding, _, _)| encoding) {
+            let bytes = encodeLines(&text, encoding, LineEnds::UNIX).unwrap();
+            assert!(bytes.starts_with(encoding.bom()));
+            let (decoded, found) = decode(&bytes, encoding).unwrap();
+            assert_eq!(decoded.lines().collect::<Vec<_>>(), text, "{}", encoding.name());
+            //latin1 and cp1252 can't be told apart from the bytes
+            assert_eq!(found.isLegacy(), encoding.isLegacy());
+        }
+    }
+
+    #[test]
+    fn lineEndsRoundTrip() {
+        for text in ["a\nb\n", "a\nb", "a\r\nb\r\n", "a\r\n\r\nb", "\n", ""] {
+            let ends = LineEnds::of(text);
+            let bytes = encodeLines(&lines(&text.lines().collect::<Vec<_>>()), Encoding::Utf8, ends).unwrap();
+            assert_eq!(String::from_utf8(bytes).unwrap(), text);
+        }
+        assert_eq!(LineEnds::of("a\r\nb\n"), LineEnds { crlf: true, final_newline: true });
+        assert_eq!(encodeLines(&lines(&["a", "b"]), Encoding::Utf16Le, DOS).unwrap(), b"\xff\xfea\x00\r\x00\n\x00b\x00");
+    }
+
+    #[test]
+    fn refusesWhatCantBeStored() {
+        let error = encodeLines(&lines(&["ok", "a€b"]), Encoding::Latin1, LineEnds::UNIX).unwrap_err();
+        assert_eq!(error.to_string(), "Line 2 has '€', which latin1 can't store");
+        assert!(encodeLines(&lines(&["😀"]), Encoding::Utf16Be, LineEnds::UNIX).is_ok());
+    }
+
+    #[test]
+    fn namesAndAliases() {
+        assert_eq!(Encoding::fromName("UTF-16"), Some(Encoding::Utf16Le));
+        assert_eq!(Encoding::fromName("windows-1252"), Some(Encoding::Cp1252));
+        assert_eq!(Encoding::fromName("ebcdic"), None);
+        for (encoding, name, _) in NAMES {
+            assert_eq!(Encoding::fromName(name), Some(encoding));
+            assert_eq!(encoding.name(), name);
+        }
+    }
+}
@@ -0,0 +1,902 @@
+use crate::browse::{currentDir, editorBrowse};
+use crate::buffer::{writeBytes, writeLines, Buffer};
+use crate::disk::editorCheckOverwrite;
+use crate::encoding::Encoding;
+use crate::finder::editorFindFile;
+use crate::hex::parseHex;
+use crate::large::{editorLargeGoto, reopenLarge, writeLargeTo};
+use crate::motion::firstNonBlank;
+use crate::operator::RangeKind;
+use crate::pattern::Pattern;
+use crate::register::{isRegisterName, Register};
+use crate::theme::Theme;
+use crate::undo::loadUndo;
+use crate::window::SplitDir;
+use crate::Terminal;
+
+use std::io;
+use std::io::Error;
+use std::io::ErrorKind::Other;
+use std::path::Path;
+
+/*==============Command line AST===============*/
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub(crate) enum Address {
+    Current,                                   // .
+    Last,                                      // $
+    Line(usize),                               // 12
+    Mark(char),                                // 'a
+    Search { pattern: String, forward: bool }, // /pat/ or ?pat?
+}
+
+// an address plus any +n/-n after it
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub(crate) struct LineSpec {
+    pub(crate) base: Address,
+    pub(crate) offset: i64,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub(crate) enum LineRange {
+    Default, //no range given - every command picks its own
+    Whole,   // %
+    One(LineSpec),
+    Two(LineSpec, LineSpec),
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub(crate) enum ExCommand {
+    Goto(LineRange), //just a range, like :12
+    Write { range: LineRange, file: Option<String>, force: bool },
+    WriteQuit { force: bool, only_if_modified: bool }, // :wq and :x
+    Quit { force: bool, all: bool },
+    Edit { file: Option<String>, force: bool },
+    SaveAs { file: String, force: bool },
+    Delete { range: LineRange, register: Option<char> },
+    Yank { range: LineRange, register: Option<char> },
+    Move { range: LineRange, dest: LineSpec },
+    Copy { range: LineRange, dest: LineSpec },
+    Set { args: Vec<String>, local: bool },
+    Substitute { range: LineRange, pattern: String, replacement: String, global: bool, ignore_case: bool },
+    BufferNext,
+    BufferPrev,
+    BufferList,
+    BufferGo(usize),
+    BufferDelete { force: bool },
+    Split { dir: SplitDir, file: Option<String> },
+    New, //split with an empty buffer in the new window
+    Close,
+    Only,
+    Convert(Option<String>), //write the file in another encoding from now on
+    Explore(Option<String>), //list a directory
+    Find(String),            //fuzzy find a file, starting with this query
+}
+
+impl ExCommand {
+    // does this change the buffer's text
+    fn isChange(&self) -> bool {
+        matches!(
+            self,
+            ExCommand::Delete { .. }
+                | ExCommand::Move { .. }
+                | ExCommand::Copy { .. }
+                | ExCommand::Substitute { .. }
+                | ExCommand::Convert(Some(_))
+        )
+    }
+}
+
+// every command name along with the shortest abbreviation it answers to
+const COMMANDS: [(&str, usize); 29] = [
+    ("write", 1),
+    ("wq", 2),
+    ("xit", 1),
+    ("quit", 1),
+    ("qall", 2),
+    ("edit", 1),
+    ("saveas", 3),
+    ("delete", 1),
+    ("yank", 1),
+    ("move", 1),
+    ("copy", 2),
+    ("t", 1),
+    ("set", 2),
+    ("setlocal", 4),
+    ("substitute", 1),
+    ("bnext", 2),
+    ("bprevious", 2),
+    ("ls", 2),
+    ("buffers", 7),
+    ("bdelete", 2),
+    ("buffer", 1),
+    ("split", 2),
+    ("vsplit", 2),
+    ("close", 3),
+    ("only", 2),
+    ("new", 3),
+    ("convert", 4),
+    ("Explore", 2),
+    ("find", 3),
+];
+
+fn lookupCommand(name: &str) -> Option<&'static str> {
+    COMMANDS
+        .iter()
+        .find(|(full, min)| name.len() >= *min && full.starts_with(name))
+        .map(|(full, _)| *full)
+}
+
+/*==============Parsing===============*/
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn skipSpaces(&mut self) {
+        while self.peek().is_some_and(|c| c == ' ' || c == '\t') {
+            self.pos += 1;
+        }
+    }
+
+    fn number(&mut self) -> Option<usize> {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        let digits: String = self.chars[start..self.pos].iter().collect();
+        digits.parse().ok()
+    }
+
+    // text up to an unescaped `delim` - escaped delimiters lose their backslash
+    fn delimited(&mut self, delim: char) -> String {
+        let mut text = String::new();
+        while let Some(c) = self.peek() {
+            self.pos += 1;
+            if c == delim {
+                break;
+            }
+            if c == '\\' && self.peek() == Some(delim) {
+                text.push(delim);
+                self.pos += 1;
+            } else {
+                text.push(c);
+                if c == '\\' {
+                    if let Some(next) = self.peek() {
+                        text.push(next);
+                        self.pos += 1;
+                    }
+                }
+            }
+        }
+        text
+    }
+
+    fn address(&mut self) -> Result<Option<LineSpec>, String> {
+        self.skipSpaces();
+        let base = match self.peek() {
+            Some('.') => {
+                self.pos += 1;
+                Some(Address::Current)
+            }
+            Some('$') => {
+                self.pos += 1;
+                Some(Address::Last)
+            }
+            Some(c) if c.is_ascii_digit() => self.number().map(Address::Line),
+            Some('\'') => {
+                self.pos += 1;
+                match self.peek() {
+                    Some(mark) => {
+                        self.pos += 1;
+                        Some(Address::Mark(mark))
+                    }
+                    None => return Err(String::from("Missing mark name")),
+                }
+            }
+            Some(c @ ('/' | '?')) => {
+                self.pos += 1;
+                Some(Address::Search { pattern: self.delimited(c), forward: c == '/' })
+            }
+            _ => None,
+        };
+
+        //offsets - a bare + or - means one line, and they can come without a base
+        let mut offset = 0;
+        let mut has_offset = false;
+        loop {
+            self.skipSpaces();
+            let sign = match self.peek() {
+                Some('+') => 1,
+                Some('-') => -1,
+                _ => break,
+            };
+            self.pos += 1;
+            has_offset = true;
+            offset += sign * self.number().unwrap_or(1) as i64;
+        }
+
+        match (base, has_offset) {
+            (Some(base), _) => Ok(Some(LineSpec { base, offset })),
+            (None, true) => Ok(Some(LineSpec { base: Address::Current, offset })),
+            (None, false) => Ok(None),
+        }
+    }
+
+    fn range(&mut self) -> Result<LineRange, String> {
+        self.skipSpaces();
+        if self.eat('%') {
+            return Ok(LineRange::Whole);
+        }
+        let first = match self.address()? {
+            Some(first) => first,
+            None => {
+                //a range can start with the separator, meaning the current line
+                if self.peek() == Some(',') || self.peek() == Some(';') {
+                    LineSpec { base: Address::Current, offset: 0 }
+                } else {
+                    return Ok(LineRange::Default);
+                }
+            }
+        };
+        self.skipSpaces();
+        if self.eat(',') || self.eat(';') {
+            let second = self.address()?.unwrap_or(LineSpec { base: Address::Current, offset: 0 });
+            Ok(LineRange::Two(first, second))
+        } else {
+            Ok(LineRange::One(first))
+        }
+    }
+
+    fn name(&mut self) -> String {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_alphabetic()) {
+            self.pos += 1;
+        }
+        self.chars[start..self.pos].iter().collect()
+    }
+
+    fn rest(&mut self) -> String {
+        let rest: String = self.chars[self.pos..].iter().collect();
+        self.pos = self.chars.len();
+        rest.trim().to_string()
+    }
+}
+
+fn optionalArg(arg: String) -> Option<String> {
+    if arg.is_empty() {
+        None
+    } else {
+        Some(arg)
+    }
+}
+
+// the register name :d and :y can be given
+fn registerArg(arg: &str) -> Result<Option<char>, String> {
+    let mut chars = arg.chars();
+    match (chars.next(), chars.next()) {
+        (None, _) => Ok(None),
+        (Some(c), None) if isRegisterName(c) => Ok(Some(c)),
+        _ => Err(format!("Trailing characters: {}", arg)),
+    }
+}
+
+fn parseSubstitute(parser: &mut Parser, range: LineRange) -> Result<ExCommand, String> {
+    let delim = match parser.peek() {
+        Some(c) if !c.is_alphanumeric() && c != ' ' && c != '\\' && c != '"' => c,
+        _ => return Err(String::from("Usage: :s/pattern/replacement/[gi]")),
+    };
+    parser.pos += 1;
+    let pattern = parser.delimited(delim);
+    let replacement = parser.delimited(delim);
+    let flags = parser.rest();
+    let mut global = false;
+    let mut ignore_case = false;
+    for flag in flags.chars() {
+        match flag {
+            'g' => global = true,
+            'i' => ignore_case = true,
+            'I' => ignore_case = false,
+            _ => return Err(format!("Trailing characters: {}", flags)),
+        }
+    }
+    Ok(ExCommand::Substitute { range, pattern, replacement, global, ignore_case })
+}
+
+// turn a command line into a command
+pub(crate) fn parseEx(line: &str) -> Result<ExCommand, String> {
+    let mut parser = Parser { chars: line.trim_start_matches(':').chars().collect(), pos: 0 };
+    let range = parser.range()?;
+    parser.skipSpaces();
+    let name = parser.name();
+    if name.is_empty() {
+        let rest = parser.rest();
+        if !rest.is_empty() {
+            return Err(format!("Not an editor command: {}", line.trim()));
+        }
+        return Ok(ExCommand::Goto(range));
+    }
+    let command = match lookupCommand(&name) {
+        Some(command) => command,
+        None => return Err(format!("Not an editor command: {}", line.trim())),
+    };
+    if command == "substitute" {
+        return parseSubstitute(&mut parser, range);
+    }
+    let force = parser.eat('!');
+    let arg = parser.rest();
+
+    //commands that don't take a range shouldn't be given one
+    let takes_range = matches!(command, "write" | "delete" | "yank" | "move" | "copy" | "t");
+    if !takes_range && range != LineRange::Default {
+        return Err(String::from("No range allowed"));
+    }
+
+    let destination = |arg: &str| -> Result<LineSpec, String> {
+        let mut dest = Parser { chars: arg.chars().collect(), pos: 0 };
+        match dest.address()? {
+            Some(spec) if dest.rest().is_empty() => Ok(spec),
+            _ => Err(String::from("Invalid address")),
+        }
+    };
+
+    Ok(match command {
+        "write" => ExCommand::Write { range, file: optionalArg(arg), force },
+        "wq" => ExCommand::WriteQuit { force, only_if_modified: false },
+        "xit" => ExCommand::WriteQuit { force, only_if_modified: true },
+        "quit" => ExCommand::Quit { force, all: false },
+        "qall" => ExCommand::Quit { force, all: true },
+        "edit" => ExCommand::Edit { file: optionalArg(arg), force },
+        "saveas" => match optionalArg(arg) {
+            Some(file) => ExCommand::SaveAs { file, force },
+            None => return Err(String::from("Argument required")),
+        },
+        "delete" => ExCommand::Delete { range, register: registerArg(&arg)? },
+        "yank" => ExCommand::Yank { range, register: registerArg(&arg)? },
+        "move" => ExCommand::Move { range, dest: destination(&arg)? },
+        "copy" | "t" => ExCommand::Copy { range, dest: destination(&arg)? },
+        "set" | "setlocal" => ExCommand::Set {
+            args: arg.split_whitespace().map(String::from).collect(),
+            local: command == "setlocal",
+        },
+        "bnext" => ExCommand::BufferNext,
+        "bprevious" => ExCommand::BufferPrev,
+        "ls" | "buffers" => ExCommand::BufferList,
+        "bdelete" => ExCommand::BufferDelete { force },
+        "buffer" => match arg.parse::<usize>() {
+            Ok(n) => ExCommand::BufferGo(n),
+            Err(_) => return Err(String::from("Usage: :buffer N")),
+        },
+        "split" => ExCommand::Split { dir: SplitDir::Horizontal, file: optionalArg(arg) },
+        "vsplit" => ExCommand::Split { dir: SplitDir::Vertical, file: optionalArg(arg) },
+        "new" => ExCommand::New,
+        "close" => ExCommand::Close,
+        "only" => ExCommand::Only,
+        "convert" => ExCommand::Convert(optionalArg(arg)),
+        "Explore" => ExCommand::Explore(optionalArg(arg)),
+        "find" => ExCommand::Find(arg),
+        _ => return Err(format!("Not an editor command: {}", line.trim())),
+    })
+}
+
+/*==============Running commands===============*/
+fn exError(msg: &str) -> Error {
+    Error::new(Other, msg)
+}
+
+// 0 based line an address points at
+fn resolveSpec(terminal: &Terminal, spec: &LineSpec) -> io::Result<i64> {
+    let buffer = terminal.buf();
+    let line = match &spec.base {
+        Address::Current => buffer.curs_y as i64,
+        Address::Last => buffer.content.len() as i64 - 1,
+        //a large file might not have its first line loaded
+        Address::Line(n) => match buffer.lineBase() {
+            Some(base) => *n as i64 - 1 - base as i64,
+            None => return Err(exError("Still counting lines - try again in a moment")),
+        },
+        //only marks in this buffer can be used as line numbers
+        Address::Mark(c) => match terminal.findMark(*c) {
+            Some((buf, pos)) if buf == terminal.currBuf() => pos.y as i64,
+            Some(_) => return Err(exError("Mark is in another buffer")),
+            None => return Err(exError("Mark not set")),
+        },
+        Address::Search { pattern, forward } => {
+            let pattern = if pattern.is_empty() {
+                match &terminal.last_pattern {
+                    Some(last) => last.clone(),
+                    None => return Err(exError("No previous regular expression")),
+                }
+            } else {
+                pattern.clone()
+            };
+            let compiled = Pattern::new(&pattern, terminal.options.ignorecase).map_err(|e| exError(&e))?;
+            let count = buffer.content.len() as i64;
+            //search starts on the line after (or before) the cursor and wraps around
+            let found = (1..=count).find_map(|i| {
+                let y = if *forward { buffer.curs_y as i64 + i } else { buffer.curs_y as i64 - i };
+                let y = y.rem_euclid(count);
+                compiled.find(&buffer.content[y as usize], 0).map(|_| y)
+            });
+            match found {
+                Some(y) => y,
+                None => return Err(exError(&format!("Pattern not found: {}", pattern))),
+            }
+        }
+    };
+    Ok(line + spec.offset)
+}
+
+// first and last line (0 based, inclusive) of a range
+fn resolveRange(terminal: &Terminal, range: &LineRange, default_whole: bool) -> io::Result<(usize, usize)> {
+    let last = terminal.buf().content.len() as i64 - 1;
+    let (a, b) = match range {
+        LineRange::Default if default_whole => (0, last),
+        LineRange::Default => {
+            let y = terminal.buf().curs_y as i64;
+            (y, y)
+        }
+        LineRange::Whole => (0, last),
+        LineRange::One(spec) => {
+            let y = resolveSpec(terminal, spec)?;
+            (y, y)
+        }
+        LineRange::Two(first, second) => (resolveSpec(terminal, first)?, resolveSpec(terminal, second)?),
+    };
+    let (a, b) = if a <= b { (a, b) } else { (b, a) };
+    if a < 0 || b > last {
+        return Err(exError("Invalid range"));
+    }
+    Ok((a as usize, b as usize))
+}
+
+// replace every match of `pattern` in a line - returns the new line and how many replacements were made
+fn substituteLine(line: &str, pattern: &Pattern, replacement: &str, global: bool) -> Option<(String, usize)> {
+    let chars: Vec<char> = line.chars().collect();
+    let mut result = String::new();
+    let mut count = 0;
+    let mut pos = 0;
+    while pos <= chars.len() {
+        let (start, end) = match pattern.find(line, pos) {
+            Some(found) => found,
+            None => break,
+        };
+        result.extend(&chars[pos..start]);
+        let matched: String = chars[start..end].iter().collect();
+        //& in the replacement is the matched text, \& is a plain &
+        let mut repl = replacement.chars().peekable();
+        while let Some(c) = repl.next() {
+            match c {
+                '&' => result.push_str(&matched),
+                '\\' => match repl.next() {
+                    Some('t') => result.push('\t'),
+                    Some(next) => result.push(next),
+                    None => result.push('\\'),
+                },
+                c => result.push(c),
+            }
+        }
+        count += 1;
+        if end == start {
+            //empty match - step over a character so we don't loop forever
+            if start < chars.len() {
+                result.push(chars[start]);
+            }
+            pos = start + 1;
+        } else {
+            pos = end;
+        }
+        if !global {
+            break;
+        }
+    }
+    if count == 0 {
+        return None;
+    }
+    if pos < chars.len() {
+        result.extend(&chars[pos..]);
+    }
+    Some((result, count))
+}
+
+fn substitute(
+    terminal: &mut Terminal,
+    range: &LineRange,
+    pattern: &str,
+    replacement: &str,
+    global: bool,
+    ignore_case: bool,
+) -> io::Result<()> {
+    let (a, b) = resolveRange(terminal, range, false)?;
+    let source = if pattern.is_empty() {
+        terminal.last_pattern.clone().ok_or_else(|| exError("No previous regular expression"))?
+    } else {
+        pattern.to_string()
+    };
+    let compiled = Pattern::new(&source, ignore_case || terminal.options.ignorecase).map_err(|e| exError(&e))?;
+    terminal.last_pattern = Some(source.clone());
+
+    let buffer = terminal.bufMut();
+    let mut total = 0;
+    let mut lines = 0;
+    let mut last_line = None;
+    for y in a..=b {
+        if let Some((new, count)) = substituteLine(&buffer.content[y], &compiled, replacement, global) {
+            buffer.setLine(y, new);
+            total += count;
+            lines += 1;
+            last_line = Some(y);
+        }
+    }
+    match last_line {
+        Some(y) => {
+            buffer.curs_y = y as i32;
+            buffer.curs_x = firstNonBlank(buffer.currRow());
+            if lines > 1 {
+                terminal.setMessage(&format!("{} substitutions on {} lines", total, lines));
+            }
+            Ok(())
+        }
+        None => Err(exError(&format!("Pattern not found: {}", source))),
+    }
+}
+
+// write a buffer (or some of its lines) to a file. Only some of the lines replacing the buffer's own file
+// takes a !, and even then it has to be writable and not changed by another program
+fn writeTo(terminal: &mut Terminal, range: &LineRange, file: &str, force: bool) -> io::Result<()> {
+    let own = file == terminal.buf().fp;
+    if own {
+        if !force {
+            return Err(exError("Use ! to write partial buffer"));
+        }
+        if terminal.buf().options.readonly {
+            return Err(exError("File is read-only"));
+        }
+    } else if Path::new(file).exists() && !force {
+        return Err(exError("File exists (add ! to override)"));
+    }
+    //most of a large file isn't loaded, so it's copied over from the file itself as a whole
+    if terminal.buf().large.is_some() {
+        if *range != LineRange::Default {
+            return Err(exError("Only whole large files can be written"));
+        }
+        let buffer = terminal.buf();
+        let (written, _) = writeLargeTo(buffer, file)?;
+        let lines = buffer.totalLines().map_or(String::from("?"), |n| n.to_string());
+        let msg = format!("\"{}\" {} lines, {} bytes written", file, lines, written);
+        terminal.setMessage(&msg);
+        return Ok(());
+    }
+    let (a, b) = resolveRange(terminal, range, true)?;
+    //asking may open a diff window, so hold on to which buffer this is
+    let buf = terminal.currBuf();
+    if own {
+        editorCheckOverwrite(terminal)?;
+    }
+    let buffer = &terminal.buffers[buf];
+    let lines = &buffer.content[a..=b];
+    //the rows of a hex dump are written as the bytes they list
+    let written = if buffer.hex { writeBytes(file, &parseHex(lines)?)? } else { writeLines(file, lines, buffer.encoding, buffer.line_ends)? };
+    let msg = format!("\"{}\" {} lines, {} bytes written", file, lines.len(), written);
+    terminal.setMessage(&msg);
+    Ok(())
+}
+
+fn quit(terminal: &mut Terminal, force: bool, all: bool) -> io::Result<bool> {
+    //with more than one window :q just closes this one
+    if !all && terminal.windows.len() > 1 {
+        terminal.editorCloseWindow()?;
+        return Ok(false);
+    }
+    if !force && terminal.hasUnsavedBuffers() {
+        return Err(exError("No write since last change (add ! to override)"));
+    }
+    Ok(true)
+}
+
+fn copyLines(terminal: &mut Terminal, range: &LineRange, dest: &LineSpec, remove: bool) -> io::Result<()> {
+    let (a, b) = resolveRange(terminal, range, false)?;
+    let dest = resolveSpec(terminal, dest)? + 1; //lines go after the destination, 0 means the top
+    if dest < 0 || dest > terminal.buf().content.len() as i64 {
+        return Err(exError("Invalid range"));
+    }
+    let dest = dest as usize;
+    if remove && dest > a && dest <= b {
+        return Err(exError("Cannot move a range of lines into itself"));
+    }
+    let buffer = terminal.bufMut();
+    let lines: Vec<String> = buffer.content[a..=b].to_vec();
+    let count = lines.len();
+    if remove {
+        //take the lines out first, then shift the destination if it was after them
+        buffer.replaceLines(a, b + 1, Vec::new());
+        let at = if dest > b { dest - count } else { dest };
+        buffer.replaceLines(at, at, lines);
+        buffer.curs_y = (at + count - 1) as i32;
+    } else {
+        buffer.replaceLines(dest, dest, lines);
+        buffer.curs_y = (dest + count - 1) as i32;
+    }
+    buffer.curs_x = firstNonBlank(buffer.currRow());
+    Ok(())
+}
+
+// run a command line - returns true when the editor should exit
+pub(crate) fn executeEx(terminal: &mut Terminal, line: &str) -> io::Result<bool> {
+    if line.trim().is_empty() {
+        return Ok(false);
+    }
+    let command = parseEx(line).map_err(|e| exError(&e))?;
+    if command.isChange() {
+        terminal.checkModifiable()?;
+    }
+    match command {
+        ExCommand::Goto(range) => {
+            terminal.pushJump();
+            //the line might be in a part of a large file that isn't loaded
+            let large = terminal.buf().large.is_some();
+            let y = match range {
+                LineRange::One(LineSpec { base: Address::Line(n), offset }) if large => {
+                    editorLargeGoto(terminal, Some((n as i64 - 1 + offset).max(0) as usize))?
+                }
+                LineRange::One(LineSpec { base: Address::Last, offset: 0 }) if large => editorLargeGoto(terminal, None)?,
+                _ => resolveRange(terminal, &range, false)?.1,
+            };
+            let buffer = terminal.bufMut();
+            buffer.curs_y = y as i32;
+            buffer.curs_x = firstNonBlank(buffer.currRow());
+        }
+        ExCommand::Write { range, file, force } => match file {
+            //a buffer without a name takes the name it's first written to - once it's been written there
+            Some(file) if terminal.buf().fp.is_empty() && range == LineRange::Default => {
+                let buf = terminal.currBuf();
+                terminal.buffers[buf].fp = file;
+                if let Err(e) = terminal.editorWriteFile(force) {
+                    terminal.buffers[buf].fp = String::new();
+                    return Err(e);
+                }
+            }
+            Some(file) if file != terminal.buf().fp => writeTo(terminal, &range, &file, force)?,
+            _ if range != LineRange::Default => {
+                let file = terminal.buf().fp.clone();
+                if file.is_empty() {
+                    return Err(exError("No file name"));
+                }
+                writeTo(terminal, &range, &file, force)?;
+            }
+            _ => terminal.editorWriteFile(force)?,
+        },
+        ExCommand::WriteQuit { force, only_if_modified } => {
+            if !only_if_modified || terminal.buf().isDirty() {
+                terminal.editorWriteFile(force)?;
+            }
+            return quit(terminal, force, false);
+        }
+        ExCommand::Quit { force, all } => return quit(terminal, force, all),
+        ExCommand::Edit { file: Some(file), .. } => terminal.editorOpenFile(&file)?,
+        ExCommand::Edit { file: None, force } => {
+            //reload the file from disk
+            if terminal.buf().isDirty() && !force {
+                return Err(exError("No write since last change (add ! to override)"));
+            }
+            let fp = terminal.buf().fp.clone();
+            if fp.is_empty() {
+                return Err(exError("No file name"));
+            }
+            let (x, y) = (terminal.buf().curs_x, terminal.buf().curs_y);
+            let large = terminal.buf().large.is_some();
+            let mut reloaded = if large { reopenLarge(terminal.buf())? } else { Buffer::open(&fp, terminal.options.legacyencoding)? };
+            reloaded.options = terminal.buf().options;
+            reloaded.swap = terminal.bufMut().swap.take();
+            if terminal.options.undofile && !large {
+                if let Some(history) = loadUndo(&fp, &reloaded.content) {
+                    reloaded.restoreUndo(history);
+                }
+            }
+            reloaded.curs_x = x;
+            reloaded.curs_y = y;
+            reloaded.clampCursor();
+            *terminal.bufMut() = reloaded;
+        }
+        ExCommand::SaveAs { file, force } => {
+            if Path::new(&file).exists() && !force {
+                return Err(exError("File exists (add ! to override)"));
+            }
+            if !force {
+                terminal.checkModifiable()?;
+            }
+            //the buffer only takes the new name once it's been written there
+            let old = std::mem::replace(&mut terminal.bufMut().fp, file);
+            if let Err(e) = terminal.editorWriteFile(true) {
+                terminal.bufMut().fp = old;
+                return Err(e);
+            }
+        }
+        ExCommand::Delete { range, register } => {
+            let (a, b) = resolveRange(terminal, &range, false)?;
+            let buffer = terminal.bufMut();
+            let text = buffer.content[a..=b].to_vec();
+            buffer.replaceLines(a, b + 1, Vec::new());
+            buffer.curs_y = a as i32;
+            buffer.clampCursor();
+            buffer.curs_x = firstNonBlank(buffer.currRow());
+            terminal.storeRegister(register, Register { text, kind: RangeKind::Linewise }, false)?;
+        }
+        ExCommand::Yank { range, register } => {
+            let (a, b) = resolveRange(terminal, &range, false)?;
+            let text = terminal.buf().content[a..=b].to_vec();
+            terminal.storeRegister(register, Register { text, kind: RangeKind::Linewise }, true)?;
+        }
+        ExCommand::Move { range, dest } => copyLines(terminal, &range, &dest, true)?,
+        ExCommand::Copy { range, dest } => copyLines(terminal, &range, &dest, false)?,
+        ExCommand::Set { args, local } => {
+            if args.is_empty() {
+                return Err(exError("Usage: :set option[=value] ..."));
+            }
+            let mut shown = Vec::new();
+            for arg in args {
+                let mut buffer_options = terminal.buf().options;
+                let theme = terminal.options.theme.clone();
+                let msg = terminal.options.set(&mut buffer_options, &arg, !local).map_err(|e| exError(&e))?;
+                terminal.bufMut().options = buffer_options;
+                if terminal.options.theme != theme {
+                    match Theme::find(&terminal.options.theme) {
+                        Ok(found) => terminal.theme = found,
+                        Err(e) => {
+                            terminal.options.theme = theme;
+                            return Err(e);
+                        }
+                    }
+                }
+                if let Some(msg) = msg {
+                    shown.push(msg);
+                }
+            }
+            if !shown.is_empty() {
+                terminal.setMessage(&shown.join("  "));
+            }
+        }
+        ExCommand::Substitute { range, pattern, replacement, global, ignore_case } => {
+            substitute(terminal, &range, &pattern, &replacement, global, ignore_case)?
+        }
+        ExCommand::BufferNext => terminal.editorSwitchBuffer(1),
+        ExCommand::BufferPrev => terminal.editorSwitchBuffer(-1),
+        ExCommand::BufferList => {
+            let list = terminal.editorListBuffers();
+            terminal.setMessage(&list);
+        }
+        ExCommand::BufferGo(n) => {
+            if n == 0 || n > terminal.buffers.len() {
+                return Err(exError(&format!("Buffer {} does not exist", n)));
+            }
+            let delta = n as i32 - 1 - terminal.currBuf() as i32;
+            terminal.editorSwitchBuffer(delta);
+        }
+        ExCommand::BufferDelete { force } => terminal.editorCloseBuffer(force)?,
+        ExCommand::Split { dir, file } => {
+            terminal.editorSplitWindow(dir)?;
+            if let Some(file) = file {
+                terminal.editorOpenFile(&file)?;
+            }
+        }
+        ExCommand::New => {
+            terminal.editorSplitWindow(SplitDir::Horizontal)?;
+            let buffer = terminal.newBuffer();
+            terminal.addBuffer(buffer);
+        }
+        ExCommand::Close => terminal.editorCloseWindow()?,
+        ExCommand::Only => terminal.editorOnlyWindow(),
+        //with no encoding, just say which one the buffer is in
+        ExCommand::Convert(None) => {
+            let msg = format!("{} is {}", terminal.buf().name(), terminal.buf().encoding.name());
+            terminal.setMessage(&msg);
+        }
+        ExCommand::Convert(Some(name)) => {
+            let encoding = match Encoding::fromName(&name) {
+                Some(encoding) => encoding,
+                None => return Err(exError(&format!("Unknown encoding: {}", name))),
+            };
+            terminal.bufMut().convert(encoding)?;
+            terminal.setMessage(&format!("Converted to {} - it's saved that way when written", encoding.name()));
+        }
+        //with no directory, the one the current file is in
+        ExCommand::Explore(dir) => {
+            let dir = dir.unwrap_or_else(|| currentDir(terminal));
+            editorBrowse(terminal, &dir)?;
+        }
+        ExCommand::Find(query) => editorFindFile(terminal, &query)?,
+    }
+    Ok(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec(base: Address, offset: i64) -> LineSpec {
+        LineSpec { base, offset }
+    }
+
+    #[test]
+    fn parsesAddresses() {
+        assert_eq!(parseEx(":12"), Ok(ExCommand::Goto(LineRange::One(spec(Address::Line(12), 0)))));
+        assert_eq!(parseEx("$"), Ok(ExCommand::Goto(LineRange::One(spec(Address::Last, 0)))));
+        assert_eq!(parseEx("+3"), Ok(ExCommand::Goto(LineRange::One(spec(Address::Current, 3)))));
+        assert_eq!(parseEx("'a-2"), Ok(ExCommand::Goto(LineRange::One(spec(Address::Mark('a'), -2)))));
+        let search = Address::Search { pattern: String::from("a/b"), forward: true };
+        assert_eq!(parseEx("/a\\/b/+-"), Ok(ExCommand::Goto(LineRange::One(spec(search, 0)))));
+        assert_eq!(parseEx("'"), Err(String::from("Missing mark name")));
+    }
+
+    #[test]
+    fn parsesRanges() {
+        assert_eq!(parseEx("%d"), Ok(ExCommand::Delete { range: LineRange::Whole, register: None }));
+        let range = LineRange::Two(spec(Address::Current, 0), spec(Address::Last, -1));
+        assert_eq!(parseEx(".,$-1y a"), Ok(ExCommand::Yank { range, register: Some('a') }));
+        //a range can leave out either end
+        let range = LineRange::Two(spec(Address::Current, 0), spec(Address::Line(5), 0));
+        assert_eq!(parseEx(",5d"), Ok(ExCommand::Delete { range, register: None }));
+        let range = LineRange::Two(spec(Address::Line(2), 0), spec(Address::Current, 0));
+        assert_eq!(parseEx("2;d"), Ok(ExCommand::Delete { range, register: None }));
+        assert_eq!(parseEx("d xy"), Err(String::from("Trailing characters: xy")));
+    }
+
+    #[test]
+    fn parsesCommands() {
+        assert_eq!(parseEx("w!"), Ok(ExCommand::Write { range: LineRange::Default, file: None, force: true }));
+        assert_eq!(parseEx("x"), Ok(ExCommand::WriteQuit { force: false, only_if_modified: true }));
+        assert_eq!(parseEx("sav! new.txt"), Ok(ExCommand::SaveAs { file: String::from("new.txt"), force: true }));
+        assert_eq!(parseEx("sav"), Err(String::from("Argument required")));
+        assert_eq!(parseEx("sp"), Ok(ExCommand::Split { dir: SplitDir::Horizontal, file: None }));
+        assert_eq!(parseEx("new"), Ok(ExCommand::New));
+        assert_eq!(parseEx("Ex src"), Ok(ExCommand::Explore(Some(String::from("src")))));
+        assert_eq!(parseEx("b 3"), Ok(ExCommand::BufferGo(3)));
+        assert_eq!(parseEx("2,3m 0"), parseEx("2,3move0"));
+        assert_eq!(parseEx("t $"), Ok(ExCommand::Copy { range: LineRange::Default, dest: spec(Address::Last, 0) }));
+        assert_eq!(parseEx("m x"), Err(String::from("Invalid address")));
+    }
+
+    #[test]
+    fn rejectsBadCommands() {
+        assert_eq!(parseEx("sa"), Err(String::from("Not an editor command: sa")));
+        assert_eq!(parseEx("frobnicate"), Err(String::from("Not an editor command: frobnicate")));
+        assert_eq!(parseEx("12x"), Err(String::from("No range allowed")));
+        assert_eq!(parseEx("5 junk!"), Err(String::from("Not an editor command: 5 junk!")));
+    }
+
+    #[test]
+    fn parsesSubstitute() {
+        let command = ExCommand::Substitute {
+            range: LineRange::Whole,
+            pattern: String::from("a"),
+            replacement: String::from("b"),
+            global: true,
+            ignore_case: true,
+        };
+        assert_eq!(parseEx("%s#a#b#gi"), Ok(command));
+        assert_eq!(parseEx("s/a/b/x"), Err(String::from("Trailing characters: x")));
+        assert!(parseEx("s a b").is_err());
+    }
+
+    #[test]
+    fn substitutesLines() {
+        let dash = Pattern::new("-", false).unwrap();
+        assert_eq!(substituteLine("a-b-c", &dash, "+", true), Some((String::from("a+b+c"), 2)));
+        assert_eq!(substituteLine("a-b-c", &dash, "+", false), Some((String::from("a+b-c"), 1)));
+        assert_eq!(substituteLine("a-b", &dash, "[&\\&\\t]", false), Some((String::from("a[-&\t]b"), 1)));
+        assert_eq!(substituteLine("abc", &dash, "+", true), None);
+    }
+}
@@ -0,0 +1,475 @@
+use crate::input::{editorNextKey, ARROW_DOWN, ARROW_UP, BACKSPACE_KEY, CTRL_KEY, ENTER_KEY, PAGE_DOWN, PAGE_UP};
+use crate::output::editorRefreshScreen;
+use crate::Terminal;
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+//most files a walk collects - past this the rest of the tree is left out
+const MAX_FILES: usize = 100_000;
+//candidates shown at once
+pub(crate) const FINDER_ROWS: usize = 12;
+
+//what a matched character is worth, and the extras it gets for where it is
+const SCORE_MATCH: i64 = 16;
+const BONUS_CONSECUTIVE: i64 = 12; //right after the last matched character
+const BONUS_BOUNDARY: i64 = 10; //starts a name or a word in one
+const BONUS_BASENAME: i64 = 4; //in the file's name rather than its directory
+const PENALTY_GAP: i64 = 1; //for each character skipped between two matches
+const PENALTY_GAP_MAX: i64 = 12;
+
+/*==============Ignore files===============*/
+// one line of a .gitignore
+struct IgnoreRule {
+    base: String, //directory the .gitignore is in, relative to the root - empty or ending in /
+    pattern: Vec<char>,
+    negate: bool,   // !pattern brings back something an earlier rule ignored
+    dir_only: bool, // pattern/ only matches directories
+    anchored: bool, //has a / in it, so it's matched against the whole path under `base` rather than a name
+}
+
+impl IgnoreRule {
+    fn parse(base: &str, line: &str) -> Option<IgnoreRule> {
+        let line = line.trim_end();
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+        let (negate, line) = match line.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, line.strip_prefix('\\').unwrap_or(line)),
+        };
+        let (dir_only, line) = match line.strip_suffix('/') {
+            Some(rest) => (true, rest),
+            None => (false, line),
+        };
+        let anchored = line.contains('/');
+        let line = line.strip_prefix('/').unwrap_or(line);
+        if line.is_empty() {
+            return None;
+        }
+        Some(IgnoreRule { base: base.to_string(), pattern: line.chars().collect(), negate, dir_only, anchored })
+    }
+
+    fn matches(&self, path: &str, is_dir: bool) -> bool {
+        if self.dir_only && !is_dir {
+            return false;
+        }
+        let rest = match path.strip_prefix(self.base.as_str()) {
+            Some(rest) => rest,
+            None => return false,
+        };
+        let subject = if self.anchored { rest } else { rest.rsplit('/').next().unwrap_or(rest) };
+        globMatch(&self.pattern, &subject.chars().collect::<Vec<char>>())
+    }
+}
+
+// gitignore globs - * and ? stop at /, ** doesn't, [abc] is any one of a set
+fn globMatch(pattern: &[char], text: &[char]) -> bool {
+    match pattern.first() {
+        None => text.is_empty(),
+        Some('*') if pattern.get(1) == Some(&'*') => {
+            //`**/` is any number of directories, none included
+            if let Some(after) = pattern[2..].strip_prefix(&['/']) {
+                return globMatch(after, text)
+                    || (0..text.len()).any(|i| text[i] == '/' && globMatch(after, &text[i + 1..]));
+            }
+            (0..=text.len()).any(|i| globMatch(&pattern[2..], &text[i..]))
+        }
+        Some('*') => {
+            let rest = &pattern[1..];
+            for i in 0..=text.len() {
+                if globMatch(rest, &text[i..]) {
+                    return true;
+                }
+                if text.get(i) == Some(&'/') {
+                    break;
+                }
+            }
+            false
+        }
+        Some('?') => text.first().is_some_and(|c| *c != '/') && globMatch(&pattern[1..], &text[1..]),
+        Some('[') => match (pattern.iter().position(|c| *c == ']'), text.first()) {
+            (Some(end), Some(c)) if end > 1 => {
+                let set = &pattern[1..end];
+                let (negate, set) = match set.first() {
+                    Some('!') | Some('^') => (true, &set[1..]),
+                    _ => (false, set),
+                };
+                let mut found = false;
+                let mut i = 0;
+                while i < set.len() {
+                    if set.get(i + 1) == Some(&'-') && i + 2 < set.len() {
+                        found |= (set[i]..=set[i + 2]).contains(c);
+                        i += 3;
+                    } else {
+                        found |= set[i] == *c;
+                        i += 1;
+                    }
+                }
+                found != negate && *c != '/' && globMatch(&pattern[end + 1..], &text[1..])
+            }
+            _ => false,
+        },
+        Some(p) => text.first() == Some(p) && globMatch(&pattern[1..], &text[1..]),
+    }
+}
+
+// is `path` ignored - the last rule that matches it decides
+fn isIgnored(rules: &[IgnoreRule], path: &str, is_dir: bool) -> bool {
+    rules.iter().rev().find(|rule| rule.matches(path, is_dir)).is_some_and(|rule| !rule.negate)
+}
+
+/*==============Walking the tree===============*/
+// every file under `dir`, relative to `root`, leaving out what .gitignore files say to. Linked directories
+// aren't followed, so a link back up the tree can't go round forever
+fn walk(root: &Path, rel: &str, rules: &mut Vec<IgnoreRule>, files: &mut Vec<String>) {
+    let dir = root.join(rel);
+    let rules_before = rules.len();
+    if let Ok(text) = fs::read_to_string(dir.join(".gitignore")) {
+        rules.extend(text.lines().filter_map(|line| IgnoreRule::parse(rel, line)));
+    }
+    let mut entries: Vec<(String, bool)> = match fs::read_dir(&dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| {
+                let is_dir = entry.file_type().is_ok_and(|t| t.is_dir());
+                (entry.file_name().to_string_lossy().to_string(), is_dir)
+            })
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    entries.sort();
+    for (name, is_dir) in entries {
+        if files.len() > MAX_FILES {
+            break;
+        }
+        let path = format!("{}{}", rel, name);
+        if name == ".git" || isIgnored(rules, &path, is_dir) {
+            continue;
+        }
+        if is_dir {
+            walk(root, &format!("{}/", path), rules, files);
+        } else {
+            files.push(path);
+        }
+    }
+    rules.truncate(rules_before);
+}
+
+// files under the current directory, as paths relative to it - and whether there were too many to list them all
+fn projectFiles() -> (Vec<String>, bool) {
+    let mut files = Vec::new();
+    walk(Path::new("."), "", &mut Vec::new(), &mut files);
+    //the walk goes one past the limit so we can tell
+    let truncated = files.len() > MAX_FILES;
+    files.truncate(MAX_FILES);
+    (files, truncated)
+}
+
+/*==============Matching===============*/
+// a file the query matches, with how well and which of its characters matched
+pub(crate) struct Candidate {
+    pub(crate) file: usize,
+    pub(crate) score: i64,
+    pub(crate) positions: Vec<usize>,
+}
+
+// characters that start a word - after a separator, or a capital after a lowercase letter
+fn boundaryBonus(path: &[char], i: usize) -> i64 {
+    match i.checked_sub(1).map(|p| path[p]) {
+        None => BONUS_BOUNDARY,
+        Some('/') | Some('_') | Some('-') | Some('.') | Some(' ') => BONUS_BOUNDARY,
+        Some(prev) if prev.is_lowercase() && path[i].is_uppercase() => BONUS_BOUNDARY,
+        _ => 0,
+    }
+}
+
+// score `path` for the query - None unless every query character appears in it in order. Finds the best
+// way of lining them up, so `sm` in `src/items/sum.rs` goes for the start of `sum` over the s of `items`
+fn fuzzyMatch(query: &[char], path: &str, ignore_case: bool) -> Option<(i64, Vec<usize>)> {
+    let original: Vec<char> = path.chars().collect();
+    let folded: Vec<char> = if ignore_case { path.to_lowercase().chars().collect() } else { original.clone() };
+    if folded.len() != original.len() {
+        return None; //lowercasing changed the length - rare enough to skip
+    }
+    let (m, n) = (query.len(), folded.len());
+    //a cheap check first - most paths don't match at all
+    let mut q = 0;
+    for c in &folded {
+        if q < m && *c == query[q] {
+            q += 1;
+        }
+    }
+    if q < m {
+        return None;
+    }
+    let basename = original.iter().rposition(|c| *c == '/').map_or(0, |i| i + 1);
+    let worth = |j: usize| SCORE_MATCH + boundaryBonus(&original, j) + if j >= basename { BONUS_BASENAME } else { 0 };
+    //best[i][j]: best score with query[i] matched at path[j], and where query[i - 1] went for it
+    let mut best = vec![vec![None::<(i64, usize)>; n]; m];
+    for j in 0..n {
+        if folded[j] == query[0] {
+            best[0][j] = Some((worth(j), 0));
+        }
+    }
+    for i in 1..m {
+        //best earlier match of query[i - 1] that isn't right before j, counting what the gap will cost
+        let mut running: Option<(i64, usize)> = None;
+        for j in 1..n {
+            if j >= 2 {
+                if let Some((score, _)) = best[i - 1][j - 2] {
+                    let k = j - 2;
+                    if running.is_none_or(|(r, rk)| score + k as i64 * PENALTY_GAP > r + rk as i64 * PENALTY_GAP) {
+                        running = Some((score, k));
+                    }
+                }
+            }
+            if folded[j] != query[i] {
+                continue;
+            }
+            let mut here = None;
+            if let Some((score, _)) = best[i - 1][j - 1] {
+                here = Some((score + BONUS_CONSECUTIVE, j - 1));
+            }
+            if let Some((score, k)) = running {
+                let gapped = score - ((j - k - 1) as i64 * PENALTY_GAP).min(PENALTY_GAP_MAX);
+                if here.is_none_or(|(h, _)| gapped > h) {
+                    here = Some((gapped, k));
+                }
+            }
+            best[i][j] = here.map(|(score, k)| (score + worth(j), k));
+        }
+    }
+    let (mut j, (score, _)) = (0..n).filter_map(|j| best[m - 1][j].map(|b| (j, b))).max_by_key(|(_, (s, _))| *s)?;
+    let mut positions = vec![0; m];
+    for i in (0..m).rev() {
+        positions[i] = j;
+        j = best[i][j].map_or(0, |(_, k)| k);
+    }
+    Some((score, positions))
+}
+
+/*==============The finder===============*/
+// what the finder shows while it's open - drawn over the bottom of the windows
+pub(crate) struct Finder {
+    pub(crate) query: String,
+    pub(crate) files: Vec<String>,
+    pub(crate) matches: Vec<Candidate>, //best first
+    pub(crate) selected: usize,
+    pub(crate) offset: usize, //first candidate shown
+    pub(crate) truncated: bool, //the tree had more files than it lists
+}
+
+impl Finder {
+    fn new(files: Vec<String>, truncated: bool, query: &str) -> Finder {
+        let matches = Vec::new();
+        let mut finder = Finder { query: query.to_string(), files, matches, selected: 0, offset: 0, truncated };
+        finder.rank();
+        finder
+    }
+
+    // match every file against the query again. Lowercase queries ignore case
+    fn rank(&mut self) {
+        let ignore_case = !self.query.chars().any(char::is_uppercase);
+        let query: Vec<char> = self.query.chars().filter(|c| *c != ' ').collect();
+        self.matches = if query.is_empty() {
+            (0..self.files.len()).map(|file| Candidate { file, score: 0, positions: Vec::new() }).collect()
+        } else {
+            let matched = self.files.iter().enumerate().filter_map(|(file, path)| {
+                fuzzyMatch(&query, path, ignore_case).map(|(score, positions)| Candidate { file, score, positions })
+            });
+            matched.collect()
+        };
+        //ties go to the shorter path
+        let files = &self.files;
+        self.matches.sort_by(|a, b| {
+            (-a.score, files[a.file].len(), &files[a.file]).cmp(&(-b.score, files[b.file].len(), &files[b.file]))
+        });
+        self.selected = 0;
+        self.offset = 0;
+    }
+
+    fn moveSelection(&mut self, delta: i64) {
+        if self.matches.is_empty() {
+            return;
+        }
+        self.selected = (self.selected as i64 + delta).clamp(0, self.matches.len() as i64 - 1) as usize;
+        if self.selected < self.offset {
+            self.offset = self.selected;
+        } else if self.selected >= self.offset + FINDER_ROWS {
+            self.offset = self.selected + 1 - FINDER_ROWS;
+        }
+    }
+}
+
+// ask for a file by typing part of its path, and open the one picked
+pub(crate) fn editorFindFile(terminal: &mut Terminal, query: &str) -> io::Result<()> {
+    let (files, truncated) = projectFiles();
+    if files.is_empty() {
+        terminal.setMessage("No files here");
+        return Ok(());
+    }
+    terminal.finder = Some(Finder::new(files, truncated, query));
+    let picked = editorRunFinder(terminal);
+    terminal.finder = None;
+    terminal.setMessage("");
+    match picked? {
+        Some(fp) => terminal.editorOpenFile(&fp),
+        None => Ok(()),
+    }
+}
+
+// keys typed while the finder is open - None if it was closed without picking anything
+fn editorRunFinder(terminal: &mut Terminal) -> io::Result<Option<String>> {
+    loop {
+        let finder = terminal.finder.as_ref().unwrap();
+        let msg = format!("Find: {}", finder.query);
+        terminal.setMessage(&msg);
+        editorRefreshScreen(terminal)?;
+
+        let mut key_buf = String::new();
+        let key = editorNextKey(terminal, &mut key_buf)?;
+        let finder = terminal.finder.as_mut().unwrap();
+        match key {
+            ENTER_KEY!() => return Ok(finder.matches.get(finder.selected).map(|c| finder.files[c.file].clone())),
+            0x1b => return Ok(None),
+            ARROW_UP!() => finder.moveSelection(-1),
+            ARROW_DOWN!() => finder.moveSelection(1),
+            PAGE_UP!() => finder.moveSelection(-(FINDER_ROWS as i64)),
+            PAGE_DOWN!() => finder.moveSelection(FINDER_ROWS as i64),
+            key if key == CTRL_KEY!(b'p') as i32 => finder.moveSelection(-1),
+            key if key == CTRL_KEY!(b'n') as i32 => finder.moveSelection(1),
+            key if key == CTRL_KEY!(b'u') as i32 => {
+                finder.query.clear();
+                finder.rank();
+            }
+            BACKSPACE_KEY!() => {
+                if finder.query.pop().is_some() {
+                    finder.rank();
+                }
+            }
+            key => {
+                if key < 1000 && !(key as u8).is_ascii_control() {
+                    finder.query.push(key as u8 as char);
+                    finder.rank();
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::lines;
+
+    fn glob(pattern: &str, text: &str) -> bool {
+        globMatch(&pattern.chars().collect::<Vec<_>>(), &text.chars().collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn globsMatch() {
+        assert!(glob("*.rs", "main.rs"));
+        assert!(!glob("*.rs", "src/main.rs"));
+        assert!(glob("src/*.rs", "src/main.rs"));
+        assert!(glob("**/gen", "gen") && glob("**/gen", "a/b/gen"));
+        assert!(glob("a/**", "a/b/c"));
+        assert!(glob("file?.[ch]", "file1.c") && !glob("file?.[ch]", "file1.o"));
+        assert!(glob("[!a-c]x", "dx") && !glob("[!a-c]x", "bx"));
+        assert!(!glob("a?b", "a/b"));
+        assert!(!glob("[ab", "a"));
+    }
+
+    #[test]
+    fn parsesIgnoreLines() {
+        assert!(IgnoreRule::parse("", "# comment").is_none());
+        assert!(IgnoreRule::parse("", "   ").is_none());
+        assert!(IgnoreRule::parse("", "/").is_none());
+        let rule = IgnoreRule::parse("sub/", "!/build/").unwrap();
+        assert!(rule.negate && rule.dir_only && rule.anchored);
+        assert_eq!(rule.pattern, ['b', 'u', 'i', 'l', 'd']);
+        //a backslash keeps a leading ! or # as it is
+        assert_eq!(IgnoreRule::parse("", "\\!x").unwrap().pattern, ['!', 'x']);
+    }
+
+    #[test]
+    fn lastRuleWins() {
+        let rules: Vec<IgnoreRule> =
+            ["*.log", "!keep.log", "/target", "cache/"].iter().filter_map(|line| IgnoreRule::parse("", line)).collect();
+        assert!(isIgnored(&rules, "a/b.log", false));
+        assert!(!isIgnored(&rules, "a/keep.log", false));
+        assert!(isIgnored(&rules, "target", true));
+        assert!(!isIgnored(&rules, "src/target", true));
+        assert!(isIgnored(&rules, "src/cache", true));
+        assert!(!isIgnored(&rules, "src/cache", false));
+        //rules only apply under the directory their file is in
+        let nested = IgnoreRule::parse("src/", "gen").unwrap();
+        assert!(nested.matches("src/a/gen", true));
+        assert!(!nested.matches("gen", true));
+    }
+
+    #[test]
+    fn walksTheTree() {
+        let root = std::env::temp_dir().join(format!("rustwrd-finder-{}", std::process::id()));
+        for dir in [".git", "src/gen", "src/target", "target"] {
+            fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        let files = [
+            (".gitignore", "*.log\n!keep.log\n/target\n"),
+            ("src/.gitignore", "gen/\n"),
+            (".git/config", ""),
+            ("a.txt", ""),
+            ("b.log", ""),
+            ("keep.log", ""),
+            ("src/main.rs", ""),
+            ("src/gen/out.rs", ""),
+            ("src/target/z", ""),
+            ("target/x", ""),
+        ];
+        for (name, text) in files {
+            fs::write(root.join(name), text).unwrap();
+        }
+        let mut found = Vec::new();
+        walk(&root, "", &mut Vec::new(), &mut found);
+        fs::remove_dir_all(&root).unwrap();
+        assert_eq!(found, [".gitignore", "a.txt", "keep.log", "src/.gitignore", "src/main.rs", "src/target/z"]);
+    }
+
+    #[test]
+    fn picksTheBestLineUp() {
+        let query: Vec<char> = "sm".chars().collect();
+        let (_, positions) = fuzzyMatch(&query, "src/items/sum.rs", true).unwrap();
+        assert_eq!(positions, [10, 12]);
+        assert!(fuzzyMatch(&query, "ms.rs", true).is_none());
+        let upper: Vec<char> = "SM".chars().collect();
+        assert!(fuzzyMatch(&upper, "src/items/sum.rs", false).is_none());
+        assert!(fuzzyMatch(&upper, "SuM", false).is_some());
+    }
+
+    #[test]
+    fn ranksMatches() {
+        let files = lines(&["src/domain/x.rs", "README.md", "src/main.rs", "main.rs"]);
+        let finder = Finder::new(files, false, "main");
+        let ranked: Vec<&str> = finder.matches.iter().map(|m| finder.files[m.file].as_str()).collect();
+        assert_eq!(ranked, ["main.rs", "src/main.rs", "src/domain/x.rs"]);
+        //an uppercase letter makes the query match case
+        assert!(Finder::new(lines(&["main.rs"]), false, "Main").matches.is_empty());
+        //spaces are left out, and no query lists everything
+        assert_eq!(Finder::new(lines(&["a.rs", "b.rs"]), false, " ").matches.len(), 2);
+    }
+
+    #[test]
+    fn selectionScrolls() {
+        let files: Vec<String> = (0..30).map(|i| format!("{:02}", i)).collect();
+        let mut finder = Finder::new(files, false, "");
+        finder.moveSelection(-1);
+        assert_eq!((finder.selected, finder.offset), (0, 0));
+        finder.moveSelection(FINDER_ROWS as i64);
+        assert_eq!((finder.selected, finder.offset), (FINDER_ROWS, 1));
+        finder.moveSelection(100);
+        assert_eq!((finder.selected, finder.offset), (29, 30 - FINDER_ROWS));
+        finder.moveSelection(-29);
+        assert_eq!((finder.selected, finder.offset), (0, 0));
+    }
+}
@@ -0,0 +1,132 @@
+use crate::Terminal;
+
+use std::io;
+use std::io::Error;
+use std::io::ErrorKind::Other;
+
+//bytes shown on each row of a hex dump
+const ROW_BYTES: usize = 16;
+//columns the hex part of a row takes - a space after every byte, and an extra one halfway
+const HEX_WIDTH: usize = ROW_BYTES * 3 + 1;
+//how far into a file to look for a NUL byte
+const SNIFF_LEN: usize = 8000;
+
+// a file that isn't text - text never has NUL bytes, unless it's UTF-16
+pub(crate) fn isBinary(bytes: &[u8]) -> bool {
+    bytes[..bytes.len().min(SNIFF_LEN)].contains(&0)
+}
+
+// one row of a dump - `00000010: 48 65 6c 6c 6f 00 ...  |Hello.|`
+fn hexRow(offset: &str, bytes: &[u8]) -> String {
+    let mut hex = String::new();
+    for (i, b) in bytes.iter().enumerate() {
+        if i == ROW_BYTES / 2 {
+            hex.push(' ');
+        }
+        hex.push_str(&format!("{:02x} ", b));
+    }
+    let text: String = bytes.iter().map(|b| if (0x20..0x7f).contains(b) { *b as char } else { '.' }).collect();
+    format!("{}: {:<width$} |{}|", offset, hex, text, width = HEX_WIDTH)
+}
+
+// the lines a binary file is shown as - offset, bytes in hex, and the bytes that are printable
+pub(crate) fn hexLines(bytes: &[u8]) -> Vec<String> {
+    let rows = bytes.chunks(ROW_BYTES).enumerate();
+    let mut lines: Vec<String> = rows.map(|(i, row)| hexRow(&format!("{:08x}", i * ROW_BYTES), row)).collect();
+    if lines.is_empty() {
+        lines.push(String::new());
+    }
+    lines
+}
+
+// offset and bytes of a row - only the hex part counts, the text after it is just for show
+fn parseRow(line: &str) -> Result<(&str, Vec<u8>), String> {
+    let (offset, rest) = line.split_once(':').unwrap_or(("", line));
+    let hex = rest.split('|').next().unwrap_or("");
+    let mut bytes = Vec::new();
+    for pair in hex.split_whitespace() {
+        match u8::from_str_radix(pair, 16) {
+            Ok(b) if pair.len() == 2 => bytes.push(b),
+            _ => return Err(format!("'{}' isn't a hex byte", pair)),
+        }
+    }
+    Ok((offset.trim(), bytes))
+}
+
+// the bytes a dump lists, for writing back - errors on the first row that doesn't make sense
+pub(crate) fn parseHex(lines: &[String]) -> io::Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    for (i, line) in lines.iter().enumerate() {
+        match parseRow(line) {
+            Ok((_, row)) => bytes.extend(row),
+            Err(e) => return Err(Error::new(Other, format!("Not written: line {}: {}", i + 1, e))),
+        }
+    }
+    Ok(bytes)
+}
+
+// after an edit to a hex buffer, bring the text column of the rows that changed up to date with their bytes.
+// Done before the edit is sealed, so it undoes along with it
+pub(crate) fn editorHexSync(terminal: &mut Terminal) {
+    let buffer = terminal.bufMut();
+    if !buffer.hex {
+        return;
+    }
+    for y in buffer.undo.pendingLines() {
+        let line = match buffer.content.get(y) {
+            Some(line) if !line.is_empty() => line,
+            _ => continue,
+        };
+        //a row still being typed is left alone
+        let row = match parseRow(line) {
+            Ok((offset, bytes)) if !bytes.is_empty() => hexRow(offset, &bytes),
+            _ => continue,
+        };
+        if row != *line {
+            buffer.setLine(y, row);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rowsLineUp() {
+        let row = hexRow("00000010", b"Hello\x00");
+        assert_eq!(row, format!("00000010: 48 65 6c 6c 6f 00 {:32}|Hello.|", ""));
+        let full = hexRow("00000000", &[0x41; ROW_BYTES]);
+        assert_eq!(full.find('|'), Some(10 + HEX_WIDTH + 1));
+        assert_eq!(&full[10 + 8 * 3..10 + 8 * 3 + 4], " 41 ");
+    }
+
+    #[test]
+    fn dumpsRoundTrip() {
+        let bytes: Vec<u8> = (0..=255).chain(0..7).collect();
+        let lines = hexLines(&bytes);
+        assert_eq!(lines.len(), 17);
+        assert!(lines[16].starts_with("00000100: 00 01"));
+        assert_eq!(parseHex(&lines).unwrap(), bytes);
+        assert_eq!(hexLines(b""), [""]);
+    }
+
+    #[test]
+    fn onlyTheHexPartCounts() {
+        //the text column and the offset are ignored, and bytes can be typed without them
+        let lines = vec![String::from("00000000: 61 62 |zzzz|"), String::from("63"), String::new()];
+        assert_eq!(parseHex(&lines).unwrap(), b"abc");
+        let bad = vec![String::from("00000000: 61 6"), String::from("xyz: 4g")];
+        assert_eq!(parseHex(&bad).unwrap_err().to_string(), "Not written: line 1: '6' isn't a hex byte");
+        assert_eq!(parseRow("0: 123").unwrap_err(), "'123' isn't a hex byte");
+    }
+
+    #[test]
+    fn nulMeansBinary() {
+        assert!(isBinary(b"text\x00more"));
+        assert!(!isBinary("plain text é".as_bytes()));
+        //only the start is looked at
+        let late: Vec<u8> = [vec![b'a'; SNIFF_LEN], vec![0]].concat();
+        assert!(!isBinary(&late));
+    }
+}
@@ -1,7 +1,33 @@
 use std::io;
 use std::io::ErrorKind::Other;
-use std::io::{stdin, stdout, Error, ErrorKind, Read, Write};
+use std::io::{stdin, stdout, BufRead, Error, ErrorKind, Read, Write};
+use std::time::{Duration, Instant};
 
+use crate::browse::{
+    editorBrowseDelete, editorBrowseNewDir, editorBrowseNewFile, editorBrowseOpen, editorBrowseRename, editorBrowseUp,
+};
+use crate::buffer::{displayCol, indentOf, lineLen};
+use crate::output::editorRefreshScreen;
+use crate::ex::executeEx;
+use crate::disk::{editorCheckDisk, FOCUS_IN, FOCUS_OUT};
+use crate::command::{
+    parseNormal, parseVisual, Action, Change, InsertAt, NormalCmd, OpTarget, Parse, Repeat, VisualAction, VisualCmd,
+};
+use crate::finder::editorFindFile;
+use crate::hex::editorHexSync;
+use crate::keymap::{Command, MapMode};
+use crate::keys::{keyName, keysToText, textToKeys};
+use crate::large::{editorLargeGoto, editorLargeSlide};
+use crate::mark::Jump;
+use crate::mode::Mode;
+use crate::motion::{applyMotion, firstNonBlank, Motion, MotionKind, Pos};
+use crate::pattern::Pattern;
+use crate::operator::{applyOperator, motionRange, ForceKind, Operator, RangeKind, TextRange};
+use crate::register::putRegister;
+use crate::swap::{editorSyncSwaps, SWAP_IDLE};
+use crate::textobject::selectObject;
+use crate::visual::{applyBlockInsert, BlockInsert, Selection, VisualKind};
+use crate::window::SplitDir;
 use crate::Terminal;
 
 //Macro to add CTRL modifier to each key
@@ -11,6 +37,9 @@ macro_rules! CTRL_KEY {
     };
 }
 
+//most keys a macro can queue up - stops one that plays itself from running forever
+const MAX_MACRO_KEYS: usize = 1_000_000;
+
 // editorKey bindings:
 macro_rules! ARROW_UP {
     () => {
@@ -65,117 +94,1079 @@ macro_rules! BACKSPACE_KEY {
         1010
     };
 }
+//the terminal's window gained or lost focus - never handed on as a key
+macro_rules! FOCUS_KEY {
+    () => {
+        1011
+    };
+}
+//other modules match on the same key codes
+pub(crate) use {
+    ARROW_DOWN, ARROW_LEFT, ARROW_RIGHT, ARROW_UP, BACKSPACE_KEY, CTRL_KEY, DEL_KEY, END_KEY, ENTER_KEY, HOME_KEY,
+    PAGE_DOWN, PAGE_UP,
+};
 
 pub(crate) fn editorProcessKeypress(terminal: &mut Terminal) -> io::Result<bool> {
+    //between commands is the one time a question about a file won't get in the way of anything
+    if terminal.macro_queue.is_empty() && terminal.pending.is_empty() && terminal.mode != Mode::Command {
+        editorAwaitKey(terminal)?;
+    }
     let mut input_buf = String::new();
-    match editorReadKey(&mut input_buf) {
-        Ok(keyPressed) => {
-            if keyPressed == CTRL_KEY!(b'q') as i32 {
-                Ok(true) //exit the program
-            } else if keyPressed == CTRL_KEY!(b'u') as i32 {
-                //clear line
-                terminal.content[terminal.curs_y as usize] = String::new();
-                terminal.curs_x = 0;
-                Ok(false)
-            } else if keyPressed == CTRL_KEY!(b's') as i32 {
-                let fp = terminal.fp.clone();
-                terminal.editorWriteFile(fp)?;
-                Ok(false)
-            }
-            else {
-                match keyPressed {
-                    HOME_KEY!() => {
-                        terminal.curs_x = 0;
-                    }
-                    END_KEY!() => {
-                        let invalidString = String::from("");
-                        let curr_row = terminal.content.get(terminal.curs_y as usize).unwrap_or(&invalidString);
-                        terminal.curs_x = curr_row.len() as i32;
-                    }
-                    PAGE_UP!() => {
-                        let mut times = terminal.screen_rows;
-                        while times > 0 {
-                            match editorMoveCursor(terminal,  ARROW_UP!() ) {
-                                Ok(_t) => { times -= 1; }
-                                Err(e) => return Err(Error::new(Other, e)),
-                            };
-                        }
-                    }
-                    PAGE_DOWN!() => {
-                        let mut times = terminal.screen_rows;
-                        while times > 0 {
-                            match editorMoveCursor(terminal, ARROW_DOWN!() ) {
-                                Ok(_t) => { times -= 1; }
-                                Err(e) => return Err(Error::new(Other, e)),
-                            };
-                        }
-                    }
-                    ENTER_KEY!() => {
-                        terminal.content.push(String::new());
-                        terminal.curs_y += 1;
+    let result = match editorNextKey(terminal, &mut input_buf) {
+        Ok(keyPressed) => match editorMapKeys(terminal, keyPressed)? {
+            Some((command, keys)) => editorRunCommand(terminal, command, &keys),
+            None => match terminal.mode {
+                Mode::Normal => editorNormalKey(terminal, keyPressed),
+                Mode::Insert => editorInsertKey(terminal, keyPressed, &input_buf),
+                Mode::Command => editorCommandKey(terminal, keyPressed, &input_buf),
+                Mode::Visual(_) => editorVisualKey(terminal, keyPressed),
+            },
+        },
+        Err(_e) => Err(Error::new(Other, "failed at editorReadKey")),
+    };
+    //a normal mode command is one undo step, so is everything typed in one go in insert mode
+    if terminal.mode != Mode::Insert {
+        editorHexSync(terminal);
+        terminal.bufMut().undo.seal();
+        terminal.bufMut().clampNormal();
+        editorLargeSlide(terminal);
+    }
+    result
+}
+
+// follow `key` through the keymap, waiting for more keys while they could still finish a longer binding.
+// Keys typed past the longest binding found are handled as if they were typed next.
+// None if the keys aren't bound to anything
+fn editorMapKeys(terminal: &mut Terminal, key: i32) -> io::Result<Option<(Command, Vec<i32>)>> {
+    //read-only buffers have pager keys over the normal ones, as long as they don't finish another command.
+    //Directory listings have their own keys over those
+    let modes = match MapMode::of(terminal.mode) {
+        MapMode::Normal if terminal.buf().listing.is_some() && terminal.pending.is_empty() => {
+            vec![MapMode::Browse, MapMode::Pager, MapMode::Normal]
+        }
+        MapMode::Normal if terminal.buf().options.readonly && terminal.pending.is_empty() => {
+            vec![MapMode::Pager, MapMode::Normal]
+        }
+        mode => vec![mode],
+    };
+    let mut keys = vec![key];
+    let mut found = None;
+    loop {
+        let (command, longer) = terminal.keymap.lookup(&modes, &keys);
+        if let Some(command) = command {
+            found = Some((command.clone(), keys.len()));
+        }
+        if !longer {
+            break;
+        }
+        match editorNextKeyWithin(terminal, terminal.options.timeoutlen)? {
+            Some(next) => keys.push(next),
+            None => break,
+        }
+    }
+    let used = found.as_ref().map_or(1, |(_, len)| *len);
+    for key in keys[used..].iter().rev() {
+        terminal.macro_queue.push_front(*key);
+    }
+    Ok(found.map(|(command, len)| (command, keys[..len].to_vec())))
+}
+
+// do what a key binding says. `keys` are the keys that were pressed for it
+fn editorRunCommand(terminal: &mut Terminal, command: Command, keys: &[i32]) -> io::Result<bool> {
+    //pressing the last key again goes through with quitting or closing
+    let again = *keys.last().unwrap_or(&0);
+    match command {
+        Command::Quit => {
+            if terminal.hasUnsavedBuffers() {
+                let msg = format!("Unsaved changes! Press {} again to quit anyway", keyName(again));
+                if !editorConfirm(terminal, &msg, again)? {
+                    return Ok(false);
+                }
+            }
+            return Ok(true); //exit the program
+        }
+        Command::Save => {
+            terminal.editorWriteFile(false)?;
+        }
+        Command::ClearLine => {
+            terminal.checkModifiable()?;
+            terminal.bufMut().clearLine();
+        }
+        Command::Undo => editorUndo(terminal)?,
+        Command::Redo => editorRedo(terminal)?,
+        Command::OpenFile => {
+            //open another file in a new buffer
+            match editorPrompt(terminal, "Open file: ")? {
+                Some(fp) if !fp.is_empty() => terminal.editorOpenFile(&fp)?,
+                _ => {}
+            }
+        }
+        Command::NextBuffer => terminal.editorSwitchBuffer(1),
+        Command::PrevBuffer => terminal.editorSwitchBuffer(-1),
+        Command::ListBuffers => {
+            let list = terminal.editorListBuffers();
+            terminal.setMessage(&list);
+        }
+        Command::CloseBuffer => {
+            //close the buffer, asking first if it has unsaved changes
+            let force = terminal.buf().isDirty() && {
+                let msg = format!(
+                    "{} has unsaved changes! Press {} again to close anyway",
+                    terminal.buf().name(),
+                    keyName(again)
+                );
+                editorConfirm(terminal, &msg, again)?
+            };
+            terminal.editorCloseBuffer(force)?;
+        }
+        Command::Close => {
+            if terminal.windows.len() > 1 {
+                terminal.editorCloseWindow()?;
+            } else if terminal.buffers.len() > 1 {
+                terminal.editorCloseBuffer(false)?;
+            } else {
+                return editorRunCommand(terminal, Command::Quit, keys);
+            }
+        }
+        //window commands take a second key
+        Command::Window => editorWindowCommand(terminal)?,
+        Command::PageUp => editorScrollPage(terminal, PAGE_UP!())?,
+        Command::PageDown => editorScrollPage(terminal, PAGE_DOWN!())?,
+        Command::DeleteChar => {
+            terminal.checkModifiable()?;
+            terminal.bufMut().deleteChar();
+        }
+        Command::SearchForward | Command::SearchBackward => {
+            let forward = command == Command::SearchForward;
+            if let Some(pattern) = editorPrompt(terminal, if forward { "/" } else { "?" })? {
+                //an empty pattern searches for the last one again
+                if !pattern.is_empty() {
+                    terminal.last_pattern = Some(pattern);
+                }
+                terminal.search_forward = forward;
+                editorSearch(terminal, forward)?;
+            }
+        }
+        Command::SearchNext => editorSearch(terminal, terminal.search_forward)?,
+        Command::SearchPrev => editorSearch(terminal, !terminal.search_forward)?,
+        Command::BrowseOpen => editorBrowseOpen(terminal)?,
+        Command::BrowseUp => editorBrowseUp(terminal)?,
+        Command::BrowseNewFile => editorBrowseNewFile(terminal)?,
+        Command::BrowseNewDir => editorBrowseNewDir(terminal)?,
+        Command::BrowseRename => editorBrowseRename(terminal)?,
+        Command::BrowseDelete => editorBrowseDelete(terminal)?,
+        Command::FindFile => editorFindFile(terminal, "")?,
+        Command::Nop => {}
+        Command::Ex(line) => return executeEx(terminal, &line),
+    }
+    Ok(false)
+}
 
-                        let invalidString = String::from("");
-                        let curr_row = terminal.content.get(terminal.curs_y as usize).unwrap_or(&invalidString);
+// keys in normal mode are commands - they pile up until they make a whole one
+fn editorNormalKey(terminal: &mut Terminal, keyPressed: i32) -> io::Result<bool> {
+    match keyPressed {
+        0x1b => {
+            terminal.pending.clear(); //escape cancels a half typed command
+            return Ok(false);
+        }
+        //q on its own stops recording
+        0x71 if terminal.pending.is_empty() && terminal.recording.is_some() => {
+            editorStopRecording(terminal);
+            return Ok(false);
+        }
+        _ => {}
+    }
 
-                        if terminal.curs_x >= curr_row.len() as i32 {
-                            //if we exceed the boundary for our new row,
-                            // snap back to last character in the row
-                            terminal.curs_x = curr_row.len() as i32;
-                        }
+    terminal.pending.push(keyPressed);
+    match parseNormal(&terminal.pending) {
+        Parse::Incomplete => Ok(false),
+        Parse::Invalid => {
+            terminal.pending.clear();
+            Ok(false)
+        }
+        Parse::Complete(cmd) => {
+            terminal.pending.clear();
+            editorExecuteNormal(terminal, cmd)
+        }
+    }
+}
 
+// move the cursor with a motion - false if the motion failed
+pub(crate) fn editorApplyMotion(terminal: &mut Terminal, motion: Motion, count: Option<usize>) -> bool {
+    let from = Pos { y: terminal.buf().curs_y, x: terminal.buf().curs_x };
+    let target = match motion {
+        //a file mark in a file that was closed opens it again
+        Motion::ToMark { name, exact } if terminal.mode == Mode::Normal && terminal.file_marks.contains_key(&name) => {
+            if let Err(e) = terminal.editorReopenMark(name) {
+                terminal.setMessage(&e.to_string());
+                return false;
+            }
+            match terminal.findMark(name) {
+                Some((buf, pos)) => {
+                    let pos = markPos(&terminal.buffers[buf].content, pos, exact);
+                    terminal.goTo(buf, pos);
+                    return true;
+                }
+                None => None,
+            }
+        }
+        Motion::ToMark { name, exact } => match terminal.findMark(name) {
+            //a file mark in another buffer takes us there, but not from the middle of a selection
+            Some((buf, pos)) if buf != terminal.currBuf() => {
+                if terminal.mode == Mode::Normal {
+                    terminal.pushJump();
+                    let pos = markPos(&terminal.buffers[buf].content, pos, exact);
+                    terminal.goTo(buf, pos);
+                    return true;
+                }
+                None
+            }
+            Some((_, pos)) => Some(markPos(&terminal.buf().content, pos, exact)),
+            None => {
+                terminal.setMessage("Mark not set");
+                None
+            }
+        },
+        //gg and G in a large file load the part they go to
+        Motion::FileStart | Motion::FileEnd if terminal.buf().large.is_some() && terminal.mode == Mode::Normal => {
+            let line = match count {
+                Some(line) => Some(line.max(1) - 1),
+                None if motion == Motion::FileStart => Some(0),
+                None => None,
+            };
+            terminal.pushJump();
+            match editorLargeGoto(terminal, line) {
+                Ok(y) => {
+                    let buffer = terminal.bufMut();
+                    buffer.curs_y = y as i32;
+                    buffer.curs_x = firstNonBlank(buffer.currRow());
+                    return true;
+                }
+                Err(e) => {
+                    terminal.setMessage(&e.to_string());
+                    None
+                }
+            }
+        }
+        _ => {
+            let mut last_find = terminal.last_find;
+            let target = applyMotion(&terminal.buf().content, from, motion, count, &mut last_find);
+            terminal.last_find = last_find;
+            target
+        }
+    };
+    match target {
+        Some(pos) => {
+            if motion.isJump() {
+                terminal.pushJump();
+            }
+            let buffer = terminal.bufMut();
+            buffer.curs_y = pos.y;
+            buffer.curs_x = pos.x;
+            true
+        }
+        None => {
+            //a motion that can't be made stops any macro being played
+            terminal.macro_queue.clear();
+            false
+        }
+    }
+}
+
+// where jumping to a mark lands - 'a goes to the first non-blank of the line, `a to the exact spot
+fn markPos(content: &[String], pos: Pos, exact: bool) -> Pos {
+    let line = &content[pos.y as usize];
+    if exact {
+        Pos { y: pos.y, x: pos.x.min((lineLen(line) - 1).max(0)) }
+    } else {
+        Pos { y: pos.y, x: firstNonBlank(line) }
+    }
+}
+
+fn editorExecuteNormal(terminal: &mut Terminal, cmd: NormalCmd) -> io::Result<bool> {
+    let times = cmd.count.unwrap_or(1).max(1);
+    if cmd.action.isChange() {
+        terminal.checkModifiable()?;
+        terminal.last_change = Some(Change { repeat: Repeat::Normal(cmd), inserted: Vec::new() });
+    }
+    match cmd.action {
+        Action::Move(motion) => {
+            editorApplyMotion(terminal, motion, cmd.count);
+        }
+        Action::Insert(at) => {
+            let buffer = terminal.bufMut();
+            match at {
+                InsertAt::Cursor => {}
+                InsertAt::After => {
+                    if !buffer.currRow().is_empty() {
+                        buffer.curs_x += 1;
                     }
-                    //trigger cursor movement
-                    ARROW_UP!() => {
-                        return match editorMoveCursor(terminal, keyPressed) {
-                            Ok(_t) => Ok(false),
-                            Err(e) => Err(Error::new(Other, e)),
-                        };
-                    }
-                    ARROW_DOWN!() => {
-                        return match editorMoveCursor(terminal, keyPressed) {
-                            Ok(_t) => Ok(false),
-                            Err(e) => Err(Error::new(Other, e)),
-                        };
-                    }
-                    ARROW_LEFT!() => {
-                        return match editorMoveCursor(terminal, keyPressed) {
-                            Ok(_t) => Ok(false),
-                            Err(e) => Err(Error::new(Other, e)),
-                        };
-                    }
-                    ARROW_RIGHT!() => {
-                        return match editorMoveCursor(terminal, keyPressed) {
-                            Ok(_t) => Ok(false),
-                            Err(e) => Err(Error::new(Other, e)),
-                        };
-                    }
-                    BACKSPACE_KEY!() => {
-                        if terminal.curs_x > 0 { // constrain backspace to beginning of line
-                            terminal.content[terminal.curs_y as usize].remove(terminal.curs_x  as usize -1);
-                            terminal.curs_x -= 1;
-                        } else if terminal.curs_x == 0 && terminal.curs_y > 0 {
-                            terminal.content.pop();
-                            terminal.curs_y -= 1;
-
-                            let invalidString = String::from("");
-                            let curr_row = terminal.content.get(terminal.curs_y as usize).unwrap_or(&invalidString);
-
-                            terminal.curs_x = curr_row.len() as i32;
-                        }
-                    }
-                    //default typing behavior
-                    _ => {
-                        terminal.content[terminal.curs_y as usize].insert(terminal.curs_x as usize, input_buf.chars().next().unwrap());
-                        terminal.curs_x += 1;
+                }
+                InsertAt::LineStart => buffer.curs_x = firstNonBlank(buffer.currRow()),
+                InsertAt::LineEnd => buffer.curs_x = lineLen(buffer.currRow()),
+                InsertAt::LineBelow | InsertAt::LineAbove => {
+                    let indent = indentOf(buffer.currRow()).to_string();
+                    buffer.openLine(at == InsertAt::LineBelow);
+                    if buffer.options.autoindent {
+                        buffer.insertStr(&indent);
                     }
                 }
-                Ok(false)
             }
+            terminal.mode = Mode::Insert;
+        }
+        Action::Operate { op, force, target } => {
+            editorOperate(terminal, op, force, target, cmd.count, cmd.register)?;
+        }
+        Action::ToggleCaseChar => {
+            let buffer = terminal.bufMut();
+            let len = lineLen(buffer.currRow());
+            if len > 0 {
+                let start = Pos { y: buffer.curs_y, x: buffer.curs_x };
+                let end = Pos { y: buffer.curs_y, x: (buffer.curs_x + times as i32).min(len) };
+                let range = TextRange { start, end, kind: RangeKind::Charwise };
+                applyOperator(buffer, Operator::ToggleCase, &range, 1);
+                buffer.curs_x = end.x;
+            }
+        }
+        Action::Put { before } => {
+            let register = match terminal.registers.get(cmd.register) {
+                Some(register) => register.clone(),
+                None => {
+                    let name = cmd.register.unwrap_or('"');
+                    return Err(Error::new(Other, format!("Nothing in register {}", name)));
+                }
+            };
+            let added = putRegister(terminal.bufMut(), &register, before, times);
+            if added > 2 {
+                terminal.setMessage(&format!("{} more lines", added));
+            }
+        }
+        Action::Undo => {
+            for _ in 0..times {
+                editorUndo(terminal)?;
+            }
+        }
+        Action::Redo => {
+            for _ in 0..times {
+                editorRedo(terminal)?;
+            }
+        }
+        Action::CommandLine => {
+            terminal.cmdline.clear();
+            terminal.mode = Mode::Command;
+        }
+        Action::RepeatChange => editorRepeatChange(terminal, cmd.count)?,
+        Action::SetMark(name) => terminal.setMark(name),
+        Action::JumpOlder => {
+            let current = Jump { buf: terminal.currBuf(), pos: Pos { y: terminal.buf().curs_y, x: terminal.buf().curs_x } };
+            if let Some(jump) = terminal.jumps.older(current, times) {
+                terminal.goTo(jump.buf, jump.pos);
+            }
+        }
+        Action::JumpNewer => {
+            if let Some(jump) = terminal.jumps.newer(times) {
+                terminal.goTo(jump.buf, jump.pos);
+            }
+        }
+        Action::RecordMacro(name) => terminal.recording = Some((name, Vec::new())),
+        Action::PlayMacro(name) => editorPlayMacro(terminal, name, times)?,
+        Action::Visual(kind) => terminal.editorEnterVisual(kind),
+        Action::Reselect => {
+            if let Some(selection) = terminal.buf().last_visual {
+                let buffer = terminal.bufMut();
+                buffer.curs_y = selection.cursor.y;
+                buffer.curs_x = selection.cursor.x;
+                buffer.clampCursor();
+                terminal.visual_anchor = selection.anchor;
+                terminal.visual_eol = selection.to_eol;
+                terminal.mode = Mode::Visual(selection.kind);
+            }
+        }
+    }
+    Ok(false)
+}
+
+// work out the range an operator covers and run it
+fn editorOperate(
+    terminal: &mut Terminal,
+    op: Operator,
+    force: Option<ForceKind>,
+    target: OpTarget,
+    count: Option<usize>,
+    name: Option<char>,
+) -> io::Result<()> {
+    let from = Pos { y: terminal.buf().curs_y, x: terminal.buf().curs_x };
+    let times = count.unwrap_or(1).max(1);
+    let content = &terminal.buf().content;
+    let last = content.len() as i32 - 1;
+    let range = match target {
+        OpTarget::Lines => {
+            //there have to be that many lines left
+            if from.y + times as i32 - 1 > last {
+                None
+            } else {
+                let end = Pos { y: from.y + times as i32 - 1, x: 0 };
+                Some(motionRange(content, from, end, MotionKind::Linewise, force))
+            }
+        }
+        OpTarget::Object(object) => selectObject(content, from, object, times),
+        //under an operator `l` may go one past the last character, that's how x deletes it
+        OpTarget::Motion(Motion::Right) => {
+            let len = lineLen(&content[from.y as usize]);
+            if from.x >= len {
+                None
+            } else {
+                let to = Pos { y: from.y, x: (from.x + times as i32).min(len) };
+                Some(motionRange(content, from, to, MotionKind::Exclusive, force))
+            }
+        }
+        //a mark in another buffer can't be used with an operator
+        OpTarget::Motion(Motion::ToMark { name, exact }) => match terminal.findMark(name) {
+            Some((buf, pos)) if buf == terminal.currBuf() => {
+                let to = markPos(content, pos, exact);
+                Some(motionRange(content, from, to, MotionKind::Exclusive, force))
+            }
+            _ => None,
+        },
+        OpTarget::Motion(motion) => {
+            let mut last_find = terminal.last_find;
+            let to = applyMotion(content, from, motion, count, &mut last_find);
+            terminal.last_find = last_find;
+            let content = &terminal.buf().content;
+            to.map(|to| motionRange(content, from, to, motion.kind(), force))
+        }
+    };
+    let range = match range {
+        Some(range) => range,
+        None => {
+            //same as a failed motion - stop any macro
+            terminal.macro_queue.clear();
+            return Ok(());
+        }
+    };
+
+    let shift = terminal.buf().options.shiftwidth;
+    editorApplyOperator(terminal, op, &range, name, shift)
+}
+
+// run an operator over a range, keeping whatever text it yanked or deleted in a register
+fn editorApplyOperator(
+    terminal: &mut Terminal,
+    op: Operator,
+    range: &TextRange,
+    name: Option<char>,
+    shift: usize,
+) -> io::Result<()> {
+    let register = applyOperator(terminal.bufMut(), op, range, shift);
+    if let Some(register) = register {
+        let lines = register.text.len();
+        if op == Operator::Yank && register.kind == RangeKind::Linewise && lines > 2 {
+            terminal.setMessage(&format!("{} lines yanked", lines));
+        } else if op == Operator::Delete && register.kind == RangeKind::Linewise && lines > 2 {
+            terminal.setMessage(&format!("{} fewer lines", lines));
+        }
+        terminal.storeRegister(name, register, op == Operator::Yank)?;
+    }
+    if op == Operator::Change {
+        terminal.mode = Mode::Insert;
+    }
+    Ok(())
+}
+
+// keys in visual mode move the cursor to grow the selection, or act on it
+fn editorVisualKey(terminal: &mut Terminal, keyPressed: i32) -> io::Result<bool> {
+    if keyPressed == 0x1b {
+        if terminal.pending.is_empty() {
+            terminal.editorExitVisual();
+        }
+        terminal.pending.clear();
+        return Ok(false);
+    }
+
+    terminal.pending.push(keyPressed);
+    match parseVisual(&terminal.pending) {
+        Parse::Incomplete => Ok(false),
+        Parse::Invalid => {
+            terminal.pending.clear();
+            Ok(false)
+        }
+        Parse::Complete(cmd) => {
+            terminal.pending.clear();
+            editorExecuteVisual(terminal, cmd)?;
+            Ok(false)
+        }
+    }
+}
+
+fn editorExecuteVisual(terminal: &mut Terminal, cmd: VisualCmd) -> io::Result<()> {
+    let selection = match terminal.currSelection() {
+        Some(selection) => selection,
+        None => return Ok(()),
+    };
+    if cmd.action.isChange() {
+        terminal.checkModifiable()?;
+    }
+    let times = cmd.count.unwrap_or(1).max(1);
+    match cmd.action {
+        VisualAction::Move(motion) => {
+            if editorApplyMotion(terminal, motion, cmd.count) {
+                terminal.visual_eol = motion == Motion::LineEnd;
+            }
+        }
+        VisualAction::Object(object) => {
+            let content = &terminal.buf().content;
+            let range = match selectObject(content, selection.cursor, object, times) {
+                Some(range) => range,
+                None => return Ok(()),
+            };
+            let (anchor, cursor) = if range.kind == RangeKind::Linewise {
+                terminal.mode = Mode::Visual(VisualKind::Line);
+                (Pos { y: range.start.y, x: 0 }, Pos { y: range.end.y, x: 0 })
+            } else {
+                //the range ends just after the object, the cursor goes on its last character
+                let end = if range.end.x > 0 || range.end.y == range.start.y {
+                    Pos { y: range.end.y, x: (range.end.x - 1).max(0) }
+                } else {
+                    let y = range.end.y - 1;
+                    Pos { y, x: (lineLen(&content[y as usize]) - 1).max(0) }
+                };
+                if selection.kind == VisualKind::Line {
+                    terminal.mode = Mode::Visual(VisualKind::Char);
+                }
+                (range.start, end)
+            };
+            terminal.visual_anchor = anchor;
+            let buffer = terminal.bufMut();
+            buffer.curs_y = cursor.y;
+            buffer.curs_x = cursor.x;
+        }
+        VisualAction::Switch(kind) => {
+            if selection.kind == kind {
+                terminal.editorExitVisual();
+            } else {
+                terminal.mode = Mode::Visual(kind);
+            }
+        }
+        VisualAction::SwapEnds { same_line } => {
+            //O in block mode goes to the other corner on the same line
+            let (anchor, cursor) = if same_line && selection.kind == VisualKind::Block {
+                (
+                    Pos { y: selection.anchor.y, x: selection.cursor.x },
+                    Pos { y: selection.cursor.y, x: selection.anchor.x },
+                )
+            } else {
+                (selection.cursor, selection.anchor)
+            };
+            terminal.visual_anchor = anchor;
+            let buffer = terminal.bufMut();
+            buffer.curs_y = cursor.y;
+            buffer.curs_x = cursor.x;
+        }
+        VisualAction::Operate(op) | VisualAction::OperateLines(op) => {
+            if op != Operator::Yank {
+                editorRecordVisualChange(terminal, cmd, &selection);
+            }
+            terminal.editorExitVisual();
+            let range = match cmd.action {
+                VisualAction::OperateLines(_) => TextRange {
+                    start: Pos { y: selection.top(), x: 0 },
+                    end: Pos { y: selection.bottom(), x: 0 },
+                    kind: RangeKind::Linewise,
+                },
+                _ => selection.range(&terminal.buf().content),
+            };
+            //a count shifts that many times over
+            let shift = terminal.buf().options.shiftwidth * times;
+            editorApplyOperator(terminal, op, &range, cmd.register, shift)?;
+            //changing a block types the same thing on every line
+            if op == Operator::Change && range.kind == RangeKind::Blockwise {
+                editorStartBlockInsert(terminal, &selection, false);
+            }
+        }
+        VisualAction::BlockInsert { append } => {
+            if selection.kind != VisualKind::Block {
+                return Ok(());
+            }
+            editorRecordVisualChange(terminal, cmd, &selection);
+            terminal.editorExitVisual();
+            editorStartBlockInsert(terminal, &selection, append);
+        }
+        VisualAction::CommandLine => {
+            //the command works on the selected lines
+            terminal.editorExitVisual();
+            terminal.cmdline = String::from("'<,'>");
+            terminal.mode = Mode::Command;
+        }
+    }
+    Ok(())
+}
+
+// remember a change made to a selection - `.` makes it again on the same amount of text
+fn editorRecordVisualChange(terminal: &mut Terminal, cmd: VisualCmd, selection: &Selection) {
+    let (start, end) = if selection.anchor <= selection.cursor {
+        (selection.anchor, selection.cursor)
+    } else {
+        (selection.cursor, selection.anchor)
+    };
+    let lines = end.y - start.y;
+    let extent = match selection.kind {
+        VisualKind::Char if lines > 0 => Pos { y: lines, x: end.x },
+        VisualKind::Char => Pos { y: 0, x: end.x - start.x },
+        _ => Pos { y: lines, x: (selection.anchor.x - selection.cursor.x).abs() },
+    };
+    let repeat = Repeat::Visual { cmd, kind: selection.kind, extent, to_eol: selection.to_eol };
+    terminal.last_change = Some(Change { repeat, inserted: Vec::new() });
+}
+
+// `.` - make the last change again at the cursor, with a new count if one was given
+fn editorRepeatChange(terminal: &mut Terminal, count: Option<usize>) -> io::Result<()> {
+    let change = match terminal.last_change.clone() {
+        Some(change) => change,
+        None => return Ok(()),
+    };
+    match change.repeat {
+        Repeat::Normal(mut cmd) => {
+            if count.is_some() {
+                cmd.count = count;
+            }
+            //repeating "1p puts from "2, then "3 and so on, to step back through old deletes
+            if let Some(c @ '1'..='8') = cmd.register {
+                if matches!(cmd.action, Action::Put { .. }) {
+                    cmd.register = Some((c as u8 + 1) as char);
+                }
+            }
+            editorExecuteNormal(terminal, cmd)?;
+        }
+        Repeat::Visual { cmd, kind, extent, to_eol } => {
+            let buffer = terminal.bufMut();
+            let start = Pos { y: buffer.curs_y, x: buffer.curs_x };
+            let last = buffer.content.len() as i32 - 1;
+            let y = (start.y + extent.y).min(last);
+            let x = match kind {
+                VisualKind::Char if extent.y > 0 => extent.x,
+                _ => start.x + extent.x,
+            };
+            let x = if kind == VisualKind::Block { x } else { x.min((lineLen(&buffer.content[y as usize]) - 1).max(0)) };
+            buffer.curs_y = y;
+            buffer.curs_x = x;
+            terminal.visual_anchor = start;
+            terminal.visual_eol = to_eol;
+            terminal.mode = Mode::Visual(kind);
+            editorExecuteVisual(terminal, cmd)?;
+        }
+    }
+    //type the same text again
+    for (key, text) in &change.inserted {
+        if terminal.mode != Mode::Insert {
+            break;
+        }
+        editorInsertKey(terminal, *key, text)?;
+    }
+    if terminal.mode == Mode::Insert {
+        editorInsertKey(terminal, 0x1b, "")?;
+    }
+    Ok(())
+}
+
+// finish recording a macro and keep it in its register as text
+fn editorStopRecording(terminal: &mut Terminal) {
+    if let Some((name, mut keys)) = terminal.recording.take() {
+        keys.pop(); //the q that stopped it
+        terminal.registers.storeMacro(name, keysToText(&keys));
+    }
+}
+
+// play the keys kept in a register `count` times
+fn editorPlayMacro(terminal: &mut Terminal, name: char, count: usize) -> io::Result<()> {
+    let name = match name {
+        '@' => terminal.last_macro.ok_or_else(|| Error::new(Other, "No previously used register"))?,
+        name => name,
+    };
+    let register = match terminal.registers.get(Some(name)) {
+        Some(register) => register,
+        None => return Err(Error::new(Other, format!("Nothing in register {}", name))),
+    };
+    let mut text = register.text.join("\n");
+    if register.kind == RangeKind::Linewise {
+        text.push('\n');
+    }
+    let keys = textToKeys(&text);
+    if keys.len().saturating_mul(count) + terminal.macro_queue.len() > MAX_MACRO_KEYS {
+        terminal.macro_queue.clear();
+        return Err(Error::new(Other, "Macro too long"));
+    }
+    //these go before anything already queued, so a macro can play another one
+    for _ in 0..count {
+        for key in keys.iter().rev() {
+            terminal.macro_queue.push_front(*key);
+        }
+    }
+    terminal.last_macro = Some(name);
+    Ok(())
+}
+
+// I or A on a block - start typing on its first line
+fn editorStartBlockInsert(terminal: &mut Terminal, selection: &Selection, append: bool) {
+    let top = selection.top();
+    let left = selection.anchor.x.min(selection.cursor.x);
+    let right = selection.anchor.x.max(selection.cursor.x) + 1;
+    let to_eol = append && selection.to_eol;
+    let buffer = terminal.bufMut();
+    let mut line = buffer.content[top as usize].clone();
+    let col = if to_eol {
+        lineLen(&line)
+    } else if append {
+        //appending past the end of a short line pads it out first
+        let len = lineLen(&line);
+        if len < right {
+            line.push_str(&" ".repeat((right - len) as usize));
+            buffer.setLine(top as usize, line.clone());
+        }
+        right
+    } else {
+        left
+    };
+    buffer.curs_y = top;
+    buffer.curs_x = col.min(lineLen(&line));
+    terminal.block_insert = Some(BlockInsert {
+        top,
+        bottom: selection.bottom(),
+        col,
+        append,
+        to_eol,
+        start_len: lineLen(&line),
+    });
+    terminal.mode = Mode::Insert;
+}
+
+fn editorUndo(terminal: &mut Terminal) -> io::Result<()> {
+    terminal.checkModifiable()?;
+    if !terminal.bufMut().undo() {
+        terminal.setMessage("Already at oldest change");
+    }
+    Ok(())
+}
+
+fn editorRedo(terminal: &mut Terminal) -> io::Result<()> {
+    terminal.checkModifiable()?;
+    if !terminal.bufMut().redo() {
+        terminal.setMessage("Already at newest change");
+    }
+    Ok(())
+}
+
+// go to the next match of the last pattern, wrapping around the end of the buffer
+fn editorSearch(terminal: &mut Terminal, forward: bool) -> io::Result<()> {
+    let source = match &terminal.last_pattern {
+        Some(source) => source.clone(),
+        None => return Err(Error::new(Other, "No previous regular expression")),
+    };
+    let pattern = Pattern::new(&source, terminal.options.ignorecase).map_err(|e| Error::new(Other, e))?;
+    let from = Pos { y: terminal.buf().curs_y, x: terminal.buf().curs_x };
+    match pattern.search(&terminal.buf().content, from, forward) {
+        Some(pos) => {
+            terminal.pushJump();
+            let buffer = terminal.bufMut();
+            buffer.curs_y = pos.y;
+            buffer.curs_x = pos.x;
+            Ok(())
+        }
+        None => Err(Error::new(Other, format!("Pattern not found: {}", source))),
+    }
+}
+
+// move the cursor a window's worth of rows up or down
+fn editorScrollPage(terminal: &mut Terminal, key: i32) -> io::Result<()> {
+    let direction = if key == PAGE_UP!() { ARROW_UP!() } else { ARROW_DOWN!() };
+    let mut times = terminal.winRows();
+    while times > 0 {
+        match editorMoveCursor(terminal, direction) {
+            Ok(_t) => { times -= 1; }
+            Err(e) => return Err(Error::new(Other, e)),
+        };
+    }
+    Ok(())
+}
+
+// keys in insert mode are typed into the buffer
+fn editorInsertKey(terminal: &mut Terminal, keyPressed: i32, input_buf: &str) -> io::Result<bool> {
+    //whatever gets typed is part of the change that started insert mode
+    if let Some(change) = terminal.last_change.as_mut() {
+        change.inserted.push((keyPressed, input_buf.to_string()));
+    }
+    match keyPressed {
+        HOME_KEY!() => {
+            terminal.bufMut().curs_x = 0;
+        }
+        END_KEY!() => {
+            let buffer = terminal.bufMut();
+            buffer.curs_x = lineLen(buffer.currRow());
+        }
+        0x1b => {
+            //back to normal mode, stepping back onto the last character typed like vim does
+            terminal.mode = Mode::Normal;
+            if let Some(block) = terminal.block_insert.take() {
+                applyBlockInsert(terminal.bufMut(), &block);
+            }
+            let buffer = terminal.bufMut();
+            buffer.curs_x = (buffer.curs_x - 1).max(0);
+        }
+        DEL_KEY!() => {
+            terminal.bufMut().deleteChar();
+        }
+        ENTER_KEY!() => {
+            let buffer = terminal.bufMut();
+            let indent = indentOf(buffer.currRow()).to_string();
+            buffer.insertNewline();
+            if buffer.options.autoindent {
+                buffer.insertStr(&indent);
+            }
+        }
+        0x09 => {
+            //with expandtab, spaces up to where the tab would have reached
+            let buffer = terminal.bufMut();
+            if buffer.options.expandtab {
+                let tabstop = buffer.options.tabstop;
+                let col = displayCol(buffer.currRow(), buffer.curs_x, tabstop) as usize;
+                buffer.insertStr(&" ".repeat(tabstop - col % tabstop));
+            } else {
+                buffer.insertChar('\t');
+            }
+        }
+        //trigger cursor movement
+        ARROW_UP!() => {
+            return match editorMoveCursor(terminal, keyPressed) {
+                Ok(_t) => Ok(false),
+                Err(e) => Err(Error::new(Other, e)),
+            };
+        }
+        ARROW_DOWN!() => {
+            return match editorMoveCursor(terminal, keyPressed) {
+                Ok(_t) => Ok(false),
+                Err(e) => Err(Error::new(Other, e)),
+            };
+        }
+        ARROW_LEFT!() => {
+            return match editorMoveCursor(terminal, keyPressed) {
+                Ok(_t) => Ok(false),
+                Err(e) => Err(Error::new(Other, e)),
+            };
+        }
+        ARROW_RIGHT!() => {
+            return match editorMoveCursor(terminal, keyPressed) {
+                Ok(_t) => Ok(false),
+                Err(e) => Err(Error::new(Other, e)),
+            };
+        }
+        BACKSPACE_KEY!() => {
+            terminal.bufMut().deleteCharBefore();
+        }
+        //default typing behavior
+        _ => {
+            if let Some(c) = input_buf.chars().next() {
+                terminal.bufMut().insertChar(c);
+            }
+        }
+    }
+    Ok(false)
+}
+
+// keys in command line mode edit the `:` line until enter runs it
+fn editorCommandKey(terminal: &mut Terminal, keyPressed: i32, input_buf: &str) -> io::Result<bool> {
+    match keyPressed {
+        ENTER_KEY!() => {
+            terminal.mode = Mode::Normal;
+            let line = std::mem::take(&mut terminal.cmdline);
+            return executeEx(terminal, &line);
+        }
+        0x1b => {
+            terminal.mode = Mode::Normal;
+            terminal.cmdline.clear();
+        }
+        BACKSPACE_KEY!() => {
+            //backspacing over the `:` leaves the command line
+            if terminal.cmdline.pop().is_none() {
+                terminal.mode = Mode::Normal;
+            }
+        }
+        _ => {
+            if let Some(c) = input_buf.chars().next() {
+                if keyPressed < 1000 && !c.is_ascii_control() {
+                    terminal.cmdline.push(c);
+                }
+            }
+        }
+    }
+    Ok(false)
+}
+
+// the key after Ctrl-W picks what to do with the windows
+fn editorWindowCommand(terminal: &mut Terminal) -> io::Result<()> {
+    let key = editorNextKey(terminal, &mut String::new())?;
+    if key == b's' as i32 || key == b'S' as i32 || key == CTRL_KEY!(b's') as i32 {
+        terminal.editorSplitWindow(SplitDir::Horizontal)
+    } else if key == b'v' as i32 || key == CTRL_KEY!(b'v') as i32 {
+        terminal.editorSplitWindow(SplitDir::Vertical)
+    } else if key == b'c' as i32 || key == b'q' as i32 {
+        terminal.editorCloseWindow()
+    } else if key == b'o' as i32 {
+        terminal.editorOnlyWindow();
+        Ok(())
+    } else if key == b'w' as i32 || key == CTRL_KEY!(b'w') as i32 {
+        terminal.editorCycleWindow(1);
+        Ok(())
+    } else if key == b'W' as i32 {
+        terminal.editorCycleWindow(-1);
+        Ok(())
+    } else if key == b'+' as i32 {
+        terminal.editorResizeWindow(SplitDir::Horizontal, 1);
+        Ok(())
+    } else if key == b'-' as i32 {
+        terminal.editorResizeWindow(SplitDir::Horizontal, -1);
+        Ok(())
+    } else if key == b'>' as i32 {
+        terminal.editorResizeWindow(SplitDir::Vertical, 1);
+        Ok(())
+    } else if key == b'<' as i32 {
+        terminal.editorResizeWindow(SplitDir::Vertical, -1);
+        Ok(())
+    } else if key == b'=' as i32 {
+        terminal.editorEqualizeWindows();
+        Ok(())
+    } else {
+        Ok(()) //anything else cancels
+    }
+}
+
+// ask the user to press `key` again to go through with something
+fn editorConfirm(terminal: &mut Terminal, msg: &str, key: i32) -> io::Result<bool> {
+    terminal.setMessage(msg);
+    editorRefreshScreen(terminal)?;
+    let confirmed = editorNextKey(terminal, &mut String::new())? == key;
+    terminal.setMessage("");
+    Ok(confirmed)
+}
+
+// ask a question in the message bar - the answer is the first key pressed that's one of `choices`,
+// None if the user backs out with escape
+pub(crate) fn editorChoose(terminal: &mut Terminal, msg: &str, choices: &str) -> io::Result<Option<char>> {
+    terminal.setMessage(msg);
+    editorRefreshScreen(terminal)?;
+    loop {
+        let key = editorNextKey(terminal, &mut String::new())?;
+        let choice = choices.chars().find(|c| *c as i32 == key);
+        if key == 0x1b || choice.is_some() {
+            terminal.setMessage("");
+            return Ok(choice);
         }
-        Err(_e) => Err(Error::new(Other, "failed at editorReadKey")),
     }
 }
 
+// read a line of text in the message bar - None if the user backs out with escape
+pub(crate) fn editorPrompt(terminal: &mut Terminal, prompt: &str) -> io::Result<Option<String>> {
+    let mut input = String::new();
+    loop {
+        terminal.setMessage(&format!("{}{}", prompt, input));
+        editorRefreshScreen(terminal)?;
+
+        let mut key_buf = String::new();
+        match editorNextKey(terminal, &mut key_buf)? {
+            ENTER_KEY!() => {
+                terminal.setMessage("");
+                return Ok(Some(input));
+            }
+            0x1b => {
+                terminal.setMessage("");
+                return Ok(None);
+            }
+            BACKSPACE_KEY!() => {
+                input.pop();
+            }
+            key => {
+                if key < 1000 && !(key as u8).is_ascii_control() {
+                    input.push(key as u8 as char);
+                }
+            }
+        }
+    }
+}
+
+// next key to act on - keys from a macro being played come first, then the keyboard.
+// Keys from the keyboard are kept if a macro is being recorded
+pub(crate) fn editorNextKey(terminal: &mut Terminal, buf: &mut String) -> io::Result<i32> {
+    if let Some(key) = terminal.macro_queue.pop_front() {
+        if key < 256 {
+            buf.push(key as u8 as char);
+        }
+        return Ok(key);
+    }
+    //swap files are kept up to date while we wait
+    editorSyncSwaps(terminal, false);
+    let waiting = Instant::now();
+    let key = loop {
+        while stdin().lock().fill_buf()?.is_empty() {
+            if waiting.elapsed() >= SWAP_IDLE {
+                editorSyncSwaps(terminal, true);
+            }
+        }
+        //focus changes in the middle of a command are left for the next wait between commands
+        match editorReadKey(buf)? {
+            FOCUS_KEY!() => buf.clear(),
+            key => break key,
+        }
+    };
+    if let Some((_, keys)) = terminal.recording.as_mut() {
+        keys.push(key);
+    }
+    Ok(key)
+}
+
+// wait for the first key of a command. Files changed by other programs are checked for whenever the keyboard
+// has been quiet for a while, and when the terminal gets focus back
+fn editorAwaitKey(terminal: &mut Terminal) -> io::Result<()> {
+    editorSyncSwaps(terminal, false);
+    let mut waiting = Instant::now();
+    let mut counting = terminal.buf().totalLines().is_none();
+    loop {
+        let mut input = stdin().lock();
+        let pending = input.fill_buf()?;
+        let focus = [FOCUS_IN, FOCUS_OUT].into_iter().find(|seq| pending.starts_with(seq));
+        let check = match focus {
+            Some(seq) => {
+                input.consume(seq.len());
+                seq == FOCUS_IN
+            }
+            None if !pending.is_empty() => return Ok(()),
+            None => waiting.elapsed() >= SWAP_IDLE,
+        };
+        drop(input);
+        //line numbers of a large file show up as soon as they've been counted
+        if counting && terminal.buf().totalLines().is_some() {
+            counting = false;
+            editorRefreshScreen(terminal)?;
+        }
+        if check {
+            editorSyncSwaps(terminal, true);
+            if editorCheckDisk(terminal)? {
+                editorRefreshScreen(terminal)?;
+            }
+            waiting = Instant::now();
+        }
+    }
+}
+
+// the next key if one comes within `ms` milliseconds - used to finish chords
+fn editorNextKeyWithin(terminal: &mut Terminal, ms: u64) -> io::Result<Option<i32>> {
+    let deadline = Instant::now() + Duration::from_millis(ms);
+    //stdin is buffered, so look there rather than at the terminal. Each read gives up after 100ms
+    while terminal.macro_queue.is_empty() && stdin().lock().fill_buf()?.is_empty() {
+        if Instant::now() >= deadline {
+            return Ok(None);
+        }
+    }
+    editorNextKey(terminal, &mut String::new()).map(Some)
+}
+
 // process input
 pub(crate) fn editorReadKey(buf: &mut String) -> io::Result<i32> {
     let mut c = [0u8; 1];
@@ -201,7 +1192,7 @@ pub(crate) fn editorReadKey(buf: &mut String) -> io::Result<i32> {
 
         //if escape follows with a [
         // then it's an escape sequence
-        if seq[0] == '[' as u8 {
+        if seq[0] == b'[' {
             if seq[1] >= b'0' && seq[1] <= b'9' {
                 if seq[2] == b'~' {
                     return match seq[1] {
@@ -224,6 +1215,7 @@ pub(crate) fn editorReadKey(buf: &mut String) -> io::Result<i32> {
                     b'D' => Ok(ARROW_LEFT!()),
                     b'H' => Ok(HOME_KEY!()),
                     b'F' => Ok(END_KEY!()),
+                    b'I' | b'O' => Ok(FOCUS_KEY!()),
                     _ => Ok(b'\x1b' as i32),
                 };
             }
@@ -246,66 +1238,64 @@ pub(crate) fn editorReadKey(buf: &mut String) -> io::Result<i32> {
 }
 
 pub(crate) fn editorMoveCursor(terminal: &mut Terminal, key: i32) -> io::Result<()> {
-    let invalidString = String::from("");
-    let mut curr_row = terminal.content.get(terminal.curs_y as usize).unwrap_or(&invalidString);
+    let buffer = terminal.bufMut();
+    let mut curr_len = lineLen(buffer.currRow());
 
     //movement with bounds checking
     //left is 0
     //top is 0
     match key {
         ARROW_LEFT!() => {
-            if terminal.curs_x > 0 { //bounds checking
-                terminal.curs_x -= 1 // - means move left
+            if buffer.curs_x > 0 { //bounds checking
+                buffer.curs_x -= 1 // - means move left
             }
             //handle pressing left at start of line
-            if terminal.curs_x == 0
-                && terminal.curs_y > 0 {
+            if buffer.curs_x == 0
+                && buffer.curs_y > 0 {
                 //move cursor up 1 row
-                terminal.curs_y -= 1;
+                buffer.curs_y -= 1;
                 //recalculate the current row's length
-                curr_row = terminal.content.get(terminal.curs_y as usize).unwrap_or(&invalidString);
+                curr_len = lineLen(buffer.currRow());
                 //bring us to last character in previous row
-                terminal.curs_x = curr_row.len() as i32;
+                buffer.curs_x = curr_len;
             }
         }
         ARROW_RIGHT!() => {
-            if terminal.curs_x < curr_row.len() as i32 { //bounds checking
-                terminal.curs_x += 1 // + means move right
+            if buffer.curs_x < curr_len { //bounds checking
+                buffer.curs_x += 1 // + means move right
             }
             //handle pressing right at end of line
-            if terminal.curs_x == curr_row.len() as i32
-                && terminal.curs_y < terminal.content.len() as i32 - 1 {
+            if buffer.curs_x == curr_len
+                && buffer.curs_y < buffer.content.len() as i32 - 1 {
                 //move cursor down 1 row
-                terminal.curs_y += 1;
-                //recalculate the current row's length
-                curr_row = terminal.content.get(terminal.curs_y as usize).unwrap_or(&invalidString);
+                buffer.curs_y += 1;
                 //bring us to first character in next row
-                terminal.curs_x = 0;
+                buffer.curs_x = 0;
             }
         }
         ARROW_UP!() => {
-            if terminal.curs_y > 0 { // bounds checking
-                terminal.curs_y -= 1; // - means move up
+            if buffer.curs_y > 0 { // bounds checking
+                buffer.curs_y -= 1; // - means move up
 
                 //recalculate the current row's length
-                curr_row = terminal.content.get(terminal.curs_y as usize).unwrap_or(&invalidString);
-                if terminal.curs_x >= curr_row.len() as i32 {
+                curr_len = lineLen(buffer.currRow());
+                if buffer.curs_x >= curr_len {
                     //if we exceed the boundary for our new row,
                     // snap back to last character in the row
-                    terminal.curs_x = curr_row.len() as i32;
+                    buffer.curs_x = curr_len;
                 }
             }
         }
         ARROW_DOWN!() => {
-            if terminal.curs_y < terminal.content.len() as i32 - 1 { //bounds checking
-                terminal.curs_y += 1; // + means move down
+            if buffer.curs_y < buffer.content.len() as i32 - 1 { //bounds checking
+                buffer.curs_y += 1; // + means move down
 
                 //recalculate the current row's length
-                curr_row = terminal.content.get(terminal.curs_y as usize).unwrap_or(&invalidString);
-                if terminal.curs_x >= curr_row.len() as i32 {
+                curr_len = lineLen(buffer.currRow());
+                if buffer.curs_x >= curr_len {
                     //if we exceed the boundary for our new row,
                     // snap back to last character in the row
-                    terminal.curs_x = curr_row.len() as i32;
+                    buffer.curs_x = curr_len;
                 }
             }
         }
@@ -0,0 +1,258 @@
+use crate::keys::textToKeys;
+use crate::mode::Mode;
+use crate::theme::configDir;
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::io::ErrorKind::Other;
+use std::io::{Error, ErrorKind};
+
+//Bindings the editor ships with - a user keymap is read on top of these
+const DEFAULT_KEYMAP: &str = include_str!("keymaps/default.keymap");
+
+// everything a key can be bound to
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub(crate) enum Command {
+    Quit,
+    Save,
+    ClearLine,
+    Undo,
+    Redo,
+    OpenFile,
+    NextBuffer,
+    PrevBuffer,
+    ListBuffers,
+    CloseBuffer,
+    Close, //the window, or the buffer when it has the only one - quits after the last
+    Window, //takes one more key to say what to do with the windows
+    PageUp,
+    PageDown,
+    DeleteChar,
+    SearchForward,  //asks for a pattern and goes to the next line matching it
+    SearchBackward, //the same going up
+    SearchNext,     //next match of the last pattern, in the direction it was searched
+    SearchPrev,     //the same the other way
+    BrowseOpen,     //directory listings: go into the directory or open the file under the cursor
+    BrowseUp,
+    BrowseNewFile,
+    BrowseNewDir,
+    BrowseRename,
+    BrowseDelete,
+    FindFile, //fuzzy find a file under the current directory and open it
+    Nop,
+    Ex(String), //a `:` command line
+}
+
+// names commands go by in keymap files
+const COMMANDS: [(&str, Command); 27] = [
+    ("quit", Command::Quit),
+    ("save", Command::Save),
+    ("clear-line", Command::ClearLine),
+    ("undo", Command::Undo),
+    ("redo", Command::Redo),
+    ("open-file", Command::OpenFile),
+    ("next-buffer", Command::NextBuffer),
+    ("prev-buffer", Command::PrevBuffer),
+    ("list-buffers", Command::ListBuffers),
+    ("close-buffer", Command::CloseBuffer),
+    ("close", Command::Close),
+    ("window", Command::Window),
+    ("page-up", Command::PageUp),
+    ("page-down", Command::PageDown),
+    ("delete-char", Command::DeleteChar),
+    ("search-forward", Command::SearchForward),
+    ("search-backward", Command::SearchBackward),
+    ("search-next", Command::SearchNext),
+    ("search-prev", Command::SearchPrev),
+    ("browse-open", Command::BrowseOpen),
+    ("browse-up", Command::BrowseUp),
+    ("browse-new-file", Command::BrowseNewFile),
+    ("browse-new-dir", Command::BrowseNewDir),
+    ("browse-rename", Command::BrowseRename),
+    ("browse-delete", Command::BrowseDelete),
+    ("find-file", Command::FindFile),
+    ("nop", Command::Nop),
+];
+
+impl Command {
+    fn fromName(name: &str) -> Option<Command> {
+        if let Some(line) = name.strip_prefix(':') {
+            return Some(Command::Ex(line.to_string()));
+        }
+        COMMANDS.iter().find(|(n, _)| *n == name).map(|(_, command)| command.clone())
+    }
+}
+
+// each mode has its own bindings - the visual modes share one
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub(crate) enum MapMode {
+    Normal,
+    Insert,
+    Visual,
+    Command,
+    Pager, //normal mode in a read-only buffer - looked at before the normal bindings
+    Browse, //normal mode in a directory listing - looked at before the pager bindings
+}
+
+impl MapMode {
+    const ALL: [MapMode; 4] = [MapMode::Normal, MapMode::Insert, MapMode::Visual, MapMode::Command];
+
+    pub(crate) fn of(mode: Mode) -> MapMode {
+        match mode {
+            Mode::Normal => MapMode::Normal,
+            Mode::Insert => MapMode::Insert,
+            Mode::Visual(_) => MapMode::Visual,
+            Mode::Command => MapMode::Command,
+        }
+    }
+
+    fn fromName(name: &str) -> Option<MapMode> {
+        match name {
+            "normal" => Some(MapMode::Normal),
+            "insert" => Some(MapMode::Insert),
+            "visual" => Some(MapMode::Visual),
+            "command" => Some(MapMode::Command),
+            "pager" => Some(MapMode::Pager),
+            "browse" => Some(MapMode::Browse),
+            _ => None,
+        }
+    }
+}
+
+// key sequences bound to commands
+pub(crate) struct Keymap {
+    bindings: HashMap<(MapMode, Vec<i32>), Command>,
+}
+
+impl Keymap {
+    pub(crate) fn builtin() -> Keymap {
+        let mut keymap = Keymap { bindings: HashMap::new() };
+        keymap.parse(DEFAULT_KEYMAP, "default keymap").expect("built in keymap should always parse");
+        keymap
+    }
+
+    // the built in bindings with <config>/rustwrd/keymap read on top, if there is one
+    pub(crate) fn fromConfig() -> io::Result<Keymap> {
+        let mut keymap = Keymap::builtin();
+        if let Some(dir) = configDir() {
+            let fp = format!("{}/rustwrd/keymap", dir);
+            match fs::read_to_string(&fp) {
+                Ok(text) => keymap.parse(&text, &fp)?,
+                Err(e) if e.kind() == ErrorKind::NotFound => {}
+                Err(e) => return Err(Error::new(Other, format!("{}: {}", fp, e))),
+            }
+        }
+        Ok(keymap)
+    }
+
+    // add the bindings in `text` - later lines replace earlier bindings of the same keys
+    pub(crate) fn parse(&mut self, text: &str, source: &str) -> io::Result<()> {
+        for (num, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let error = |msg: String| Error::new(Other, format!("{} line {}: {}", source, num + 1, msg));
+            //the command is the rest of the line, so `:` commands can have spaces in them
+            let fields = line.split_once(char::is_whitespace).and_then(|(modes, rest)| {
+                let (keys, command) = rest.trim_start().split_once(char::is_whitespace)?;
+                Some((modes, keys, command.trim()))
+            });
+            let (modes, keys, command) = match fields {
+                Some(fields) => fields,
+                None => return Err(error(String::from("expected <modes> <keys> <command>"))),
+            };
+
+            let mut map_modes = Vec::new();
+            for name in modes.split(',') {
+                match name {
+                    "all" => map_modes.extend(MapMode::ALL),
+                    _ => match MapMode::fromName(name) {
+                        Some(mode) => map_modes.push(mode),
+                        None => return Err(error(format!("unknown mode `{}`", name))),
+                    },
+                }
+            }
+            let keys = textToKeys(keys);
+            //`unmap` takes keys back out, anything else has to be a command
+            let command = match command {
+                "unmap" => None,
+                _ => match Command::fromName(command) {
+                    Some(command) => Some(command),
+                    None => return Err(error(format!("unknown command `{}`", command))),
+                },
+            };
+            for mode in map_modes {
+                match &command {
+                    Some(command) => self.bindings.insert((mode, keys.clone()), command.clone()),
+                    None => self.bindings.remove(&(mode, keys.clone())),
+                };
+            }
+        }
+        Ok(())
+    }
+
+    // the command bound to exactly `keys` in the first of `modes` that has one, and whether any longer
+    // binding starts with them
+    pub(crate) fn lookup(&self, modes: &[MapMode], keys: &[i32]) -> (Option<&Command>, bool) {
+        let found = modes.iter().find_map(|mode| self.bindings.get(&(*mode, keys.to_vec())));
+        let longer = self
+            .bindings
+            .keys()
+            .any(|(m, k)| modes.contains(m) && k.len() > keys.len() && k.starts_with(keys));
+        (found, longer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(text: &str) -> Vec<i32> {
+        textToKeys(text)
+    }
+
+    #[test]
+    fn builtinKeymapParses() {
+        let keymap = Keymap::builtin();
+        assert_eq!(keymap.lookup(&[MapMode::Insert], &keys("<C-f>")).0, Some(&Command::FindFile));
+        //q in a pager only closes what it's looking at
+        assert_eq!(keymap.lookup(&[MapMode::Pager], &keys("q")).0, Some(&Command::Close));
+    }
+
+    #[test]
+    fn parsesBindings() {
+        let mut keymap = Keymap { bindings: HashMap::new() };
+        let text = "# a comment\n\nnormal,visual <Space>w  :w  file.txt\nall <C-q> quit\n";
+        keymap.parse(text, "test").unwrap();
+        let found = keymap.lookup(&[MapMode::Visual], &keys("<Space>w")).0;
+        assert_eq!(found, Some(&Command::Ex(String::from("w  file.txt"))));
+        assert_eq!(keymap.lookup(&[MapMode::Insert], &keys("<Space>w")).0, None);
+        assert_eq!(keymap.lookup(&[MapMode::Command], &keys("<C-q>")).0, Some(&Command::Quit));
+        //pager isn't part of all
+        assert_eq!(keymap.lookup(&[MapMode::Pager], &keys("<C-q>")).0, None);
+
+        keymap.parse("normal <Space>w unmap", "test").unwrap();
+        assert_eq!(keymap.lookup(&[MapMode::Normal], &keys("<Space>w")).0, None);
+        assert!(keymap.lookup(&[MapMode::Visual], &keys("<Space>w")).0.is_some());
+    }
+
+    #[test]
+    fn findsChords() {
+        let mut keymap = Keymap { bindings: HashMap::new() };
+        keymap.parse("normal gq nop\npager g quit", "test").unwrap();
+        let modes = [MapMode::Pager, MapMode::Normal];
+        assert_eq!(keymap.lookup(&modes, &keys("g")), (Some(&Command::Quit), true));
+        assert_eq!(keymap.lookup(&modes, &keys("gq")), (Some(&Command::Nop), false));
+        assert_eq!(keymap.lookup(&[MapMode::Normal], &keys("g")), (None, true));
+    }
+
+    #[test]
+    fn reportsBadLines() {
+        let error = |text: &str| Keymap { bindings: HashMap::new() }.parse(text, "test").unwrap_err().to_string();
+        assert_eq!(error("normal x"), "test line 1: expected <modes> <keys> <command>");
+        assert_eq!(error("\nsideways x quit"), "test line 2: unknown mode `sideways`");
+        assert_eq!(error("normal x fly"), "test line 1: unknown command `fly`");
+    }
+}
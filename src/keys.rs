@@ -0,0 +1,141 @@
+use crate::input::{
+    ARROW_DOWN, ARROW_LEFT, ARROW_RIGHT, ARROW_UP, BACKSPACE_KEY, DEL_KEY, END_KEY, ENTER_KEY, HOME_KEY, PAGE_DOWN,
+    PAGE_UP,
+};
+
+// names of keys that don't type a character, as written between < and >
+const KEY_NAMES: [(i32, &str); 13] = [
+    (0x1b, "Esc"),
+    (ENTER_KEY!(), "CR"),
+    (BACKSPACE_KEY!(), "BS"),
+    (b'\t' as i32, "Tab"),
+    (DEL_KEY!(), "Del"),
+    (ARROW_UP!(), "Up"),
+    (ARROW_DOWN!(), "Down"),
+    (ARROW_LEFT!(), "Left"),
+    (ARROW_RIGHT!(), "Right"),
+    (HOME_KEY!(), "Home"),
+    (END_KEY!(), "End"),
+    (PAGE_UP!(), "PageUp"),
+    (PAGE_DOWN!(), "PageDown"),
+];
+
+// a key written out so it can be read and edited - `x`, `<Esc>`, `<C-w>`, and `<xff>` for a byte outside ascii
+pub(crate) fn keyName(key: i32) -> String {
+    if let Some((_, name)) = KEY_NAMES.iter().find(|(k, _)| *k == key) {
+        return format!("<{}>", name);
+    }
+    match key {
+        0x3c => String::from("<lt>"),
+        0..=0x1f => format!("<C-{}>", (((key as u8) | 0x40) as char).to_ascii_lowercase()),
+        0x20..=0x7f => ((key as u8) as char).to_string(),
+        0x80..=0xff => format!("<x{:02x}>", key),
+        _ => String::new(),
+    }
+}
+
+pub(crate) fn keysToText(keys: &[i32]) -> String {
+    let mut text = String::new();
+    let mut i = 0;
+    while i < keys.len() {
+        //characters outside ascii come in as several bytes - put them back together
+        let run = keys[i..].iter().take_while(|k| (0x80..=0xff).contains(*k)).count();
+        if run > 0 {
+            let bytes: Vec<u8> = keys[i..i + run].iter().map(|k| *k as u8).collect();
+            for chunk in bytes.utf8_chunks() {
+                text.push_str(chunk.valid());
+                text.extend(chunk.invalid().iter().map(|b| keyName(*b as i32)));
+            }
+            i += run;
+        } else {
+            text.push_str(&keyName(keys[i]));
+            i += 1;
+        }
+    }
+    text
+}
+
+// the key a name between < and > stands for
+fn namedKey(name: &str) -> Option<i32> {
+    if name.eq_ignore_ascii_case("lt") {
+        return Some(b'<' as i32);
+    }
+    if name.eq_ignore_ascii_case("Space") {
+        return Some(b' ' as i32);
+    }
+    if name.eq_ignore_ascii_case("Enter") || name.eq_ignore_ascii_case("Return") {
+        return Some(ENTER_KEY!());
+    }
+    if let Some((key, _)) = KEY_NAMES.iter().find(|(_, n)| n.eq_ignore_ascii_case(name)) {
+        return Some(*key);
+    }
+    //<xff> is a byte that isn't part of a character
+    if let Some(hex) = name.strip_prefix(['x', 'X']).filter(|hex| hex.len() == 2) {
+        return u8::from_str_radix(hex, 16).ok().filter(|b| *b >= 0x80).map(|b| b as i32);
+    }
+    //<C-x> is x with control held
+    let mut chars = name.chars();
+    match (chars.next(), chars.next(), chars.next(), chars.next()) {
+        (Some('C' | 'c'), Some('-'), Some(c), None) if c.is_ascii_alphabetic() || "@[\\]^_".contains(c) => {
+            Some((c.to_ascii_lowercase() as u8 & 0x1f) as i32)
+        }
+        _ => None,
+    }
+}
+
+// read keys back out of text written by keysToText. Anything in <> that isn't a key name is typed as is
+pub(crate) fn textToKeys(text: &str) -> Vec<i32> {
+    let mut keys = Vec::new();
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        if c == '<' {
+            if let Some(end) = rest.find('>') {
+                if let Some(key) = namedKey(&rest[1..end]) {
+                    keys.push(key);
+                    rest = &rest[end + 1..];
+                    continue;
+                }
+            }
+        }
+        match c {
+            '\n' | '\r' => keys.push(ENTER_KEY!()),
+            '\x7f' => keys.push(BACKSPACE_KEY!()),
+            //keys are read a byte at a time, so that's how anything else comes in
+            _ => keys.extend(c.to_string().bytes().map(|b| b as i32)),
+        }
+        rest = &rest[c.len_utf8()..];
+    }
+    keys
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn readsKeyNames() {
+        assert_eq!(textToKeys("ab"), vec![b'a' as i32, b'b' as i32]);
+        assert_eq!(textToKeys("<C-w>v<Esc>"), vec![0x17, b'v' as i32, 0x1b]);
+        assert_eq!(textToKeys("<cr><Space><lt>"), vec![ENTER_KEY!(), b' ' as i32, b'<' as i32]);
+        assert_eq!(textToKeys("<Up><PageDown>"), vec![ARROW_UP!(), PAGE_DOWN!()]);
+        assert_eq!(textToKeys("\n"), vec![ENTER_KEY!()]);
+        //anything in <> that isn't a key is typed as it is
+        assert_eq!(textToKeys("<C-1>"), "<C-1>".bytes().map(|b| b as i32).collect::<Vec<_>>());
+        assert_eq!(textToKeys("<x"), vec![b'<' as i32, b'x' as i32]);
+        assert_eq!(textToKeys("é"), vec![0xc3, 0xa9]);
+    }
+
+    #[test]
+    fn writesKeysBack() {
+        let keys = vec![b'i' as i32, 0xc3, 0xa9, b'<' as i32, 0x1b, 0x17, DEL_KEY!(), HOME_KEY!()];
+        assert_eq!(keysToText(&keys), "ié<lt><Esc><C-w><Del><Home>");
+        assert_eq!(textToKeys(&keysToText(&keys)), keys);
+        //bytes that aren't UTF-8 are written one at a time, and read back as the same bytes
+        let bytes = vec![0xff, b'a' as i32, 0xc3, 0xc3, 0xa9, 0x80];
+        assert_eq!(keysToText(&bytes), "<xff>a<xc3>é<x80>");
+        assert_eq!(textToKeys(&keysToText(&bytes)), bytes);
+        assert_eq!(textToKeys("<XFF>"), vec![0xff]);
+        //ascii is only ever written as itself
+        assert_eq!(textToKeys("<x41>"), "<x41>".bytes().map(|b| b as i32).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,626 @@
+use crate::buffer::Buffer;
+use crate::encoding::isUtf16;
+use crate::hex::isBinary;
+use crate::mark::adjustMarks;
+use crate::mode::Mode;
+use crate::Terminal;
+
+use nix::sys::mman::{mmap, munmap, MapFlags, ProtFlags};
+use std::collections::HashMap;
+use std::ffi::c_void;
+use std::fs;
+use std::fs::File;
+use std::borrow::Cow;
+use std::io;
+use std::io::ErrorKind::Other;
+use std::io::{BufWriter, Error, Write};
+use std::num::NonZeroUsize;
+use std::ops::Range;
+use std::os::fd::AsRawFd;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+//large files are read a piece of about this many bytes at a time - pieces always end at the end of a line
+const PIECE_SIZE: usize = 1 << 20;
+//most pieces kept in the buffer at once
+const MAX_LOADED: usize = 3;
+//the next piece is read in when the cursor gets this close to the edge of what's loaded
+const MARGIN: i32 = 200;
+
+// a file mapped into memory, read only
+struct Mapping {
+    ptr: *mut c_void,
+    len: usize,
+}
+
+//nothing ever writes through the pointer, so the indexing thread can read it too
+unsafe impl Send for Mapping {}
+unsafe impl Sync for Mapping {}
+
+impl Mapping {
+    fn new(file: &File, len: usize) -> io::Result<Mapping> {
+        let length = match NonZeroUsize::new(len) {
+            Some(length) => length,
+            None => return Err(Error::new(Other, "Can't map an empty file")),
+        };
+        let ptr = unsafe { mmap(None, length, ProtFlags::PROT_READ, MapFlags::MAP_PRIVATE, file.as_raw_fd(), 0)? };
+        Ok(Mapping { ptr, len })
+    }
+
+    fn bytes(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.ptr as *const u8, self.len) }
+    }
+}
+
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        let _ = unsafe { munmap(self.ptr, self.len) };
+    }
+}
+
+// byte offset piece `k` starts at - the first line starting at or after k * PIECE_SIZE
+fn pieceStart(bytes: &[u8], k: usize) -> usize {
+    let at = k * PIECE_SIZE;
+    if at == 0 || at >= bytes.len() {
+        return at.min(bytes.len());
+    }
+    match bytes[at - 1..].iter().position(|b| *b == b'\n') {
+        Some(i) => at + i,
+        None => bytes.len(),
+    }
+}
+
+fn pieceBytes(bytes: &[u8], k: usize) -> &[u8] {
+    &bytes[pieceStart(bytes, k)..pieceStart(bytes, k + 1)]
+}
+
+fn countLines(bytes: &[u8]) -> usize {
+    let newlines = bytes.iter().filter(|b| **b == b'\n').count();
+    newlines + (bytes.last().is_some_and(|b| *b != b'\n') as usize)
+}
+
+// lines of a piece as they are in the file, each with its line ending - the last one may not have one
+fn rawLines(bytes: &[u8]) -> Vec<&[u8]> {
+    bytes.split_inclusive(|b| *b == b'\n').collect()
+}
+
+// a line's text without its line ending
+fn lineText(raw: &[u8]) -> String {
+    let raw = match raw.strip_suffix(b"\n") {
+        Some(raw) => raw.strip_suffix(b"\r").unwrap_or(raw),
+        None => raw,
+    };
+    String::from_utf8_lossy(raw).into_owned()
+}
+
+// what new lines in a file end with - the same as its first line
+fn lineEnd(bytes: &[u8]) -> &'static str {
+    match bytes.iter().position(|b| *b == b'\n') {
+        Some(i) if i > 0 && bytes[i - 1] == b'\r' => "\r\n",
+        _ => "\n",
+    }
+}
+
+fn decodeLines(bytes: &[u8]) -> Vec<String> {
+    rawLines(bytes).into_iter().map(lineText).collect()
+}
+
+// bytes to write for each line a piece has now, line endings included. Lines that were in the piece before are
+// copied as they were, so their line endings and any bytes that aren't UTF-8 stay the same - only new or
+// changed lines are written out again, ending the way the line they took the place of did
+fn pieceLines<'a>(raw: &'a [u8], lines: &[String], line_end: &'static str) -> Vec<Cow<'a, [u8]>> {
+    let raw = rawLines(raw);
+    let old: Vec<String> = raw.iter().map(|line| lineText(line)).collect();
+    let prefix = old.iter().zip(lines).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..].iter().rev().zip(lines[prefix..].iter().rev()).take_while(|(a, b)| a == b).count();
+
+    //which old line each new one is, if any - lines in between the start and end that didn't change are
+    //found again by looking for the next old line with the same text
+    let mut found: Vec<Option<usize>> = (0..prefix).map(Some).collect();
+    let mut at: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (i, line) in old.iter().enumerate().take(old.len() - suffix).skip(prefix) {
+        at.entry(line).or_default().push(i);
+    }
+    let mut next = prefix;
+    for line in &lines[prefix..lines.len() - suffix] {
+        let i = at.get(line.as_str()).and_then(|places| places.get(places.partition_point(|i| *i < next)));
+        if let Some(&i) = i {
+            next = i + 1;
+        }
+        found.push(i.copied());
+    }
+    found.extend((old.len() - suffix..old.len()).map(Some));
+
+    let ending = |i: usize| -> &'static [u8] {
+        match raw.get(i).or(raw.last()) {
+            Some(line) if line.ends_with(b"\r\n") => b"\r\n",
+            Some(line) if line.ends_with(b"\n") => b"\n",
+            _ => line_end.as_bytes(),
+        }
+    };
+    let mut out = Vec::with_capacity(lines.len());
+    let mut next = 0;
+    for (j, line) in lines.iter().enumerate() {
+        match found[j] {
+            //the file's last line may not have had a line ending - it needs one if lines now come after it
+            Some(i) if raw[i].ends_with(b"\n") || j == lines.len() - 1 => {
+                out.push(Cow::Borrowed(raw[i]));
+                next = i + 1;
+            }
+            Some(i) => {
+                out.push(Cow::Owned([raw[i], line_end.as_bytes()].concat()));
+                next = i + 1;
+            }
+            None => out.push(Cow::Owned([line.as_bytes(), ending(next)].concat())),
+        }
+    }
+    out
+}
+
+// should `fp` be opened a piece at a time - `mb` is the largefile option, 0 turns it off
+pub(crate) fn isLarge(fp: &str, mb: u64) -> bool {
+    mb > 0 && fs::metadata(fp).is_ok_and(|meta| meta.is_file() && meta.len() >= mb.saturating_mul(1024 * 1024))
+}
+
+// a file too big to read in whole. The buffer only holds a few pieces of it at a time - pieces that were
+// changed are kept aside when they're unloaded, everything else is read from the mapping again when needed
+pub(crate) struct LargeFile {
+    map: Arc<Mapping>,
+    pieces: usize,
+    counts: Arc<Mutex<Vec<usize>>>, //lines in each piece on disk, filled in by a background thread
+    stop: Arc<AtomicBool>,          //tells that thread to give up
+    first: usize,                   //first piece loaded into the buffer
+    bounds: Vec<usize>,             //where each loaded piece starts in the buffer
+    overlay: HashMap<usize, Vec<String>>, //pieces that were changed and then unloaded
+    line_end: &'static str, //new lines with no old line nearby to copy a line ending from get this one
+    pub(crate) utf8: bool,  //large files are only read as UTF-8 - one that isn't can be looked at but not changed
+}
+
+impl LargeFile {
+    fn open(fp: &str) -> io::Result<LargeFile> {
+        let file = match File::open(fp) {
+            Ok(file) => file,
+            Err(e) => return Err(Error::new(Other, format!("{}: {}", fp, e))),
+        };
+        let len = file.metadata()?.len() as usize;
+        let map = Arc::new(Mapping::new(&file, len)?);
+        //only the start is looked at - reading all of it would take as long as loading it
+        let start = &map.bytes()[..len.min(PIECE_SIZE)];
+        if isBinary(start) || isUtf16(start) {
+            return Err(Error::new(Other, format!("{}: too large to open unless it's text (:set largefile=0)", fp)));
+        }
+        //a character cut in half at the end doesn't count
+        let utf8 = std::str::from_utf8(start).map_or_else(|e| e.error_len().is_none(), |_| true);
+        let pieces = len.div_ceil(PIECE_SIZE);
+        let counts = Arc::new(Mutex::new(Vec::new()));
+        let stop = Arc::new(AtomicBool::new(false));
+
+        //line numbers need every line counted - that happens while the file is already on screen
+        let (thread_map, thread_counts, thread_stop) = (map.clone(), counts.clone(), stop.clone());
+        thread::spawn(move || {
+            for k in 0..pieces {
+                if thread_stop.load(Ordering::Relaxed) {
+                    return;
+                }
+                let count = countLines(pieceBytes(thread_map.bytes(), k));
+                thread_counts.lock().unwrap().push(count);
+            }
+        });
+
+        let line_end = lineEnd(map.bytes());
+        Ok(LargeFile { map, pieces, counts, stop, first: 0, bounds: Vec::new(), overlay: HashMap::new(), line_end, utf8 })
+    }
+
+    fn loaded(&self) -> Range<usize> {
+        self.first..self.first + self.bounds.len()
+    }
+
+    // lines in the buffer that belong to loaded piece `k`
+    fn span(&self, k: usize, content_len: usize) -> Range<usize> {
+        let i = k - self.first;
+        self.bounds[i]..self.bounds.get(i + 1).copied().unwrap_or(content_len)
+    }
+
+    // text of a piece that isn't loaded
+    fn piece(&self, k: usize) -> Vec<String> {
+        match self.overlay.get(&k) {
+            Some(lines) => lines.clone(),
+            None => decodeLines(pieceBytes(self.map.bytes(), k)),
+        }
+    }
+
+    // number of lines in a piece - None if it hasn't been counted yet
+    fn lines(&self, k: usize, content_len: usize) -> Option<usize> {
+        if self.loaded().contains(&k) {
+            return Some(self.span(k, content_len).len());
+        }
+        match self.overlay.get(&k) {
+            Some(lines) => Some(lines.len()),
+            None => self.counts.lock().unwrap().get(k).copied(),
+        }
+    }
+
+    // true once a piece has been changed and unloaded - the buffer has changes that aren't in it
+    pub(crate) fn isEdited(&self) -> bool {
+        !self.overlay.is_empty()
+    }
+
+    // keep the piece boundaries on the same text when lines `start..start + old_len` become `new_len` lines
+    pub(crate) fn adjust(&mut self, start: usize, old_len: usize, new_len: usize) {
+        for bound in self.bounds.iter_mut().skip(1) {
+            if *bound >= start + old_len {
+                *bound = *bound + new_len - old_len;
+            } else if *bound > start {
+                //what's left of lines that were split between pieces goes with the first one
+                *bound = start + new_len;
+            }
+        }
+    }
+}
+
+impl Drop for LargeFile {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+impl Buffer {
+    // number of the buffer's first line in the whole file, counting from 0 - None until it's known
+    pub(crate) fn lineBase(&self) -> Option<usize> {
+        match &self.large {
+            Some(large) => (0..large.first).map(|k| large.lines(k, self.content.len())).sum(),
+            None => Some(0),
+        }
+    }
+
+    // lines in the whole file - None while they're still being counted
+    pub(crate) fn totalLines(&self) -> Option<usize> {
+        match &self.large {
+            Some(large) => (0..large.pieces).map(|k| large.lines(k, self.content.len())).sum(),
+            None => Some(self.content.len()),
+        }
+    }
+}
+
+// open a large file with just its first piece read in
+pub(crate) fn openLarge(fp: &str) -> io::Result<Buffer> {
+    let mut buffer = Buffer::new();
+    buffer.fp = String::from(fp);
+    buffer.large = Some(LargeFile::open(fp)?);
+    loadAt(&mut buffer, 0);
+    Ok(buffer)
+}
+
+// read a large file again with the same pieces loaded as `buffer` has, so line numbers in it stay the same
+pub(crate) fn reopenLarge(buffer: &Buffer) -> io::Result<Buffer> {
+    let loaded = buffer.large.as_ref().unwrap().loaded();
+    let mut reopened = Buffer::new();
+    reopened.fp = buffer.fp.clone();
+    reopened.large = Some(LargeFile::open(&buffer.fp)?);
+    loadAt(&mut reopened, loaded.start);
+    loop {
+        let large = reopened.large.as_ref().unwrap();
+        if large.loaded().end >= loaded.end.min(large.pieces) {
+            break;
+        }
+        loadNext(&mut reopened);
+    }
+    Ok(reopened)
+}
+
+// put piece `k` aside if it was changed
+fn unload(large: &mut LargeFile, k: usize, lines: Vec<String>) {
+    if lines != decodeLines(pieceBytes(large.map.bytes(), k)) {
+        large.overlay.insert(k, lines);
+    } else {
+        large.overlay.remove(&k);
+    }
+}
+
+// replace what's loaded with piece `k` - or the nearest piece that has any lines in it
+fn loadAt(buffer: &mut Buffer, k: usize) {
+    let content = std::mem::take(&mut buffer.content);
+    let large = buffer.large.as_mut().unwrap();
+    for piece in large.loaded().rev() {
+        let span = large.span(piece, content.len());
+        unload(large, piece, content[span].to_vec());
+    }
+    let mut first = k.min(large.pieces.saturating_sub(1));
+    let mut lines = large.piece(first);
+    //a line longer than a piece leaves pieces with nothing in them
+    let mut next = first + 1;
+    while lines.is_empty() && next < large.pieces {
+        lines = large.piece(next);
+        next += 1;
+    }
+    if lines.is_empty() {
+        first = k.min(large.pieces.saturating_sub(1));
+        while lines.is_empty() && first > 0 {
+            first -= 1;
+            lines = large.piece(first);
+        }
+    } else if next > first + 1 {
+        first = next - 1;
+    }
+    large.first = first;
+    large.bounds = vec![0];
+    buffer.content = lines;
+    if buffer.content.is_empty() {
+        buffer.content.push(String::new());
+    }
+    buffer.marks.clear();
+    buffer.forgetUndo(0, 0);
+    buffer.clampCursor();
+}
+
+// read the piece after what's loaded onto the end of the buffer
+fn loadNext(buffer: &mut Buffer) {
+    let large = buffer.large.as_mut().unwrap();
+    let k = large.loaded().end;
+    large.bounds.push(buffer.content.len());
+    buffer.content.extend(large.piece(k));
+}
+
+// read the piece before what's loaded into the start of the buffer - returns how many lines it added
+fn loadPrev(buffer: &mut Buffer) -> usize {
+    let large = buffer.large.as_mut().unwrap();
+    large.first -= 1;
+    let lines = large.piece(large.first);
+    let n = lines.len();
+    for bound in large.bounds.iter_mut() {
+        *bound += n;
+    }
+    large.bounds.insert(0, 0);
+    buffer.content.splice(0..0, lines);
+    adjustMarks(&mut buffer.marks, 0, 0, n);
+    buffer.undo.shift(n as i64);
+    buffer.curs_y += n as i32;
+    buffer.v_offset += n as i32;
+    n
+}
+
+// take the first loaded piece out of the buffer - returns how many lines went
+fn unloadFirst(buffer: &mut Buffer) -> usize {
+    let large = buffer.large.as_mut().unwrap();
+    let n = large.bounds[1];
+    let lines: Vec<String> = buffer.content.drain(..n).collect();
+    unload(large, large.first, lines);
+    large.first += 1;
+    large.bounds.remove(0);
+    for bound in large.bounds.iter_mut() {
+        *bound -= n;
+    }
+    adjustMarks(&mut buffer.marks, 0, n, 0);
+    buffer.forgetUndo(n, buffer.content.len() + n);
+    buffer.undo.shift(-(n as i64));
+    buffer.curs_y -= n as i32;
+    buffer.v_offset = (buffer.v_offset - n as i32).max(0);
+    n
+}
+
+// take the last loaded piece out of the buffer
+fn unloadLast(buffer: &mut Buffer) {
+    let large = buffer.large.as_mut().unwrap();
+    let k = large.loaded().end - 1;
+    let start = *large.bounds.last().unwrap();
+    let lines: Vec<String> = buffer.content.drain(start..).collect();
+    unload(large, k, lines);
+    large.bounds.pop();
+    adjustMarks(&mut buffer.marks, start, buffer.content.len() + 1, 0);
+    buffer.forgetUndo(0, start);
+}
+
+// other windows showing the buffer keep looking at the same lines when lines go in or out above them
+fn shiftWindows(terminal: &mut Terminal, buf: usize, delta: i32) {
+    let curr_win = terminal.curr_win;
+    let last = terminal.buffers[buf].content.len() as i32 - 1;
+    for (i, window) in terminal.windows.iter_mut().enumerate() {
+        if i != curr_win && window.buf == buf {
+            window.curs_y = (window.curs_y + delta).clamp(0, last);
+            window.v_offset = (window.v_offset + delta).clamp(0, last);
+        }
+    }
+}
+
+// read in the next piece when the cursor gets near the edge of what's loaded, and let go of the pieces
+// furthest from it. Only done in normal mode - nothing else is holding on to line numbers then
+pub(crate) fn editorLargeSlide(terminal: &mut Terminal) {
+    if terminal.mode != Mode::Normal || terminal.buf().large.is_none() {
+        return;
+    }
+    let buf = terminal.currBuf();
+    let buffer = &mut terminal.buffers[buf];
+    let large = buffer.large.as_ref().unwrap();
+    if buffer.curs_y >= buffer.content.len() as i32 - MARGIN && large.loaded().end < large.pieces {
+        loadNext(buffer);
+    } else if buffer.curs_y < MARGIN && large.first > 0 {
+        let n = loadPrev(buffer);
+        shiftWindows(terminal, buf, n as i32);
+    }
+    loop {
+        let buffer = &mut terminal.buffers[buf];
+        let large = buffer.large.as_ref().unwrap();
+        if large.bounds.len() <= MAX_LOADED {
+            break;
+        }
+        //the cursor is never in the piece that goes
+        if buffer.curs_y as usize >= large.bounds[1] {
+            let n = unloadFirst(buffer);
+            shiftWindows(terminal, buf, -(n as i32));
+        } else {
+            unloadLast(buffer);
+            shiftWindows(terminal, buf, 0);
+        }
+    }
+}
+
+// make sure line `line` of the whole file (counting from 0, None for the last one) is loaded, and return
+// where it is in the buffer
+pub(crate) fn editorLargeGoto(terminal: &mut Terminal, line: Option<usize>) -> io::Result<usize> {
+    let buf = terminal.currBuf();
+    let buffer = &mut terminal.buffers[buf];
+    let large = buffer.large.as_ref().unwrap();
+    let content_len = buffer.content.len();
+    let target = match line {
+        Some(line) => {
+            let mut before = 0;
+            let mut found = None;
+            for k in 0..large.pieces {
+                let lines = match large.lines(k, content_len) {
+                    Some(lines) => lines,
+                    None => return Err(Error::new(Other, "Still counting lines - try again in a moment")),
+                };
+                if line < before + lines {
+                    found = Some((k, line - before));
+                    break;
+                }
+                before += lines;
+            }
+            found
+        }
+        None => None,
+    };
+    let (k, offset) = target.unwrap_or((large.pieces - 1, usize::MAX));
+    if large.loaded().contains(&k) {
+        let span = large.span(k, content_len);
+        return Ok((span.start + offset).min(span.end.max(1) - 1).min(content_len - 1));
+    }
+    loadAt(buffer, k);
+    shiftWindows(terminal, buf, 0);
+    Ok(offset.min(terminal.buffers[buf].content.len() - 1))
+}
+
+// write a large buffer to `fp` by way of a temporary file next to it, which is removed again if anything
+// goes wrong. Returns the bytes written and where the cursor's line starts in them
+pub(crate) fn writeLargeTo(buffer: &Buffer, fp: &str) -> io::Result<(usize, usize)> {
+    let tmp = format!("{}.tmp", fp);
+    let file = match File::create(&tmp) {
+        Ok(file) => file,
+        Err(e) => return Err(Error::new(Other, format!("{}: {}", tmp, e))),
+    };
+    let result = writePieces(buffer, file, fp).and_then(|written| fs::rename(&tmp, fp).map(|_| written));
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    result
+}
+
+// pieces that were never changed are copied straight from the old file, and so are the lines in changed pieces
+// that are still the same
+fn writePieces(buffer: &Buffer, file: File, fp: &str) -> io::Result<(usize, usize)> {
+    //a file that's replaced keeps its permissions
+    if let Ok(meta) = fs::metadata(fp) {
+        file.set_permissions(meta.permissions())?;
+    }
+    let large = buffer.large.as_ref().unwrap();
+    let bytes = large.map.bytes();
+    let mut out = BufWriter::new(file);
+    let mut written = 0;
+    let mut cursor_at = 0;
+    let cursor = buffer.curs_y as usize;
+    let ends_with_newline = bytes.last() == Some(&b'\n');
+    for k in 0..large.pieces {
+        let raw = pieceBytes(bytes, k);
+        let (lines, first) = if large.loaded().contains(&k) {
+            let span = large.span(k, buffer.content.len());
+            let first = span.start;
+            (&buffer.content[span], Some(first))
+        } else {
+            match large.overlay.get(&k) {
+                Some(lines) => (&lines[..], None),
+                None => {
+                    out.write_all(raw)?;
+                    written += raw.len();
+                    continue;
+                }
+            }
+        };
+        let line_bytes = pieceLines(raw, lines, large.line_end);
+        for (i, line) in line_bytes.iter().enumerate() {
+            if first.is_some_and(|first| first + i == cursor) {
+                cursor_at = written;
+            }
+            //the file ends the way it did
+            let mut line: &[u8] = line;
+            let last = k == large.pieces - 1 && i == lines.len() - 1;
+            if last && !ends_with_newline {
+                line = line.strip_suffix(b"\n").map_or(line, |line| line.strip_suffix(b"\r").unwrap_or(line));
+            }
+            out.write_all(line)?;
+            written += line.len();
+        }
+    }
+    out.flush()?;
+    Ok((written, cursor_at))
+}
+
+// write a large buffer out, then read the file again from where the cursor is
+pub(crate) fn writeLarge(buffer: &mut Buffer) -> io::Result<usize> {
+    let (written, cursor_at) = writeLargeTo(buffer, &buffer.fp)?;
+    let screen_row = buffer.curs_y - buffer.v_offset;
+    buffer.large = Some(LargeFile::open(&buffer.fp)?);
+    let reopened = buffer.large.as_ref().unwrap();
+    let new_bytes = reopened.map.bytes();
+    let mut k = cursor_at / PIECE_SIZE;
+    while k > 0 && pieceStart(new_bytes, k) > cursor_at {
+        k -= 1;
+    }
+    let y = countLines(&new_bytes[pieceStart(new_bytes, k)..cursor_at]);
+    loadAt(buffer, k);
+    buffer.curs_y = y as i32;
+    buffer.v_offset = (buffer.curs_y - screen_row).max(0);
+    buffer.clampCursor();
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::lines;
+
+    fn written(raw: &[u8], text: &[&str]) -> Vec<u8> {
+        pieceLines(raw, &lines(text), lineEnd(raw)).concat()
+    }
+
+    #[test]
+    fn piecesEndAtLines() {
+        let bytes: Vec<u8> = (0..3 * PIECE_SIZE / 10).flat_map(|_| *b"123456789\n").collect();
+        assert_eq!(pieceStart(&bytes, 0), 0);
+        let start = pieceStart(&bytes, 1);
+        assert!(start >= PIECE_SIZE && bytes[start - 1] == b'\n');
+        assert_eq!(pieceStart(&bytes, 5), bytes.len());
+        assert_eq!(countLines(b"a\nb"), 2);
+        assert_eq!(countLines(b"a\nb\n"), 2);
+    }
+
+    #[test]
+    fn hugeThresholdsDontOverflow() {
+        assert!(!isLarge(file!(), u64::MAX));
+        assert!(!isLarge(file!(), 0));
+    }
+
+    #[test]
+    fn linesKeepTheirBytes() {
+        let raw = b"one\r\ntwo\n\xffbad\nlast";
+        assert_eq!(decodeLines(raw), ["one", "two", "\u{fffd}bad", "last"]);
+        //nothing changed, nothing different
+        assert_eq!(written(raw, &["one", "two", "\u{fffd}bad", "last"]), raw);
+        //changed lines end the way the line they replace did
+        assert_eq!(written(raw, &["ONE", "two", "\u{fffd}bad", "last"]), b"ONE\r\ntwo\n\xffbad\nlast");
+        assert_eq!(written(raw, &["one", "new", "two", "\u{fffd}bad", "last"]), b"one\r\nnew\ntwo\n\xffbad\nlast");
+        //lines between two changes are found again - whether the file ends in a newline is up to writeLarge
+        let edited = written(raw, &["x", "two", "\u{fffd}bad", "y"]);
+        assert_eq!(edited, b"x\r\ntwo\n\xffbad\ny\r\n");
+    }
+
+    #[test]
+    fn lastLineGetsAnEndingIfLinesFollowIt() {
+        let raw = b"a\r\nb";
+        assert_eq!(written(raw, &["a", "b", "c"]), b"a\r\nb\r\nc\r\n");
+        assert_eq!(written(b"a\nb", &["a", "b", "c"]), b"a\nb\nc\n");
+        assert_eq!(written(raw, &["b"]), b"b");
+        assert_eq!(written(b"", &["new"]), b"new\n");
+        assert!(pieceLines(raw, &[], "\n").is_empty());
+    }
+}
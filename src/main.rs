@@ -1,28 +1,67 @@
 #![allow(non_snake_case)]
 #![allow(unused_imports)]
 
+mod browse;
+mod buffer;
+mod cli;
+mod command;
+mod disk;
+mod encoding;
+mod ex;
+mod finder;
+mod hex;
 mod input;
+mod keys;
+mod keymap;
+mod large;
+mod mark;
+mod mode;
+mod motion;
+mod operator;
+mod options;
+mod pattern;
+mod register;
+mod swap;
 mod output;
 
-use crate::input::{editorProcessKeypress, editorReadKey};
-use crate::output::editorRefreshScreen;
+use crate::cli::{parseArgs, Parsed, USAGE};
+use crate::ex::executeEx;
+use crate::input::editorProcessKeypress;
+use crate::output::{editorRefreshScreen, RUST_WRD};
 
 mod terminal;
+#[cfg(test)]
+mod testutil;
+mod textobject;
+mod theme;
+mod undo;
+mod visual;
+mod window;
 
 use terminal::Terminal;
+use theme::Theme;
+use keymap::Keymap;
+use mark::JumpList;
+use mode::Mode;
+use motion::Pos;
+use options::Options;
+use register::Registers;
+use window::{Layout, Window};
 
 use nix::libc::STDIN_FILENO;
 use nix::sys::termios;
 use std::fs::File;
 use std::io::ErrorKind::Other;
 use std::io::{BufRead, Read, stdin, stdout, Write};
+use std::collections::{HashMap, VecDeque};
+use std::time::Instant;
 use std::{env, io};
 
 #[allow(dead_code)]
 fn keycodes() -> io::Result<()> {
     let mut c: char;
     //loop through all input bytes
-    for byte in stdin().bytes() {
+    for byte in io::BufReader::new(stdin()).bytes() {
         let b = byte?;
         c = b as char;
         if c == 'q' {
@@ -39,41 +78,122 @@ fn keycodes() -> io::Result<()> {
     Ok(())
 }
 
+// put things back the way they were before quitting
+fn editorExit(terminal: &mut Terminal) -> io::Result<()> {
+    //swap files are only left behind when something goes wrong
+    for buffer in terminal.buffers.iter_mut() {
+        swap::removeSwap(buffer);
+    }
+    stdout().write_all(b"\x1b[2J")?;
+    stdout().write_all(b"\x1b[H")?;
+    Ok(())
+}
+
 // entry point
 fn main() -> io::Result<()> {
-    let args: Vec<_> = env::args().collect();
+    let mut cli = match parseArgs(env::args().skip(1)) {
+        Ok(Parsed::Run(cli)) => cli,
+        Ok(Parsed::Help) => {
+            print!("{}", USAGE);
+            return Ok(());
+        }
+        Ok(Parsed::Version) => {
+            println!("rustwrd {}", RUST_WRD!());
+            return Ok(());
+        }
+        Err(e) => {
+            eprintln!("rustwrd: {}\nTry 'rustwrd --help' for more information.", e);
+            std::process::exit(2);
+        }
+    };
 
+    //with no files, text piped in is what we show - how pagers get run
+    if cli.files.is_empty() && !nix::unistd::isatty(STDIN_FILENO).unwrap_or(true) {
+        cli.files.push(String::from("-"));
+    }
+    //`-` reads stdin, so keys have to come from the terminal instead
+    let mut piped = if cli.files.iter().any(|fp| fp == "-") { Some(terminal::readPipe()?) } else { None };
+    terminal::attachTty()?;
+
+    let options = Options::fromConfig()?;
+    let theme = Theme::find(&options.theme)?;
     let mut terminal = Terminal {
         orig_termios: termios::tcgetattr(STDIN_FILENO)?,
         screen_rows: 0,
         screen_cols: 0,
-        curs_x: 0,
-        curs_y: 0,
-        v_offset: 0,
-        fp: String::new(),
-        content: Vec::new(),
+        buffers: Vec::new(),
+        windows: Vec::new(),
+        curr_win: 0,
+        layout: Layout::Leaf(0),
+        mode: Mode::Normal,
+        cmdline: String::new(),
+        pending: Vec::new(),
+        last_find: None,
+        visual_anchor: Pos { y: 0, x: 0 },
+        visual_eol: false,
+        block_insert: None,
+        registers: Registers::new(),
+        last_change: None,
+        recording: None,
+        macro_queue: VecDeque::new(),
+        last_macro: None,
+        jumps: JumpList::default(),
+        file_marks: HashMap::new(),
+        keymap: Keymap::fromConfig()?,
+        last_pattern: None,
+        search_forward: true,
+        options,
+        theme,
+        status_msg: String::new(),
+        finder: None,
+        status_time: Instant::now(),
     };
 
     terminal.enableRawMode()?;
     terminal.initEditor()?;
-    if args.len() >= 2 {
-        terminal.editorOpenFile(&args[1])?;
+    for fp in cli.files.iter() {
+        match fp.as_str() {
+            //stdin can only be read once
+            "-" => match piped.take() {
+                Some(content) => terminal.editorOpenPiped(content),
+                None => continue,
+            },
+            _ => terminal.editorOpenFile(fp)?,
+        }
+        terminal.bufMut().options.readonly |= cli.readonly;
+    }
+    if terminal.buffers.len() > 1 {
+        terminal.windows[0].buf = 0;
+        let msg = format!("{} files loaded", terminal.buffers.len());
+        terminal.setMessage(&msg);
+    }
+    //+N, +/pattern and -c commands, on the first file
+    for command in cli.commands.iter() {
+        match executeEx(&mut terminal, command) {
+            Ok(true) => return editorExit(&mut terminal),
+            Ok(false) => {}
+            Err(e) => terminal.setMessage(&e.to_string()),
+        }
     }
 
     // keycodes();
 
     loop {
-        editorRefreshScreen(&mut terminal)?;
+        //no need to draw every step of a macro
+        if terminal.macro_queue.is_empty() {
+            editorRefreshScreen(&mut terminal)?;
+        }
         match editorProcessKeypress(&mut terminal) {
             Ok(exit) => {
                 if exit {
-                    stdout().write_all(b"\x1b[2J")?;
-                    stdout().write_all(b"\x1b[H")?;
+                    editorExit(&mut terminal)?;
                     break;
-                } else {
                 }
             }
-            Err(_e) => {
+            Err(e) => {
+                //an error stops any macro being played
+                terminal.macro_queue.clear();
+                terminal.setMessage(&e.to_string());
                 editorRefreshScreen(&mut terminal)?;
             }
         }
@@ -0,0 +1,155 @@
+use crate::motion::Pos;
+
+use std::collections::HashMap;
+
+//most positions the jump list remembers
+const MAX_JUMPS: usize = 100;
+
+// can `c` be set with m
+pub(crate) fn isMarkName(c: char) -> bool {
+    c.is_ascii_alphabetic() || c == '\'' || c == '`'
+}
+
+// file marks (A-Z) can be jumped to from any buffer
+pub(crate) fn isFileMark(c: char) -> bool {
+    c.is_ascii_uppercase()
+}
+
+// keep marks on the same text when lines `start..start + old_len` are replaced by `new_len` lines.
+// Marks below move up or down, marks on lines that went away are dropped
+pub(crate) fn adjustMarks(marks: &mut HashMap<char, Pos>, start: usize, old_len: usize, new_len: usize) {
+    let (start, old_end) = (start as i32, (start + old_len) as i32);
+    let delta = new_len as i32 - old_len as i32;
+    marks.retain(|_, pos| pos.y < start || pos.y >= old_end || pos.y - start < new_len as i32);
+    for pos in marks.values_mut() {
+        if pos.y >= old_end {
+            pos.y += delta;
+        }
+    }
+}
+
+// a place the cursor jumped away from
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) struct Jump {
+    pub(crate) buf: usize,
+    pub(crate) pos: Pos,
+}
+
+// positions from before big jumps, walked with Ctrl-O and Ctrl-I
+#[derive(Default)]
+pub(crate) struct JumpList {
+    entries: Vec<Jump>,
+    idx: usize, //where Ctrl-O/Ctrl-I are up to - entries.len() when we're not walking the list
+}
+
+impl JumpList {
+    // remember where we were before a jump. Only the newest entry for each line is kept
+    pub(crate) fn push(&mut self, jump: Jump) {
+        self.entries.retain(|j| !(j.buf == jump.buf && j.pos.y == jump.pos.y));
+        self.entries.push(jump);
+        if self.entries.len() > MAX_JUMPS {
+            self.entries.remove(0);
+        }
+        self.idx = self.entries.len();
+    }
+
+    // Ctrl-O - `current` is kept the first time so Ctrl-I can come back to it
+    pub(crate) fn older(&mut self, current: Jump, count: usize) -> Option<Jump> {
+        if self.idx >= self.entries.len() {
+            self.push(current);
+            self.idx = self.entries.len() - 1;
+        }
+        let target = self.idx.checked_sub(count)?;
+        self.idx = target;
+        Some(self.entries[target])
+    }
+
+    // Ctrl-I
+    pub(crate) fn newer(&mut self, count: usize) -> Option<Jump> {
+        let target = self.idx + count;
+        if target >= self.entries.len() {
+            return None;
+        }
+        self.idx = target;
+        Some(self.entries[target])
+    }
+
+    // forget jumps into a buffer that was closed and renumber the ones after it
+    pub(crate) fn removeBuffer(&mut self, buf: usize) {
+        self.entries.retain(|j| j.buf != buf);
+        for jump in self.entries.iter_mut() {
+            if jump.buf > buf {
+                jump.buf -= 1;
+            }
+        }
+        self.idx = self.idx.min(self.entries.len());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn jump(buf: usize, y: i32) -> Jump {
+        Jump { buf, pos: Pos { y, x: 0 } }
+    }
+
+    #[test]
+    fn marksFollowTheirLines() {
+        let mut marks: HashMap<char, Pos> = [('a', 1), ('b', 3), ('c', 4), ('d', 8)]
+            .into_iter()
+            .map(|(name, y)| (name, Pos { y, x: 2 }))
+            .collect();
+        //lines 2-4 become one line - b stays on the line that's left, c goes with its line
+        adjustMarks(&mut marks, 2, 3, 1);
+        let mut left: Vec<(char, i32)> = marks.iter().map(|(name, pos)| (*name, pos.y)).collect();
+        left.sort();
+        assert_eq!(left, [('a', 1), ('d', 6)]);
+        adjustMarks(&mut marks, 0, 0, 10);
+        assert_eq!((marks[&'a'].y, marks[&'d'].y, marks[&'d'].x), (11, 16, 2));
+    }
+
+    #[test]
+    fn markNames() {
+        assert!(isMarkName('a') && isMarkName('Z') && isMarkName('\''));
+        assert!(!isMarkName('1') && !isMarkName('<'));
+        assert!(isFileMark('A') && !isFileMark('a'));
+    }
+
+    #[test]
+    fn jumpsGoBackAndForth() {
+        let mut jumps = JumpList::default();
+        jumps.push(jump(0, 1));
+        jumps.push(jump(0, 5));
+        assert_eq!(jumps.older(jump(0, 9), 1), Some(jump(0, 5)));
+        assert_eq!(jumps.older(jump(0, 5), 1), Some(jump(0, 1)));
+        assert_eq!(jumps.older(jump(0, 1), 1), None);
+        //where we started from was kept
+        assert_eq!(jumps.newer(2), Some(jump(0, 9)));
+        assert_eq!(jumps.newer(1), None);
+    }
+
+    #[test]
+    fn oneJumpPerLine() {
+        let mut jumps = JumpList::default();
+        jumps.push(jump(0, 1));
+        jumps.push(jump(0, 2));
+        jumps.push(jump(0, 1));
+        assert_eq!(jumps.entries, [jump(0, 2), jump(0, 1)]);
+        for y in 0..MAX_JUMPS as i32 + 5 {
+            jumps.push(jump(1, y));
+        }
+        assert_eq!(jumps.entries.len(), MAX_JUMPS);
+    }
+
+    #[test]
+    fn closingABufferForgetsItsJumps() {
+        let mut jumps = JumpList::default();
+        jumps.push(jump(0, 1));
+        jumps.push(jump(1, 1));
+        jumps.push(jump(2, 1));
+        jumps.removeBuffer(1);
+        assert_eq!(jumps.entries, [jump(0, 1), jump(1, 1)]);
+        assert_eq!(jumps.older(jump(0, 3), 1), Some(jump(1, 1)));
+    }
+}
@@ -0,0 +1,36 @@
+use crate::visual::VisualKind;
+
+// what keys do depends on the mode we're in
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum Mode {
+    Normal,             //keys are commands
+    Insert,             //keys are typed into the buffer
+    Command,            //keys go into the `:` command line
+    Visual(VisualKind), //motions grow a selection that operators act on
+}
+
+impl Mode {
+    // name shown in the status bar
+    pub(crate) fn label(self) -> &'static str {
+        match self {
+            Mode::Normal => "NORMAL",
+            Mode::Insert => "INSERT",
+            Mode::Command => "COMMAND",
+            Mode::Visual(VisualKind::Char) => "VISUAL",
+            Mode::Visual(VisualKind::Line) => "VISUAL LINE",
+            Mode::Visual(VisualKind::Block) => "VISUAL BLOCK",
+        }
+    }
+
+    // DECSCUSR escape sequence for the cursor shape of the mode
+    pub(crate) fn cursorShape(self) -> &'static [u8] {
+        match self {
+            Mode::Normal | Mode::Visual(_) => b"\x1b[2 q", //steady block
+            Mode::Insert => b"\x1b[6 q",                   //steady bar
+            Mode::Command => b"\x1b[6 q",                  //steady bar
+        }
+    }
+}
+
+//puts the cursor back to whatever the terminal uses by default
+pub(crate) const DEFAULT_CURSOR_SHAPE: &[u8] = b"\x1b[0 q";
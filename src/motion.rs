@@ -0,0 +1,458 @@
+use crate::buffer::lineLen;
+
+// a spot in a buffer - x counts characters, not bytes
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub(crate) struct Pos {
+    pub(crate) y: i32,
+    pub(crate) x: i32,
+}
+
+// how much of the text between the cursor and a motion's target it covers
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum MotionKind {
+    Exclusive, //stops short of the target
+    Inclusive, //includes the character at the target
+    Linewise,  //covers whole lines
+}
+
+// f/t/F/T remember what they searched for so ; and , can repeat it
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) struct FindChar {
+    pub(crate) c: char,
+    pub(crate) forward: bool,
+    pub(crate) till: bool, //t/T stop next to the character instead of on it
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum Motion {
+    Left,
+    Right,
+    Up,
+    Down,
+    WordForward { big: bool },  // w W
+    WordBackward { big: bool }, // b B
+    WordEnd { big: bool },      // e E
+    LineStart,                  // 0
+    FirstNonBlank,              // ^
+    LineEnd,                    // $
+    FileStart,                  // gg
+    FileEnd,                    // G
+    ParagraphForward,           // }
+    ParagraphBackward,          // {
+    Find(FindChar),             // f t F T
+    RepeatFind { reverse: bool }, // ; ,
+    MatchBracket,               // %
+    ToMark { name: char, exact: bool }, // 'a goes to the line, `a to the exact spot
+}
+
+impl Motion {
+    pub(crate) fn kind(self) -> MotionKind {
+        match self {
+            Motion::Up | Motion::Down | Motion::FileStart | Motion::FileEnd => MotionKind::Linewise,
+            Motion::WordEnd { .. } | Motion::LineEnd | Motion::MatchBracket => MotionKind::Inclusive,
+            Motion::Find(find) => {
+                if find.forward {
+                    MotionKind::Inclusive
+                } else {
+                    MotionKind::Exclusive
+                }
+            }
+            Motion::RepeatFind { .. } => MotionKind::Inclusive,
+            Motion::ToMark { exact: false, .. } => MotionKind::Linewise,
+            _ => MotionKind::Exclusive,
+        }
+    }
+
+    // motions that go far enough to be kept in the jump list
+    pub(crate) fn isJump(self) -> bool {
+        matches!(
+            self,
+            Motion::FileStart
+                | Motion::FileEnd
+                | Motion::ParagraphForward
+                | Motion::ParagraphBackward
+                | Motion::MatchBracket
+                | Motion::ToMark { .. }
+        )
+    }
+}
+
+
+/*==============Character helpers===============*/
+fn lineAt(content: &[String], y: i32) -> &str {
+    content.get(y as usize).map_or("", |line| line.as_str())
+}
+
+// character at a position - None at the end of a line
+fn charAt(content: &[String], pos: Pos) -> Option<char> {
+    lineAt(content, pos.y).chars().nth(pos.x as usize)
+}
+
+// 0 for whitespace and line ends, otherwise which kind of word the character belongs to
+fn charClass(c: Option<char>, big: bool) -> u8 {
+    match c {
+        None => 0,
+        Some(c) if c.is_whitespace() => 0,
+        Some(_) if big => 1,
+        Some(c) if c.is_alphanumeric() || c == '_' => 2,
+        Some(_) => 1,
+    }
+}
+
+// step forward one character, counting the end of each line as a character
+fn nextPos(content: &[String], pos: Pos) -> Option<Pos> {
+    if pos.x < lineLen(lineAt(content, pos.y)) {
+        Some(Pos { y: pos.y, x: pos.x + 1 })
+    } else if pos.y + 1 < content.len() as i32 {
+        Some(Pos { y: pos.y + 1, x: 0 })
+    } else {
+        None
+    }
+}
+
+fn prevPos(content: &[String], pos: Pos) -> Option<Pos> {
+    if pos.x > 0 {
+        Some(Pos { y: pos.y, x: pos.x - 1 })
+    } else if pos.y > 0 {
+        Some(Pos { y: pos.y - 1, x: lineLen(lineAt(content, pos.y - 1)) })
+    } else {
+        None
+    }
+}
+
+fn isEmptyLine(content: &[String], pos: Pos) -> bool {
+    pos.x == 0 && lineAt(content, pos.y).is_empty()
+}
+
+pub(crate) fn firstNonBlank(line: &str) -> i32 {
+    line.chars().take_while(|c| c.is_whitespace()).count() as i32
+}
+
+/*==============Motions===============*/
+fn wordForward(content: &[String], from: Pos, big: bool) -> Pos {
+    let mut pos = from;
+    let cls = charClass(charAt(content, pos), big);
+    //get off the word we're on
+    if cls != 0 {
+        while charClass(charAt(content, pos), big) == cls {
+            match nextPos(content, pos) {
+                Some(p) => pos = p,
+                None => return pos,
+            }
+        }
+    }
+    //then skip the gap - empty lines count as words of their own
+    while charClass(charAt(content, pos), big) == 0 {
+        if isEmptyLine(content, pos) && pos != from {
+            break;
+        }
+        match nextPos(content, pos) {
+            Some(p) => pos = p,
+            None => break,
+        }
+    }
+    pos
+}
+
+fn wordEnd(content: &[String], from: Pos, big: bool) -> Pos {
+    let mut pos = match nextPos(content, from) {
+        Some(p) => p,
+        None => return from,
+    };
+    while charClass(charAt(content, pos), big) == 0 {
+        match nextPos(content, pos) {
+            Some(p) => pos = p,
+            None => return pos,
+        }
+    }
+    let cls = charClass(charAt(content, pos), big);
+    while let Some(p) = nextPos(content, pos) {
+        if charClass(charAt(content, p), big) != cls {
+            break;
+        }
+        pos = p;
+    }
+    pos
+}
+
+fn wordBackward(content: &[String], from: Pos, big: bool) -> Pos {
+    let mut pos = match prevPos(content, from) {
+        Some(p) => p,
+        None => return from,
+    };
+    while charClass(charAt(content, pos), big) == 0 {
+        if isEmptyLine(content, pos) {
+            return pos;
+        }
+        match prevPos(content, pos) {
+            Some(p) => pos = p,
+            None => return pos,
+        }
+    }
+    let cls = charClass(charAt(content, pos), big);
+    while let Some(p) = prevPos(content, pos) {
+        if charClass(charAt(content, p), big) != cls {
+            break;
+        }
+        pos = p;
+    }
+    pos
+}
+
+fn isBlankLine(content: &[String], y: i32) -> bool {
+    lineAt(content, y).is_empty()
+}
+
+fn paragraph(content: &[String], from: Pos, forward: bool) -> Pos {
+    let last = content.len() as i32 - 1;
+    let step = if forward { 1 } else { -1 };
+    let mut y = from.y;
+    //skip blank lines we're on, then find the next one
+    while (0..=last).contains(&(y + step)) && isBlankLine(content, y) {
+        y += step;
+    }
+    while (0..=last).contains(&(y + step)) {
+        y += step;
+        if isBlankLine(content, y) {
+            return Pos { y, x: 0 };
+        }
+    }
+    if forward {
+        Pos { y: last, x: (lineLen(lineAt(content, last)) - 1).max(0) }
+    } else {
+        Pos { y: 0, x: 0 }
+    }
+}
+
+fn findChar(content: &[String], from: Pos, find: FindChar, count: usize, repeat: bool) -> Option<Pos> {
+    let chars: Vec<char> = lineAt(content, from.y).chars().collect();
+    let mut x = from.x;
+    //repeating a `t` would just find the same character again, so look one past it
+    if repeat && find.till {
+        x += if find.forward { 1 } else { -1 };
+    }
+    for _ in 0..count {
+        loop {
+            x += if find.forward { 1 } else { -1 };
+            if x < 0 || x >= chars.len() as i32 {
+                return None;
+            }
+            if chars[x as usize] == find.c {
+                break;
+            }
+        }
+    }
+    if find.till {
+        x -= if find.forward { 1 } else { -1 };
+    }
+    Some(Pos { y: from.y, x })
+}
+
+const BRACKETS: [(char, char); 3] = [('(', ')'), ('[', ']'), ('{', '}')];
+
+// jump to the bracket matching the one under (or after) the cursor
+fn matchBracket(content: &[String], from: Pos) -> Option<Pos> {
+    let line: Vec<char> = lineAt(content, from.y).chars().collect();
+    let start = (from.x.max(0) as usize..line.len()).find(|i| {
+        BRACKETS.iter().any(|(open, close)| line[*i] == *open || line[*i] == *close)
+    })?;
+    let c = line[start];
+    let (open, close) = *BRACKETS.iter().find(|(o, cl)| *o == c || *cl == c)?;
+    let forward = c == open;
+
+    let mut pos = Pos { y: from.y, x: start as i32 };
+    let mut depth = 0;
+    loop {
+        match charAt(content, pos) {
+            Some(ch) if ch == open => depth += if forward { 1 } else { -1 },
+            Some(ch) if ch == close => depth += if forward { -1 } else { 1 },
+            _ => {}
+        }
+        if depth == 0 {
+            return Some(pos);
+        }
+        pos = if forward { nextPos(content, pos)? } else { prevPos(content, pos)? };
+    }
+}
+
+// take `step` up to `n` times - stops early once it gets stuck at the start or end of the buffer
+fn repeatStep(n: i32, from: Pos, step: impl Fn(Pos) -> Pos) -> Pos {
+    let mut pos = from;
+    for _ in 0..n {
+        let next = step(pos);
+        if next == pos {
+            break;
+        }
+        pos = next;
+    }
+    pos
+}
+
+// where a motion takes the cursor - None when it can't go anywhere
+pub(crate) fn applyMotion(
+    content: &[String],
+    from: Pos,
+    motion: Motion,
+    count: Option<usize>,
+    last_find: &mut Option<FindChar>,
+) -> Option<Pos> {
+    let n = i32::try_from(count.unwrap_or(1).max(1)).unwrap_or(i32::MAX);
+    let last = content.len() as i32 - 1;
+    let len = lineLen(lineAt(content, from.y));
+    let target = match motion {
+        Motion::Left => {
+            if from.x == 0 {
+                return None;
+            }
+            Pos { y: from.y, x: from.x.saturating_sub(n).max(0) }
+        }
+        Motion::Right => {
+            if from.x >= len - 1 {
+                return None;
+            }
+            Pos { y: from.y, x: from.x.saturating_add(n).min(len - 1) }
+        }
+        Motion::Up | Motion::Down => {
+            let y = if motion == Motion::Up { from.y.saturating_sub(n) } else { from.y.saturating_add(n) };
+            if (motion == Motion::Up && from.y == 0) || (motion == Motion::Down && from.y == last) {
+                return None;
+            }
+            let y = y.clamp(0, last);
+            Pos { y, x: from.x.min((lineLen(lineAt(content, y)) - 1).max(0)) }
+        }
+        Motion::WordForward { big } => {
+            repeatStep(n, from, |pos| wordForward(content, pos, big))
+        }
+        Motion::WordBackward { big } => {
+            repeatStep(n, from, |pos| wordBackward(content, pos, big))
+        }
+        Motion::WordEnd { big } => {
+            repeatStep(n, from, |pos| wordEnd(content, pos, big))
+        }
+        Motion::LineStart => Pos { y: from.y, x: 0 },
+        Motion::FirstNonBlank => Pos { y: from.y, x: firstNonBlank(lineAt(content, from.y)) },
+        Motion::LineEnd => {
+            let y = from.y.saturating_add(n - 1).min(last);
+            Pos { y, x: (lineLen(lineAt(content, y)) - 1).max(0) }
+        }
+        Motion::FileStart | Motion::FileEnd => {
+            let y = match count {
+                Some(line) => (line as i32 - 1).clamp(0, last),
+                None if motion == Motion::FileStart => 0,
+                None => last,
+            };
+            Pos { y, x: firstNonBlank(lineAt(content, y)) }
+        }
+        Motion::ParagraphForward | Motion::ParagraphBackward => {
+            repeatStep(n, from, |pos| paragraph(content, pos, motion == Motion::ParagraphForward))
+        }
+        Motion::Find(find) => {
+            *last_find = Some(find);
+            findChar(content, from, find, n as usize, false)?
+        }
+        Motion::RepeatFind { reverse } => {
+            let mut find = (*last_find)?;
+            if reverse {
+                find.forward = !find.forward;
+            }
+            findChar(content, from, find, n as usize, true)?
+        }
+        Motion::MatchBracket => matchBracket(content, from)?,
+        Motion::ToMark { .. } => return None, //marks aren't kept in the text - callers look them up
+    };
+    //word motions that can't go any further have failed
+    let is_word = matches!(
+        motion,
+        Motion::WordForward { .. } | Motion::WordBackward { .. } | Motion::WordEnd { .. }
+    );
+    if is_word && target == from {
+        return None;
+    }
+    Some(target)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::lines;
+
+    fn pos(y: i32, x: i32) -> Pos {
+        Pos { y, x }
+    }
+
+    fn go(content: &[String], from: Pos, motion: Motion, count: Option<usize>) -> Option<Pos> {
+        applyMotion(content, from, motion, count, &mut None)
+    }
+
+    #[test]
+    fn wordsStopAtPunctuationUnlessBig() {
+        let content = lines(&["foo.bar baz", "", "  qux"]);
+        let w = Motion::WordForward { big: false };
+        let big_w = Motion::WordForward { big: true };
+        assert_eq!(go(&content, pos(0, 0), w, None), Some(pos(0, 3)));
+        assert_eq!(go(&content, pos(0, 0), big_w, None), Some(pos(0, 8)));
+        //an empty line is a word of its own
+        assert_eq!(go(&content, pos(0, 8), w, None), Some(pos(1, 0)));
+        assert_eq!(go(&content, pos(0, 0), w, Some(5)), Some(pos(2, 2)));
+        assert_eq!(go(&content, pos(2, 2), Motion::WordBackward { big: false }, None), Some(pos(1, 0)));
+        assert_eq!(go(&content, pos(0, 0), Motion::WordEnd { big: false }, Some(2)), Some(pos(0, 3)));
+    }
+
+    #[test]
+    fn stuckMotionsFail() {
+        let content = lines(&["one two"]);
+        assert_eq!(go(&content, pos(0, 0), Motion::WordBackward { big: false }, None), None);
+        assert_eq!(go(&content, pos(0, 0), Motion::Left, None), None);
+        assert_eq!(go(&content, pos(0, 6), Motion::Right, None), None);
+        assert_eq!(go(&content, pos(0, 0), Motion::Up, None), None);
+    }
+
+    #[test]
+    fn hugeCountsDontWrap() {
+        let content = lines(&["a b c", "d", "", "e"]);
+        let huge = Some(usize::MAX);
+        assert_eq!(go(&content, pos(0, 0), Motion::Down, huge), Some(pos(3, 0)));
+        assert_eq!(go(&content, pos(3, 0), Motion::Up, huge), Some(pos(0, 0)));
+        assert_eq!(go(&content, pos(0, 0), Motion::Right, huge), Some(pos(0, 4)));
+        assert_eq!(go(&content, pos(0, 0), Motion::LineEnd, huge), Some(pos(3, 0)));
+        //w runs off the end of the last line, so dw can take the last word
+        assert_eq!(go(&content, pos(0, 0), Motion::WordForward { big: false }, huge), Some(pos(3, 1)));
+        assert_eq!(go(&content, pos(0, 0), Motion::ParagraphForward, huge), Some(pos(3, 0)));
+        assert_eq!(go(&content, pos(0, 0), Motion::FileStart, Some(i32::MAX as usize)), Some(pos(3, 0)));
+    }
+
+    #[test]
+    fn paragraphsAndLines() {
+        let content = lines(&["a", "b", "", "", "  c", "d"]);
+        assert_eq!(go(&content, pos(0, 0), Motion::ParagraphForward, None), Some(pos(2, 0)));
+        assert_eq!(go(&content, pos(2, 0), Motion::ParagraphForward, None), Some(pos(5, 0)));
+        assert_eq!(go(&content, pos(5, 0), Motion::ParagraphBackward, None), Some(pos(3, 0)));
+        assert_eq!(go(&content, pos(0, 0), Motion::FileEnd, Some(5)), Some(pos(4, 2)));
+        assert_eq!(go(&content, pos(4, 4), Motion::FirstNonBlank, None), Some(pos(4, 2)));
+    }
+
+    #[test]
+    fn findsCharactersAndRepeats() {
+        let content = lines(&["a,b,c,d"]);
+        let mut last = None;
+        let t = FindChar { c: ',', forward: true, till: true };
+        assert_eq!(applyMotion(&content, pos(0, 0), Motion::Find(t), None, &mut last), Some(pos(0, 0)));
+        assert_eq!(applyMotion(&content, pos(0, 0), Motion::Find(t), Some(2), &mut last), Some(pos(0, 2)));
+        //; after t steps past the character it stopped next to
+        let repeat = Motion::RepeatFind { reverse: false };
+        assert_eq!(applyMotion(&content, pos(0, 2), repeat, None, &mut last), Some(pos(0, 4)));
+        let back = Motion::RepeatFind { reverse: true };
+        assert_eq!(applyMotion(&content, pos(0, 4), back, None, &mut last), Some(pos(0, 2)));
+        let f = FindChar { c: 'z', forward: true, till: false };
+        assert_eq!(applyMotion(&content, pos(0, 0), Motion::Find(f), None, &mut last), None);
+    }
+
+    #[test]
+    fn matchesBrackets() {
+        let content = lines(&["if (a[1]) {", "  x", "}"]);
+        assert_eq!(go(&content, pos(0, 0), Motion::MatchBracket, None), Some(pos(0, 8)));
+        assert_eq!(go(&content, pos(0, 5), Motion::MatchBracket, None), Some(pos(0, 7)));
+        assert_eq!(go(&content, pos(2, 0), Motion::MatchBracket, None), Some(pos(0, 10)));
+        assert_eq!(go(&content, pos(1, 0), Motion::MatchBracket, None), None);
+    }
+}
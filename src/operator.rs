@@ -0,0 +1,259 @@
+use crate::buffer::{lineLen, Buffer};
+use crate::motion::{firstNonBlank, MotionKind, Pos};
+use crate::register::Register;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum Operator {
+    Delete,     // d
+    Change,     // c
+    Yank,       // y
+    Indent,     // >
+    Dedent,     // <
+    Lowercase,  // gu
+    Uppercase,  // gU
+    ToggleCase, // g~
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum RangeKind {
+    Charwise,
+    Linewise,
+    Blockwise,
+}
+
+// a stretch of a buffer an operator works on.
+// Charwise runs from start up to (not including) end, linewise covers lines start.y..=end.y
+// and blockwise covers columns start.x..end.x on each of those lines
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) struct TextRange {
+    pub(crate) start: Pos,
+    pub(crate) end: Pos,
+    pub(crate) kind: RangeKind,
+}
+
+// forces an operator's motion to be a different kind, like `dvj` or `d<C-v>j`
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum ForceKind {
+    Charwise,
+    Linewise,
+    Blockwise,
+}
+
+fn chars(line: &str) -> Vec<char> {
+    line.chars().collect()
+}
+
+// the range covered between the cursor and where a motion took it
+pub(crate) fn motionRange(content: &[String], from: Pos, to: Pos, kind: MotionKind, force: Option<ForceKind>) -> TextRange {
+    let (start, end) = if from <= to { (from, to) } else { (to, from) };
+    let kind = match (force, kind) {
+        (None, kind) => kind,
+        (Some(ForceKind::Linewise), _) => MotionKind::Linewise,
+        (Some(ForceKind::Blockwise), _) => {
+            let left = from.x.min(to.x);
+            let right = from.x.max(to.x) + 1;
+            return TextRange {
+                start: Pos { y: start.y, x: left },
+                end: Pos { y: end.y, x: right },
+                kind: RangeKind::Blockwise,
+            };
+        }
+        //v flips inclusive and exclusive, and makes a linewise motion charwise
+        (Some(ForceKind::Charwise), MotionKind::Linewise) => MotionKind::Exclusive,
+        (Some(ForceKind::Charwise), MotionKind::Inclusive) => MotionKind::Exclusive,
+        (Some(ForceKind::Charwise), MotionKind::Exclusive) => MotionKind::Inclusive,
+    };
+    match kind {
+        MotionKind::Linewise => TextRange { start, end, kind: RangeKind::Linewise },
+        MotionKind::Inclusive => {
+            let len = lineLen(&content[end.y as usize]);
+            TextRange { start, end: Pos { y: end.y, x: (end.x + 1).min(len.max(1)) }, kind: RangeKind::Charwise }
+        }
+        MotionKind::Exclusive => {
+            //an exclusive motion that lands at the start of a later line stops at the end of the line before
+            let mut end = end;
+            if end.x == 0 && end.y > start.y && force.is_none() {
+                end.y -= 1;
+                end.x = lineLen(&content[end.y as usize]);
+            }
+            TextRange { start, end, kind: RangeKind::Charwise }
+        }
+    }
+}
+
+// the text a range covers, one entry per line
+pub(crate) fn rangeText(content: &[String], range: &TextRange) -> Vec<String> {
+    let (sy, ey) = (range.start.y as usize, range.end.y as usize);
+    match range.kind {
+        RangeKind::Linewise => content[sy..=ey].to_vec(),
+        RangeKind::Charwise => {
+            if sy == ey {
+                let line = chars(&content[sy]);
+                let (a, b) = (range.start.x as usize, (range.end.x as usize).min(line.len()));
+                vec![line[a.min(b)..b].iter().collect()]
+            } else {
+                let mut text = vec![chars(&content[sy]).iter().skip(range.start.x as usize).collect()];
+                for line in &content[sy + 1..ey] {
+                    text.push(line.clone());
+                }
+                text.push(chars(&content[ey]).iter().take(range.end.x as usize).collect());
+                text
+            }
+        }
+        RangeKind::Blockwise => content[sy..=ey]
+            .iter()
+            .map(|line| {
+                chars(line)
+                    .iter()
+                    .skip(range.start.x as usize)
+                    .take((range.end.x - range.start.x).max(0) as usize)
+                    .collect()
+            })
+            .collect(),
+    }
+}
+
+// take a range out of the buffer, leaving the cursor where the text was
+fn deleteRange(buffer: &mut Buffer, range: &TextRange) {
+    let (sy, ey) = (range.start.y as usize, range.end.y as usize);
+    match range.kind {
+        RangeKind::Linewise => {
+            buffer.replaceLines(sy, ey + 1, Vec::new());
+            buffer.curs_y = (sy as i32).min(buffer.content.len() as i32 - 1);
+            buffer.curs_x = firstNonBlank(buffer.currRow());
+        }
+        RangeKind::Charwise => {
+            let first = chars(&buffer.content[sy]);
+            let last = chars(&buffer.content[ey]);
+            let start_x = (range.start.x as usize).min(first.len());
+            let end_x = (range.end.x as usize).min(last.len());
+            let joined: String = first[..start_x].iter().chain(last[end_x..].iter()).collect();
+            buffer.replaceLines(sy, ey + 1, vec![joined]);
+            buffer.curs_y = sy as i32;
+            buffer.curs_x = start_x as i32;
+        }
+        RangeKind::Blockwise => {
+            let lines = buffer.content[sy..=ey]
+                .iter()
+                .map(|line| {
+                    let line = chars(line);
+                    let a = (range.start.x as usize).min(line.len());
+                    let b = (range.end.x as usize).min(line.len());
+                    line[..a].iter().chain(line[b..].iter()).collect()
+                })
+                .collect();
+            buffer.replaceLines(sy, ey + 1, lines);
+            buffer.curs_y = sy as i32;
+            buffer.curs_x = range.start.x;
+        }
+    }
+}
+
+// add or take away `width` columns of indent on every line of the range
+fn shiftLines(buffer: &mut Buffer, range: &TextRange, indent: bool, width: usize) {
+    let (sy, ey) = (range.start.y as usize, range.end.y as usize);
+    let lines = buffer.content[sy..=ey]
+        .iter()
+        .map(|line| {
+            if line.is_empty() {
+                line.clone()
+            } else if indent {
+                format!("{}{}", " ".repeat(width), line)
+            } else {
+                let mut line = line.as_str();
+                for _ in 0..width {
+                    line = match line.strip_prefix(' ') {
+                        Some(rest) => rest,
+                        None => line.strip_prefix('\t').unwrap_or(line),
+                    };
+                }
+                line.to_string()
+            }
+        })
+        .collect();
+    buffer.replaceLines(sy, ey + 1, lines);
+    buffer.curs_y = sy as i32;
+    buffer.curs_x = firstNonBlank(buffer.currRow());
+}
+
+fn changeCase(c: char, op: Operator) -> String {
+    match op {
+        Operator::Lowercase => c.to_lowercase().collect(),
+        Operator::Uppercase => c.to_uppercase().collect(),
+        _ if c.is_uppercase() => c.to_lowercase().collect(),
+        _ => c.to_uppercase().collect(),
+    }
+}
+
+fn caseRange(buffer: &mut Buffer, range: &TextRange, op: Operator) {
+    let (sy, ey) = (range.start.y as usize, range.end.y as usize);
+    let lines = buffer.content[sy..=ey]
+        .iter()
+        .enumerate()
+        .map(|(i, line)| {
+            let y = (sy + i) as i32;
+            let (a, b) = match range.kind {
+                RangeKind::Linewise => (0, i32::MAX),
+                RangeKind::Blockwise => (range.start.x, range.end.x),
+                RangeKind::Charwise => (
+                    if y == range.start.y { range.start.x } else { 0 },
+                    if y == range.end.y { range.end.x } else { i32::MAX },
+                ),
+            };
+            line.chars()
+                .enumerate()
+                .map(|(x, c)| {
+                    if (a..b).contains(&(x as i32)) {
+                        changeCase(c, op)
+                    } else {
+                        c.to_string()
+                    }
+                })
+                .collect()
+        })
+        .collect();
+    buffer.replaceLines(sy, ey + 1, lines);
+    buffer.curs_y = range.start.y;
+    buffer.curs_x = if range.kind == RangeKind::Linewise { buffer.curs_x } else { range.start.x };
+}
+
+// run an operator over a range. Returns the text it yanked or deleted so it can be put back later.
+// `shift` is how many columns > and < move lines by
+pub(crate) fn applyOperator(buffer: &mut Buffer, op: Operator, range: &TextRange, shift: usize) -> Option<Register> {
+    let text = rangeText(&buffer.content, range);
+    let register = Register { text, kind: range.kind };
+    match op {
+        Operator::Delete => {
+            deleteRange(buffer, range);
+            Some(register)
+        }
+        Operator::Change => {
+            if range.kind == RangeKind::Linewise {
+                //keep one line to type into, with the indent of the first line
+                let sy = range.start.y as usize;
+                let indent: String = buffer.content[sy].chars().take_while(|c| c.is_whitespace()).collect();
+                buffer.replaceLines(sy, range.end.y as usize + 1, vec![indent.clone()]);
+                buffer.curs_y = sy as i32;
+                buffer.curs_x = lineLen(&indent);
+            } else {
+                deleteRange(buffer, range);
+            }
+            Some(register)
+        }
+        Operator::Yank => {
+            buffer.curs_y = range.start.y;
+            if range.kind != RangeKind::Linewise {
+                buffer.curs_x = range.start.x;
+            }
+            Some(register)
+        }
+        Operator::Indent | Operator::Dedent => {
+            shiftLines(buffer, range, op == Operator::Indent, shift);
+            None
+        }
+        Operator::Lowercase | Operator::Uppercase | Operator::ToggleCase => {
+            caseRange(buffer, range, op);
+            None
+        }
+    }
+}
@@ -0,0 +1,324 @@
+use crate::encoding::Encoding;
+use crate::theme::configDir;
+
+use std::env;
+use std::fs;
+use std::io;
+use std::io::ErrorKind::Other;
+use std::io::{Error, ErrorKind};
+
+//most columns tabstop and shiftwidth can be
+const MAX_WIDTH: u64 = 100;
+//longest timeoutlen - a minute
+const MAX_TIMEOUTLEN: u64 = 60_000;
+//scrolloff is counted in screen rows, so it has to fit in one
+const MAX_SCROLLOFF: u64 = i32::MAX as u64;
+//largest largefile - a terabyte, in megabytes
+const MAX_LARGEFILE: u64 = 1 << 20;
+
+// options each buffer keeps its own copy of - :set changes the buffer and the default, :setlocal just the buffer
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) struct BufferOptions {
+    pub(crate) tabstop: usize,    //columns a tab takes up on screen
+    pub(crate) expandtab: bool,   //the tab key types spaces instead
+    pub(crate) shiftwidth: usize, //columns added or removed by > and <
+    pub(crate) autoindent: bool,  //new lines start with the indent of the line they came from
+    pub(crate) readonly: bool,    //the text can't be changed - only ever set for one buffer
+}
+
+impl Default for BufferOptions {
+    fn default() -> BufferOptions {
+        BufferOptions { tabstop: 4, expandtab: false, shiftwidth: 4, autoindent: false, readonly: false }
+    }
+}
+
+// settings that change how the editor behaves, changed with :set
+pub(crate) struct Options {
+    pub(crate) buffer: BufferOptions, //what new buffers start with
+    pub(crate) ignorecase: bool,      //searches and :s ignore case
+    pub(crate) timeoutlen: u64,       //milliseconds to wait for the next key of a chord
+    pub(crate) number: bool,          //line numbers down the left of each window
+    pub(crate) wrap: bool,            //long lines carry on onto the next row instead of scrolling sideways
+    pub(crate) scrolloff: usize,      //lines kept on screen above and below the cursor
+    pub(crate) theme: String,         //name or path of the color theme
+    pub(crate) eob: String,           //drawn on the rows past the end of the text
+    pub(crate) welcome: bool,         //show the credits in the empty buffer we start with
+    pub(crate) undofile: bool,        //keep undo history on disk so it survives closing the file
+    pub(crate) largefile: u64,        //files of at least this many megabytes are loaded a part at a time
+    pub(crate) legacyencoding: Encoding, //what files that aren't UTF-8 are read as
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            buffer: BufferOptions::default(),
+            ignorecase: false,
+            timeoutlen: 1000,
+            number: false,
+            wrap: true,
+            scrolloff: 0,
+            theme: String::from("default"),
+            eob: String::from("."),
+            welcome: true,
+            undofile: true,
+            largefile: 64,
+            legacyencoding: Encoding::Latin1,
+        }
+    }
+}
+
+// what a :set argument asks for
+enum Value<'a> {
+    Query,           //name?
+    Flag(bool),      //name or noname
+    Assign(&'a str), //name=value
+}
+
+// full names of the options with their short forms
+const NAMES: [(&str, &str); 16] = [
+    ("tabstop", "ts"),
+    ("expandtab", "et"),
+    ("shiftwidth", "sw"),
+    ("autoindent", "ai"),
+    ("ignorecase", "ic"),
+    ("timeoutlen", "tm"),
+    ("number", "nu"),
+    ("wrap", "wrap"),
+    ("scrolloff", "so"),
+    ("theme", "theme"),
+    ("eob", "eob"),
+    ("welcome", "welcome"),
+    ("undofile", "udf"),
+    ("readonly", "ro"),
+    ("largefile", "lf"),
+    ("legacyencoding", "lenc"),
+];
+
+const FLAGS: [&str; 8] =
+    ["expandtab", "autoindent", "ignorecase", "number", "wrap", "welcome", "undofile", "readonly"];
+
+fn fullName(name: &str) -> Option<&'static str> {
+    NAMES.iter().find(|(full, short)| *full == name || *short == name).map(|(full, _)| *full)
+}
+
+fn isFlag(name: &str) -> bool {
+    FLAGS.contains(&name)
+}
+
+fn parseArg(arg: &str) -> Result<(&'static str, Value<'_>), String> {
+    let unknown = |name: &str| format!("Unknown option: {}", name);
+    if let Some(name) = arg.strip_suffix('?') {
+        return Ok((fullName(name).ok_or_else(|| unknown(name))?, Value::Query));
+    }
+    if let Some((name, value)) = arg.split_once('=') {
+        return Ok((fullName(name).ok_or_else(|| unknown(name))?, Value::Assign(value)));
+    }
+    if let Some(name) = fullName(arg) {
+        //asking for an option that isn't on or off shows its value
+        return Ok((name, if isFlag(name) { Value::Flag(true) } else { Value::Query }));
+    }
+    match arg.strip_prefix("no").and_then(fullName) {
+        Some(name) if isFlag(name) => Ok((name, Value::Flag(false))),
+        _ => Err(unknown(arg)),
+    }
+}
+
+// a number for an option - past `max` it would only eat memory or time, or stop fitting where it goes
+fn parseBounded(arg: &str, value: &str, min: u64, max: u64) -> Result<u64, String> {
+    match value.parse::<u64>() {
+        Ok(n) if n >= min && n <= max => Ok(n),
+        Ok(n) if n >= min => Err(format!("Invalid argument: {} (at most {})", arg, max)),
+        _ => Err(format!("Invalid argument: {}", arg)),
+    }
+}
+
+impl BufferOptions {
+    // false if `name` isn't one of the buffer's own options
+    fn set(&mut self, name: &str, value: &Value, arg: &str) -> Result<bool, String> {
+        match (name, value) {
+            ("tabstop", Value::Assign(v)) => self.tabstop = parseBounded(arg, v, 1, MAX_WIDTH)? as usize,
+            ("shiftwidth", Value::Assign(v)) => self.shiftwidth = parseBounded(arg, v, 1, MAX_WIDTH)? as usize,
+            ("expandtab", Value::Flag(on)) => self.expandtab = *on,
+            ("autoindent", Value::Flag(on)) => self.autoindent = *on,
+            ("readonly", Value::Flag(on)) => self.readonly = *on,
+            ("tabstop" | "shiftwidth" | "expandtab" | "autoindent" | "readonly", _) => {
+                return Err(format!("Invalid argument: {}", arg))
+            }
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    fn get(&self, name: &str) -> Option<String> {
+        match name {
+            "tabstop" => Some(self.tabstop.to_string()),
+            "shiftwidth" => Some(self.shiftwidth.to_string()),
+            "expandtab" => Some(self.expandtab.to_string()),
+            "autoindent" => Some(self.autoindent.to_string()),
+            "readonly" => Some(self.readonly.to_string()),
+            _ => None,
+        }
+    }
+}
+
+impl Options {
+    // defaults with <config>/rustwrd/config applied on top. Each line of it is what you'd type after :set.
+    // $RUSTWRD_THEME picks the theme over the config file
+    pub(crate) fn fromConfig() -> io::Result<Options> {
+        let mut options = Options::default();
+        if let Some(dir) = configDir() {
+            let fp = format!("{}/rustwrd/config", dir);
+            match fs::read_to_string(&fp) {
+                Ok(text) => options.load(&text, &fp)?,
+                Err(e) if e.kind() == ErrorKind::NotFound => {}
+                Err(e) => return Err(Error::new(Other, format!("{}: {}", fp, e))),
+            }
+        }
+        match env::var("RUSTWRD_THEME") {
+            Ok(name) if !name.is_empty() => options.theme = name,
+            _ => {}
+        }
+        Ok(options)
+    }
+
+    fn load(&mut self, text: &str, source: &str) -> io::Result<()> {
+        //there are no buffers yet, so everything goes in the defaults
+        let mut local = self.buffer;
+        for (num, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with('"') {
+                continue;
+            }
+            //`set` at the start is allowed so lines can be copied from the command line
+            let line = line.strip_prefix("set ").unwrap_or(line);
+            for arg in line.split_whitespace() {
+                //new buffers never start out read-only, so it would do nothing here
+                let result = match parseArg(arg) {
+                    Ok(("readonly", _)) => Err(String::from("readonly can't be set here (use -R)")),
+                    _ => self.set(&mut local, arg, true),
+                };
+                if let Err(e) = result {
+                    return Err(Error::new(Other, format!("{} line {}: {}", source, num + 1, e)));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // apply one :set argument - `name`, `noname`, `name=value` or `name?`. Options kept per buffer are
+    // changed in `local`, and in the defaults for new buffers too when `global` is set.
+    // Returns a message to show for queries
+    pub(crate) fn set(&mut self, local: &mut BufferOptions, arg: &str, global: bool) -> Result<Option<String>, String> {
+        let (name, value) = parseArg(arg)?;
+        if let Value::Query = value {
+            return self.get(local, name).map(|value| Some(format!("{}={}", name, value)));
+        }
+        if local.set(name, &value, arg)? {
+            //new buffers never start out read-only
+            if global && name != "readonly" {
+                self.buffer.set(name, &value, arg)?;
+            }
+            return Ok(None);
+        }
+        match (name, value) {
+            ("timeoutlen", Value::Assign(v)) => self.timeoutlen = parseBounded(arg, v, 0, MAX_TIMEOUTLEN)?,
+            ("scrolloff", Value::Assign(v)) => self.scrolloff = parseBounded(arg, v, 0, MAX_SCROLLOFF)? as usize,
+            ("theme", Value::Assign(v)) if !v.is_empty() => self.theme = v.to_string(),
+            ("eob", Value::Assign(v)) if v.chars().count() <= 1 => self.eob = v.to_string(),
+            ("ignorecase", Value::Flag(on)) => self.ignorecase = on,
+            ("number", Value::Flag(on)) => self.number = on,
+            ("wrap", Value::Flag(on)) => self.wrap = on,
+            ("welcome", Value::Flag(on)) => self.welcome = on,
+            ("undofile", Value::Flag(on)) => self.undofile = on,
+            ("largefile", Value::Assign(v)) => self.largefile = parseBounded(arg, v, 0, MAX_LARGEFILE)?,
+            ("legacyencoding", Value::Assign(v)) => match Encoding::fromName(v) {
+                Some(encoding) if encoding.isLegacy() => self.legacyencoding = encoding,
+                _ => return Err(format!("Invalid argument: {} (latin1 or cp1252)", arg)),
+            },
+            _ => return Err(format!("Invalid argument: {}", arg)),
+        }
+        Ok(None)
+    }
+
+    pub(crate) fn get(&self, local: &BufferOptions, name: &str) -> Result<String, String> {
+        if let Some(value) = local.get(name) {
+            return Ok(value);
+        }
+        match name {
+            "ignorecase" => Ok(self.ignorecase.to_string()),
+            "timeoutlen" => Ok(self.timeoutlen.to_string()),
+            "number" => Ok(self.number.to_string()),
+            "wrap" => Ok(self.wrap.to_string()),
+            "scrolloff" => Ok(self.scrolloff.to_string()),
+            "theme" => Ok(self.theme.clone()),
+            "eob" => Ok(self.eob.clone()),
+            "welcome" => Ok(self.welcome.to_string()),
+            "undofile" => Ok(self.undofile.to_string()),
+            "largefile" => Ok(self.largefile.to_string()),
+            "legacyencoding" => Ok(self.legacyencoding.name().to_string()),
+            _ => Err(format!("Unknown option: {}", name)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set(options: &mut Options, local: &mut BufferOptions, arg: &str) -> Result<Option<String>, String> {
+        options.set(local, arg, true)
+    }
+
+    #[test]
+    fn setsAndQueries() {
+        let (mut options, mut local) = (Options::default(), BufferOptions::default());
+        assert_eq!(set(&mut options, &mut local, "ts=8"), Ok(None));
+        assert_eq!(set(&mut options, &mut local, "noet"), Ok(None));
+        assert_eq!(set(&mut options, &mut local, "ic"), Ok(None));
+        assert_eq!(set(&mut options, &mut local, "tabstop"), Ok(Some(String::from("tabstop=8"))));
+        assert_eq!(set(&mut options, &mut local, "ic?"), Ok(Some(String::from("ignorecase=true"))));
+        assert_eq!(set(&mut options, &mut local, "lenc=windows-1252"), Ok(None));
+        assert_eq!(options.get(&local, "legacyencoding"), Ok(String::from("cp1252")));
+        assert_eq!(options.buffer.tabstop, 8);
+        assert_eq!(set(&mut options, &mut local, "so=2147483647"), Ok(None));
+    }
+
+    #[test]
+    fn setlocalOnlyChangesTheBuffer() {
+        let (mut options, mut local) = (Options::default(), BufferOptions::default());
+        options.set(&mut local, "sw=2", false).unwrap();
+        assert_eq!((local.shiftwidth, options.buffer.shiftwidth), (2, 4));
+        //and readonly never becomes the default
+        options.set(&mut local, "ro", true).unwrap();
+        assert!(local.readonly && !options.buffer.readonly);
+    }
+
+    #[test]
+    fn rejectsBadValues() {
+        let (mut options, mut local) = (Options::default(), BufferOptions::default());
+        let mut error = |arg| set(&mut options, &mut local, arg).unwrap_err();
+        assert_eq!(error("frob"), "Unknown option: frob");
+        assert_eq!(error("nots"), "Unknown option: nots");
+        assert_eq!(error("ts=0"), "Invalid argument: ts=0");
+        assert_eq!(error("ts=101"), "Invalid argument: ts=101 (at most 100)");
+        assert_eq!(error("sw=99999999999999999999"), "Invalid argument: sw=99999999999999999999");
+        assert_eq!(error("tm=60001"), "Invalid argument: tm=60001 (at most 60000)");
+        assert_eq!(error("so=2147483648"), "Invalid argument: so=2147483648 (at most 2147483647)");
+        assert_eq!(error("lf=18446744073709551615"), "Invalid argument: lf=18446744073709551615 (at most 1048576)");
+        assert_eq!(error("et=1"), "Invalid argument: et=1");
+        assert_eq!(error("eob=ab"), "Invalid argument: eob=ab");
+        assert_eq!(error("lenc=utf-8"), "Invalid argument: lenc=utf-8 (latin1 or cp1252)");
+    }
+
+    #[test]
+    fn loadsConfigFiles() {
+        let mut options = Options::default();
+        let text = "# comment\n\" vim style comment\nset ts=2 et\n\nnonumber scrolloff=3\n";
+        options.load(text, "config").unwrap();
+        assert_eq!((options.buffer.tabstop, options.buffer.expandtab, options.scrolloff), (2, true, 3));
+        let error = options.load("ts=2\nset ro\n", "config").unwrap_err();
+        assert_eq!(error.to_string(), "config line 2: readonly can't be set here (use -R)");
+        let error = options.load("wrap tm=x", "config").unwrap_err();
+        assert_eq!(error.to_string(), "config line 1: Invalid argument: tm=x");
+    }
+}
@@ -1,3 +1,8 @@
+use crate::buffer::{displayCol, lineLen, Buffer};
+use crate::finder::FINDER_ROWS;
+use crate::mode::Mode;
+use crate::theme::Face;
+use crate::window::Rect;
 use crate::Terminal;
 
 use std::io;
@@ -9,20 +14,82 @@ macro_rules! RUST_WRD {
         "0.0.1"
     };
 }
-//Line Prefix - what's at the beginning of every line in the editor
-macro_rules! PRFX {
-    () => {
-        b"."
-    };
+pub(crate) use RUST_WRD; //--version prints it too
+
+// columns taken by line numbers - at least three digits and a space, nothing with `nonumber`
+fn gutterWidth(terminal: &Terminal, buffer: &Buffer, rect: &Rect) -> i32 {
+    if !terminal.options.number {
+        return 0;
+    }
+    let lines = buffer.totalLines().unwrap_or(buffer.content.len());
+    let width = lines.to_string().len().max(3) as i32 + 1;
+    //a window too narrow for them goes without
+    if width < rect.width {
+        width
+    } else {
+        0
+    }
 }
 
-fn editorScroll(terminal: &mut Terminal) {
-    if terminal.curs_y < terminal.v_offset {
-        terminal.v_offset = terminal.curs_y;
+// a line as it looks on screen, with tabs turned into spaces
+fn renderLine(line: &str, tabstop: usize) -> Vec<char> {
+    let mut rendered = Vec::new();
+    for c in line.chars() {
+        if c == '\t' {
+            let spaces = tabstop - rendered.len() % tabstop;
+            rendered.extend(std::iter::repeat_n(' ', spaces));
+        } else {
+            rendered.push(c);
+        }
     }
-    if terminal.curs_y >= terminal.v_offset + terminal.screen_rows {
-        terminal.v_offset = terminal.curs_y - terminal.screen_rows + 1;
+    rendered
+}
+
+// screen rows line `y` takes up - more than one when it wraps
+fn lineRows(terminal: &Terminal, buffer: &Buffer, y: i32, text_width: i32) -> i32 {
+    if !terminal.options.wrap {
+        return 1;
+    }
+    let line = &buffer.content[y as usize];
+    let width = displayCol(line, lineLen(line), buffer.options.tabstop);
+    ((width + text_width - 1) / text_width).max(1)
+}
+
+// keep a window's cursor inside its buffer and on screen, with `scrolloff` lines around it
+fn editorScroll(terminal: &mut Terminal, win: usize, rect: &Rect) {
+    let window = &terminal.windows[win];
+    let buffer = &terminal.buffers[window.buf];
+    let last = buffer.content.len() as i32 - 1;
+    let curs_y = window.curs_y.clamp(0, last.max(0));
+    let line = &buffer.content[curs_y as usize];
+    let curs_x = window.curs_x.clamp(0, lineLen(line));
+    let col = displayCol(line, curs_x, buffer.options.tabstop);
+
+    let rows = rect.textRows().max(1);
+    let text_width = (rect.width - gutterWidth(terminal, buffer, rect)).max(1);
+    let scrolloff = (terminal.options.scrolloff as i32).min((rows - 1) / 2);
+    let mut v_offset = window.v_offset.min((curs_y - scrolloff).max(0));
+    //everything from the top of the window down to `scrolloff` lines past the cursor has to fit
+    let bottom = (curs_y + scrolloff).min(last);
+    //every line takes at least a row, so nothing further up can - saves measuring after a long jump
+    v_offset = v_offset.max(bottom - rows + 1).max(0);
+    let height = |top: i32| (top..=bottom).map(|y| lineRows(terminal, buffer, y, text_width)).sum::<i32>();
+    while v_offset < curs_y && height(v_offset) > rows {
+        v_offset += 1;
+    }
+    let mut h_offset = 0;
+    if !terminal.options.wrap {
+        h_offset = window.h_offset.min(col);
+        if col >= h_offset + text_width {
+            h_offset = col - text_width + 1;
+        }
     }
+
+    let window = &mut terminal.windows[win];
+    window.curs_y = curs_y;
+    window.curs_x = curs_x;
+    window.v_offset = v_offset;
+    window.h_offset = h_offset;
 }
 
 // write out
@@ -34,16 +101,52 @@ pub(crate) fn editorRefreshScreen(terminal: &mut Terminal) -> io::Result<()> {
     appendBuf.extend(b"\x1b[?25l"); //hide cursor
     appendBuf.extend(b"\x1b[H"); //move cursor
 
-    editorDrawRows(terminal, &mut appendBuf)?;
+    let (rects, separators) = terminal.windowRects();
+
+    //adjust scroll offsets - the focused window's cursor has to come out of its buffer first
+    terminal.storeCursor();
+    for (win, rect) in rects.iter() {
+        editorScroll(terminal, *win, rect);
+    }
+    terminal.loadCursor();
 
-    let curs_x = terminal.curs_x + 1;
-    let curs_y = terminal.curs_y + 1;
+    for (win, rect) in rects.iter() {
+        editorDrawRows(terminal, *win, rect, &mut appendBuf)?;
+        editorDrawStatusBar(terminal, *win, rect, &mut appendBuf);
+    }
+    editorDrawSeparators(terminal, &separators, &mut appendBuf);
+    editorDrawFinder(terminal, &mut appendBuf);
+    editorDrawMessageBar(terminal, &mut appendBuf);
 
-    let buf = format!("\x1b[{};{}H", (curs_y - terminal.v_offset), curs_x); //move cursor
-    appendBuf.extend(buf.as_bytes());
+    if terminal.finder.is_some() {
+        //cursor goes after the query
+        let buf = format!("\x1b[{};{}H", terminal.screen_rows + 1, terminal.currMessage().chars().count() + 1);
+        appendBuf.extend(buf.as_bytes());
+    } else if terminal.mode == Mode::Command {
+        //cursor goes at the end of the command line
+        let buf = format!("\x1b[{};{}H", terminal.screen_rows + 1, terminal.cmdline.chars().count() + 2);
+        appendBuf.extend(buf.as_bytes());
+    } else if let Some((_, rect)) = rects.iter().find(|(win, _)| *win == terminal.curr_win) {
+        let buffer = terminal.buf();
+        let gutter = gutterWidth(terminal, buffer, rect);
+        let text_width = (rect.width - gutter).max(1);
+        let col = displayCol(buffer.currRow(), buffer.curs_x, buffer.options.tabstop);
+        //rows taken by the lines above the cursor, then however far down its own line it is
+        let mut row: i32 = (buffer.v_offset..buffer.curs_y).map(|y| lineRows(terminal, buffer, y, text_width)).sum();
+        let col = if terminal.options.wrap {
+            row += col / text_width;
+            col % text_width
+        } else {
+            col - buffer.h_offset
+        };
+        let curs_x = rect.x + gutter + col + 1;
+        let curs_y = rect.y + row + 1;
 
-    editorScroll(terminal); //adjust scroll offset
+        let buf = format!("\x1b[{};{}H", curs_y, curs_x); //move cursor
+        appendBuf.extend(buf.as_bytes());
+    }
 
+    appendBuf.extend(terminal.mode.cursorShape());
     appendBuf.extend(b"\x1b[?25h"); //show cursor
 
     //write out everything in buffer
@@ -52,69 +155,258 @@ pub(crate) fn editorRefreshScreen(terminal: &mut Terminal) -> io::Result<()> {
     Ok(())
 }
 
-fn displayCredits(terminal: &Terminal, ab: &mut Vec<u8>) {
-    let welcome = "Rust Wrd -- Version ";
+// one of the two lines of credits, centered in `width` columns
+fn displayCredits(width: i32, line: i32, eob: &str) -> String {
+    let welcome = format!("Rust Wrd -- Version {}", RUST_WRD!());
     let author = "by Issa Aboudi 2023";
 
-    //center welcome message
-    let padding = (terminal.screen_cols - welcome.len() as i32) / 2;
-    if padding > 0 {
-        ab.extend(PRFX!());
-        let spaces = " ".repeat(padding as usize);
-        ab.extend(spaces.as_bytes());
-    }
-    //Write welcome text and version number
-    ab.extend(welcome.as_bytes());
-    ab.extend(RUST_WRD!().as_bytes());
-    ab.extend(b"\r\n");
-
-    //do it again for author
-    let padding = ((terminal.screen_cols - author.len() as i32) / 2) + 3;
-    if padding > 0 {
-        ab.extend(PRFX!());
-        let spaces = " ".repeat(padding as usize);
-        ab.extend(spaces.as_bytes());
-    }
-    ab.extend(author.as_bytes());
+    let mut text = String::new();
+    if line == 0 {
+        //center welcome message
+        let padding = (width - welcome.len() as i32) / 2;
+        if padding > 0 {
+            text.push_str(eob);
+            text.push_str(&" ".repeat(padding as usize));
+        }
+        //Write welcome text and version number
+        text.push_str(&welcome);
+    } else {
+        //do it again for author
+        let padding = ((width - author.len() as i32) / 2) + 3;
+        if padding > 0 {
+            text.push_str(eob);
+            text.push_str(&" ".repeat(padding as usize));
+        }
+        text.push_str(author);
+    }
+    text
+}
+
+// cut text down to `width` columns and pad it out so it covers whatever was there before
+fn fitToWidth(text: &str, width: i32) -> String {
+    let width = width.max(0) as usize;
+    let mut fitted: String = text.chars().take(width).collect();
+    let used = fitted.chars().count();
+    fitted.push_str(&" ".repeat(width - used));
+    fitted
 }
 
-pub(crate) fn editorDrawRows(terminal: &Terminal, ab: &mut Vec<u8>) -> io::Result<()> {
+// draw a window's text into its rectangle of the screen
+pub(crate) fn editorDrawRows(terminal: &Terminal, win: usize, rect: &Rect, ab: &mut Vec<u8>) -> io::Result<()> {
+    let window = &terminal.windows[win];
+    let buffer = &terminal.buffers[window.buf];
+    //the untouched buffer we start with shows the credits
+    let welcome = terminal.options.welcome
+        && buffer.fp.is_empty()
+        && !buffer.isDirty()
+        && buffer.content == [String::new()];
+    let credits_row = rect.textRows() / 3;
+    //only the focused window shows the selection
+    let selection = if win == terminal.curr_win { terminal.currSelection() } else { None };
+    let gutter = gutterWidth(terminal, buffer, rect);
+    //numbers in a large file count from the start of the file, once they're known
+    let base = buffer.lineBase();
+    let text_width = (rect.width - gutter).max(1);
+    let tabstop = buffer.options.tabstop;
+
     let mut i = 0;
-    loop {
-        if i > terminal.screen_rows {
-            break;
+    let mut file_row = window.v_offset;
+    while i < rect.textRows() {
+        if file_row >= buffer.content.len() as i32 || welcome {
+            ab.extend(format!("\x1b[{};{}H", rect.y + i + 1, rect.x + 1).as_bytes());
+            ab.extend(terminal.theme.sgr(Face::NonText).as_bytes());
+            //add welcome message a third of the way down the window
+            if welcome && (i == credits_row || i == credits_row + 1) {
+                let credits = displayCredits(rect.width, i - credits_row, &terminal.options.eob);
+                ab.extend(fitToWidth(&credits, rect.width).as_bytes());
+            } else {
+                // write the filler on every line
+                ab.extend(fitToWidth(&terminal.options.eob, rect.width).as_bytes());
+            }
+            ab.extend(b"\x1b[0m");
+            i += 1;
+            continue;
         }
 
-        // erase content on first line - ideally would like to hide it outright.
-        ab.extend(b"\x1b[K");
+        let line = &buffer.content[file_row as usize];
+        let rendered = renderLine(line, tabstop);
+        //the selection is in characters, the screen in columns
+        let selected = selection
+            .and_then(|sel| sel.columns(file_row, line))
+            .map(|(a, b)| (displayCol(line, a, tabstop), displayCol(line, b, tabstop)));
+        //a wrapped line is drawn a row at a time, otherwise only what's scrolled into view
+        let starts: Vec<i32> = if terminal.options.wrap {
+            (0..lineRows(terminal, buffer, file_row, text_width)).map(|r| r * text_width).collect()
+        } else {
+            vec![window.h_offset]
+        };
+        for (r, start) in starts.into_iter().enumerate() {
+            if i >= rect.textRows() {
+                break;
+            }
+            ab.extend(format!("\x1b[{};{}H", rect.y + i + 1, rect.x + 1).as_bytes());
+            if gutter > 0 {
+                let number = match base {
+                    Some(base) if r == 0 => (base + file_row as usize + 1).to_string(),
+                    _ => String::new(),
+                };
+                ab.extend(terminal.theme.sgr(Face::LineNumber).as_bytes());
+                ab.extend(format!("{:>width$} ", number, width = gutter as usize - 1).as_bytes());
+            }
+            editorDrawLine(terminal, &rendered, start, text_width, selected, ab);
+            ab.extend(b"\x1b[0m");
+            i += 1;
+        }
+        file_row += 1;
+    }
+    Ok(())
+}
 
-        //add a new line for every line but the last one
-        // if i < terminal.screen_rows + 1 {
-        // }
-        ab.extend(b"\r\n");
+// `width` columns of a rendered line from `start` on, with the columns in `selected` highlighted
+fn editorDrawLine(
+    terminal: &Terminal,
+    line: &[char],
+    start: i32,
+    width: i32,
+    selected: Option<(i32, i32)>,
+    ab: &mut Vec<u8>,
+) {
+    ab.extend(terminal.theme.sgr(Face::Normal).as_bytes());
+    let start = (start.max(0) as usize).min(line.len());
+    let text: String = line[start..].iter().collect();
+    let fitted: Vec<char> = fitToWidth(&text, width).chars().collect();
+    let (a, b) = match selected {
+        Some((a, b)) => (a - start as i32, b - start as i32),
+        None => {
+            ab.extend(fitted.iter().collect::<String>().as_bytes());
+            return;
+        }
+    };
+    let a = (a.max(0) as usize).min(fitted.len());
+    let b = (b.max(0) as usize).clamp(a, fitted.len());
+    ab.extend(fitted[..a].iter().collect::<String>().as_bytes());
+    ab.extend(terminal.theme.sgr(Face::Selection).as_bytes());
+    ab.extend(fitted[a..b].iter().collect::<String>().as_bytes());
+    ab.extend(terminal.theme.sgr(Face::Normal).as_bytes());
+    ab.extend(fitted[b..].iter().collect::<String>().as_bytes());
+}
 
-        // erase content on last line
-        ab.extend(b"\x1b[K");
+// file name and position at the bottom of each window
+fn editorDrawStatusBar(terminal: &Terminal, win: usize, rect: &Rect, ab: &mut Vec<u8>) {
+    let window = &terminal.windows[win];
+    let buffer = &terminal.buffers[window.buf];
+    let curs_y = if win == terminal.curr_win { buffer.curs_y } else { window.curs_y };
+    let modified = if buffer.isDirty() { " (modified)" } else { "" };
+    let readonly = if buffer.options.readonly { " [RO]" } else { "" };
+    let hex = if buffer.hex { " [hex]" } else { "" };
+    //the focused window says which mode we're in
+    let mode = if win == terminal.curr_win {
+        match terminal.recording {
+            Some((name, _)) => format!(" {} recording @{} |", terminal.mode.label(), name),
+            None => format!(" {} |", terminal.mode.label()),
+        }
+    } else {
+        String::new()
+    };
+    let unknown = || String::from("?");
+    let total = buffer.totalLines().map_or_else(unknown, |n| n.to_string());
+    let line = buffer.lineBase().map_or_else(unknown, |base| (base + curs_y as usize + 1).to_string());
+    let left = format!(
+        "{} [{}/{}] {}{}{} - {} lines{}",
+        mode,
+        window.buf + 1,
+        terminal.buffers.len(),
+        buffer.name(),
+        hex,
+        readonly,
+        total,
+        modified
+    );
+    //a hex dump has no encoding - it's bytes
+    let encoding = if buffer.hex { "" } else { buffer.encoding.name() };
+    let right = format!("{} {}/{} ", encoding, line, total);
 
-        let file_row = i + terminal.v_offset;
+    let width = rect.width.max(0) as usize;
+    let mut bar: String = left.chars().take(width).collect();
+    let used = bar.chars().count();
+    if used + right.len() <= width {
+        bar.push_str(&" ".repeat(width - used - right.len()));
+        bar.push_str(&right);
+    } else {
+        bar.push_str(&" ".repeat(width - used));
+    }
 
-        if file_row >= terminal.content.len() as i32 {
-            //add welcome message in the bottom 1/3 of the window
-            if i == (terminal.screen_rows / 3 + 10) && terminal.content.is_empty() {
-                if terminal.content.is_empty() {
-                    displayCredits(terminal, ab);
-                } else {
-                    ab.extend(b"\r\n"); //adds another space - don't remove this
-                }
+    let face = if win == terminal.curr_win { Face::StatusBar } else { Face::StatusBarInactive };
+    ab.extend(format!("\x1b[{};{}H", rect.y + rect.height, rect.x + 1).as_bytes());
+    ab.extend(terminal.theme.sgr(face).as_bytes());
+    ab.extend(bar.as_bytes());
+    ab.extend(b"\x1b[0m");
+}
+
+// the lines between side by side windows
+fn editorDrawSeparators(terminal: &Terminal, separators: &[Rect], ab: &mut Vec<u8>) {
+    for sep in separators {
+        ab.extend(terminal.theme.sgr(Face::Separator).as_bytes());
+        for row in 0..sep.height {
+            ab.extend(format!("\x1b[{};{}H|", sep.y + row + 1, sep.x + 1).as_bytes());
+        }
+        ab.extend(b"\x1b[0m");
+    }
+}
+
+// the file finder's candidates, over the bottom of the windows - a line with the count, then the best
+// matches with the characters that matched picked out
+fn editorDrawFinder(terminal: &Terminal, ab: &mut Vec<u8>) {
+    let finder = match &terminal.finder {
+        Some(finder) => finder,
+        None => return,
+    };
+    let shown = &finder.matches[finder.offset..finder.matches.len().min(finder.offset + FINDER_ROWS)];
+    let height = (shown.len() as i32 + 1).min(terminal.screen_rows);
+    if height <= 0 {
+        return;
+    }
+    let top = terminal.screen_rows - height + 1;
+    let width = terminal.screen_cols;
+
+    let mut count = format!(" {}/{} files", finder.matches.len(), finder.files.len());
+    if finder.truncated {
+        count.push_str(" (too many to list them all - only these were looked at)");
+    }
+    ab.extend(format!("\x1b[{};1H", top).as_bytes());
+    ab.extend(terminal.theme.sgr(Face::StatusBar).as_bytes());
+    ab.extend(fitToWidth(&count, width).as_bytes());
+
+    for (row, candidate) in shown.iter().take((height - 1).max(0) as usize).enumerate() {
+        let selected = finder.offset + row == finder.selected;
+        let face = if selected { Face::Selection } else { Face::Normal };
+        let marker = if selected { "> " } else { "  " };
+        let path: Vec<char> = fitToWidth(&finder.files[candidate.file], width - 2).chars().collect();
+        ab.extend(format!("\x1b[{};1H", top + 1 + row as i32).as_bytes());
+        ab.extend(terminal.theme.sgr(face).as_bytes());
+        ab.extend(marker.chars().take(width.max(0) as usize).collect::<String>().as_bytes());
+        for (i, c) in path.iter().enumerate() {
+            if candidate.positions.contains(&i) {
+                ab.extend(terminal.theme.sgr(Face::SearchMatch).as_bytes());
+                ab.extend(c.to_string().as_bytes());
+                ab.extend(terminal.theme.sgr(face).as_bytes());
             } else {
-                // write a period on every line
-                ab.extend(PRFX!());
+                ab.extend(c.to_string().as_bytes());
             }
-        } else {
-            ab.extend(terminal.content[file_row as usize].as_bytes());
         }
-
-        i += 1;
     }
-    Ok(())
+    ab.extend(b"\x1b[0m");
+}
+
+// messages and prompts go on the last line
+fn editorDrawMessageBar(terminal: &Terminal, ab: &mut Vec<u8>) {
+    ab.extend(format!("\x1b[{};1H", terminal.screen_rows + 1).as_bytes());
+    ab.extend(b"\x1b[K");
+    let width = terminal.screen_cols.max(0) as usize;
+    let msg: String = if terminal.mode == Mode::Command {
+        format!(":{}", terminal.cmdline).chars().take(width).collect()
+    } else {
+        terminal.currMessage().chars().take(width).collect()
+    };
+    ab.extend(msg.as_bytes());
 }
@@ -0,0 +1,293 @@
+// a small regular expression matcher for searches and :s - no groups or alternation, but
+// . * + ? ^ $ [...] and \d \w \s (plus their upper case opposites) all work
+
+use crate::motion::Pos;
+
+#[derive(Clone, Debug)]
+enum Atom {
+    Char(char),
+    Any,
+    Class { ranges: Vec<(char, char)>, negated: bool },
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Repeat {
+    One,
+    ZeroOrMore,
+    OneOrMore,
+    ZeroOrOne,
+}
+
+#[derive(Clone, Debug)]
+struct Piece {
+    atom: Atom,
+    repeat: Repeat,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct Pattern {
+    pieces: Vec<Piece>,
+    anchored_start: bool,
+    anchored_end: bool,
+    ignore_case: bool,
+}
+
+const DIGITS: [(char, char); 1] = [('0', '9')];
+const WORD: [(char, char); 4] = [('a', 'z'), ('A', 'Z'), ('0', '9'), ('_', '_')];
+const SPACE: [(char, char); 4] = [(' ', ' '), ('\t', '\t'), ('\r', '\r'), ('\n', '\n')];
+
+fn escapeClass(c: char) -> Option<Atom> {
+    let (ranges, negated): (&[(char, char)], bool) = match c {
+        'd' => (&DIGITS, false),
+        'D' => (&DIGITS, true),
+        'w' => (&WORD, false),
+        'W' => (&WORD, true),
+        's' => (&SPACE, false),
+        'S' => (&SPACE, true),
+        _ => return None,
+    };
+    Some(Atom::Class { ranges: ranges.to_vec(), negated })
+}
+
+impl Atom {
+    fn matches(&self, c: char, ignore_case: bool) -> bool {
+        let fold = |c: char| if ignore_case { c.to_ascii_lowercase() } else { c };
+        match self {
+            Atom::Char(a) => fold(*a) == fold(c),
+            Atom::Any => true,
+            Atom::Class { ranges, negated } => {
+                let hit = ranges.iter().any(|(lo, hi)| {
+                    (*lo..=*hi).contains(&c)
+                        || (ignore_case
+                            && ((*lo..=*hi).contains(&c.to_ascii_lowercase())
+                                || (*lo..=*hi).contains(&c.to_ascii_uppercase())))
+                });
+                hit != *negated
+            }
+        }
+    }
+}
+
+impl Pattern {
+    pub(crate) fn new(source: &str, ignore_case: bool) -> Result<Pattern, String> {
+        let chars: Vec<char> = source.chars().collect();
+        let mut pieces = Vec::new();
+        let mut i = 0;
+        let anchored_start = chars.first() == Some(&'^');
+        if anchored_start {
+            i += 1;
+        }
+        let mut anchored_end = false;
+        while i < chars.len() {
+            let atom = match chars[i] {
+                '$' if i == chars.len() - 1 => {
+                    anchored_end = true;
+                    i += 1;
+                    continue;
+                }
+                '.' => Atom::Any,
+                '\\' => {
+                    i += 1;
+                    let c = *chars.get(i).ok_or("trailing backslash in pattern")?;
+                    match escapeClass(c) {
+                        Some(atom) => atom,
+                        None => Atom::Char(match c {
+                            't' => '\t',
+                            'n' => '\n',
+                            c => c,
+                        }),
+                    }
+                }
+                '[' => {
+                    i += 1;
+                    let negated = chars.get(i) == Some(&'^');
+                    if negated {
+                        i += 1;
+                    }
+                    let mut ranges = Vec::new();
+                    let mut first = true;
+                    loop {
+                        let c = *chars.get(i).ok_or("missing ] in pattern")?;
+                        if c == ']' && !first {
+                            break;
+                        }
+                        first = false;
+                        let lo = if c == '\\' {
+                            i += 1;
+                            let e = *chars.get(i).ok_or("trailing backslash in pattern")?;
+                            if let Some(Atom::Class { ranges: r, .. }) = escapeClass(e) {
+                                ranges.extend(r);
+                                i += 1;
+                                continue;
+                            }
+                            e
+                        } else {
+                            c
+                        };
+                        if chars.get(i + 1) == Some(&'-') && chars.get(i + 2).is_some_and(|c| *c != ']') {
+                            ranges.push((lo, chars[i + 2]));
+                            i += 3;
+                        } else {
+                            ranges.push((lo, lo));
+                            i += 1;
+                        }
+                    }
+                    Atom::Class { ranges, negated }
+                }
+                c => Atom::Char(c),
+            };
+            i += 1;
+            let repeat = match chars.get(i) {
+                Some('*') => Repeat::ZeroOrMore,
+                Some('+') => Repeat::OneOrMore,
+                Some('?') => Repeat::ZeroOrOne,
+                _ => Repeat::One,
+            };
+            if repeat != Repeat::One {
+                i += 1;
+            }
+            pieces.push(Piece { atom, repeat });
+        }
+        Ok(Pattern { pieces, anchored_start, anchored_end, ignore_case })
+    }
+
+    // length of the match of pieces[p..] starting at text[t..], if there is one
+    fn matchHere(&self, p: usize, text: &[char], t: usize) -> Option<usize> {
+        if p == self.pieces.len() {
+            if self.anchored_end && t != text.len() {
+                return None;
+            }
+            return Some(t);
+        }
+        let piece = &self.pieces[p];
+        let (min, max) = match piece.repeat {
+            Repeat::One => (1, 1),
+            Repeat::ZeroOrOne => (0, 1),
+            Repeat::ZeroOrMore => (0, usize::MAX),
+            Repeat::OneOrMore => (1, usize::MAX),
+        };
+        //greedy: take as many as we can, then back off
+        let mut n = 0;
+        while n < max && t + n < text.len() && piece.atom.matches(text[t + n], self.ignore_case) {
+            n += 1;
+        }
+        loop {
+            if n < min {
+                return None;
+            }
+            if let Some(end) = self.matchHere(p + 1, text, t + n) {
+                return Some(end);
+            }
+            if n == 0 {
+                return None;
+            }
+            n -= 1;
+        }
+    }
+
+    // start of the next match after `from` (or the last one before it), wrapping around the ends of the text
+    pub(crate) fn search(&self, content: &[String], from: Pos, forward: bool) -> Option<Pos> {
+        let count = content.len() as i32;
+        //the cursor's own line is looked at twice - once from the cursor, once after wrapping round to it
+        for i in 0..=count {
+            let y = if forward { from.y + i } else { from.y - i }.rem_euclid(count);
+            let mut starts = Vec::new();
+            let mut x = 0;
+            while let Some((start, end)) = self.find(&content[y as usize], x) {
+                starts.push(start as i32);
+                x = end.max(start + 1);
+            }
+            let found = match (forward, i) {
+                (true, 0) => starts.into_iter().find(|x| *x > from.x),
+                (false, 0) => starts.into_iter().rev().find(|x| *x < from.x),
+                (true, _) => starts.into_iter().next(),
+                (false, _) => starts.into_iter().next_back(),
+            };
+            if let Some(x) = found {
+                return Some(Pos { y, x });
+            }
+        }
+        None
+    }
+
+    // first match at or after character `from`, as (start, end) character offsets
+    pub(crate) fn find(&self, line: &str, from: usize) -> Option<(usize, usize)> {
+        let text: Vec<char> = line.chars().collect();
+        if self.anchored_start {
+            return if from == 0 { self.matchHere(0, &text, 0).map(|end| (0, end)) } else { None };
+        }
+        (from..=text.len()).find_map(|start| self.matchHere(0, &text, start).map(|end| (start, end)))
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::lines;
+
+    fn find(pattern: &str, line: &str) -> Option<(usize, usize)> {
+        Pattern::new(pattern, false).unwrap().find(line, 0)
+    }
+
+    #[test]
+    fn matchesAtomsAndRepeats() {
+        assert_eq!(find("a.c", "xxabc"), Some((2, 5)));
+        assert_eq!(find("ab*c", "ac"), Some((0, 2)));
+        assert_eq!(find("ab+c", "ac"), None);
+        assert_eq!(find("ab+c", "abbbc"), Some((0, 5)));
+        assert_eq!(find("colou?r", "color"), Some((0, 5)));
+        assert_eq!(find("x*", "abc"), Some((0, 0)));
+        assert_eq!(find("a\\.b", "axb a.b"), Some((4, 7)));
+        assert_eq!(find("\\d+", "ab 123"), Some((3, 6)));
+        assert_eq!(find("\\S+", "  foo "), Some((2, 5)));
+    }
+
+    #[test]
+    fn matchesClasses() {
+        assert_eq!(find("[a-c]+", "xxbcad"), Some((2, 5)));
+        assert_eq!(find("[^0-9]", "12a"), Some((2, 3)));
+        assert_eq!(find("[\\w-]+", "  foo-bar"), Some((2, 9)));
+        assert_eq!(find("[]]", "a]"), Some((1, 2)));
+    }
+
+    #[test]
+    fn matchesAnchors() {
+        assert_eq!(find("^foo", "a foo"), None);
+        assert_eq!(find("^foo", "foo"), Some((0, 3)));
+        assert_eq!(Pattern::new("^foo", false).unwrap().find("foo foo", 1), None);
+        assert_eq!(find("bar$", "bar bar"), Some((4, 7)));
+        assert_eq!(find("a$b", "a$b"), Some((0, 3)));
+    }
+
+    #[test]
+    fn ignoresCase() {
+        let pattern = Pattern::new("HeLLo", true).unwrap();
+        assert_eq!(pattern.find("say hello", 0), Some((4, 9)));
+        let pattern = Pattern::new("[A-Z]+", true).unwrap();
+        assert_eq!(pattern.find("abc", 0), Some((0, 3)));
+        assert_eq!(find("HeLLo", "hello"), None);
+    }
+
+    #[test]
+    fn rejectsBadPatterns() {
+        assert!(Pattern::new("abc\\", false).is_err());
+        assert!(Pattern::new("[abc", false).is_err());
+    }
+
+    #[test]
+    fn searchesWrappingAround() {
+        let content = lines(&["foo", "bar foo", "baz"]);
+        let pattern = Pattern::new("foo", false).unwrap();
+        assert_eq!(pattern.search(&content, Pos { y: 1, x: 0 }, true), Some(Pos { y: 1, x: 4 }));
+        assert_eq!(pattern.search(&content, Pos { y: 1, x: 4 }, true), Some(Pos { y: 0, x: 0 }));
+        assert_eq!(pattern.search(&content, Pos { y: 1, x: 4 }, false), Some(Pos { y: 0, x: 0 }));
+        assert_eq!(pattern.search(&content, Pos { y: 0, x: 0 }, false), Some(Pos { y: 1, x: 4 }));
+
+        //a single match is found again from itself
+        let content = lines(&["foo"]);
+        assert_eq!(pattern.search(&content, Pos { y: 0, x: 0 }, true), Some(Pos { y: 0, x: 0 }));
+        let pattern = Pattern::new("qux", false).unwrap();
+        assert_eq!(pattern.search(&content, Pos { y: 0, x: 0 }, true), None);
+    }
+}
@@ -0,0 +1,326 @@
+use crate::buffer::{byteIdx, lineLen, Buffer};
+use crate::motion::firstNonBlank;
+use crate::operator::RangeKind;
+
+use std::collections::{HashMap, VecDeque};
+
+// text that was yanked or deleted, along with how it should be put back
+#[derive(Clone, Debug)]
+pub(crate) struct Register {
+    pub(crate) text: Vec<String>,
+    pub(crate) kind: RangeKind,
+}
+
+// every register we keep. Names work like vim's:
+//  "  unnamed - whatever was last yanked, deleted or stored anywhere
+//  0  last yank
+//  1-9 deletes of whole lines or more than one line, newest first
+//  -  deletes within a line
+//  a-z named registers, A-Z appends to them
+//  + and * the system clipboard
+//  _  black hole - throws text away
+pub(crate) struct Registers {
+    unnamed: Option<Register>,
+    yanked: Option<Register>,
+    deleted: VecDeque<Register>,
+    small_delete: Option<Register>,
+    named: HashMap<char, Register>,
+    clipboard: Option<Register>,
+}
+
+// can `c` come after a `"`
+pub(crate) fn isRegisterName(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '"' | '-' | '+' | '*' | '_')
+}
+
+// add text onto the end of a register, for "A to "Z
+fn appendRegister(register: &mut Register, more: &Register) {
+    if register.kind == RangeKind::Charwise && more.kind == RangeKind::Charwise {
+        let mut lines = more.text.iter();
+        if let (Some(last), Some(first)) = (register.text.last_mut(), lines.next()) {
+            last.push_str(first);
+        }
+        register.text.extend(lines.cloned());
+    } else {
+        register.text.extend(more.text.iter().cloned());
+        register.kind = RangeKind::Linewise;
+    }
+}
+
+impl Registers {
+    pub(crate) fn new() -> Registers {
+        Registers {
+            unnamed: None,
+            yanked: None,
+            deleted: VecDeque::new(),
+            small_delete: None,
+            named: HashMap::new(),
+            clipboard: None,
+        }
+    }
+
+    // keep text that was just yanked (or deleted, when `yank` is false)
+    pub(crate) fn store(&mut self, name: Option<char>, register: Register, yank: bool) {
+        let stored = match name {
+            Some('_') => return,
+            Some(c) if c.is_ascii_lowercase() => {
+                self.named.insert(c, register.clone());
+                register
+            }
+            Some(c) if c.is_ascii_uppercase() => {
+                let named = self.named.entry(c.to_ascii_lowercase()).or_insert(Register {
+                    text: Vec::new(),
+                    kind: register.kind,
+                });
+                if named.text.is_empty() {
+                    *named = register;
+                } else {
+                    appendRegister(named, &register);
+                }
+                named.clone()
+            }
+            Some('+' | '*') => {
+                self.clipboard = Some(register.clone());
+                register
+            }
+            //no register given - yanks go in 0 and deletes in 1-9 or -
+            _ => {
+                if yank {
+                    self.yanked = Some(register.clone());
+                } else if register.kind == RangeKind::Linewise || register.text.len() > 1 {
+                    self.deleted.push_front(register.clone());
+                    self.deleted.truncate(9);
+                } else {
+                    self.small_delete = Some(register.clone());
+                }
+                register
+            }
+        };
+        self.unnamed = Some(stored);
+    }
+
+    // keep a recorded macro - unlike yanking this leaves the unnamed register alone
+    pub(crate) fn storeMacro(&mut self, name: char, text: String) {
+        let unnamed = self.unnamed.take();
+        self.store(Some(name), Register { text: vec![text], kind: RangeKind::Charwise }, true);
+        self.unnamed = unnamed;
+    }
+
+    pub(crate) fn get(&self, name: Option<char>) -> Option<&Register> {
+        match name {
+            None | Some('"') => self.unnamed.as_ref(),
+            Some('0') => self.yanked.as_ref(),
+            Some(c @ '1'..='9') => self.deleted.get(c as usize - '1' as usize),
+            Some('-') => self.small_delete.as_ref(),
+            Some('+' | '*') => self.clipboard.as_ref(),
+            Some(c) if c.is_ascii_alphabetic() => self.named.get(&c.to_ascii_lowercase()),
+            _ => None,
+        }
+    }
+}
+
+// put a register's text into a buffer `count` times, before or after the cursor.
+// Returns how many lines it added
+pub(crate) fn putRegister(buffer: &mut Buffer, register: &Register, before: bool, count: usize) -> usize {
+    let y = buffer.curs_y as usize;
+    let row = buffer.currRow().to_string();
+    //p on an empty line puts at the start of it since there's nothing to go after
+    let x = if before || row.is_empty() { buffer.curs_x } else { buffer.curs_x + 1 };
+    match register.kind {
+        RangeKind::Linewise => {
+            let at = if before { y } else { y + 1 };
+            let lines: Vec<String> = (0..count).flat_map(|_| register.text.iter().cloned()).collect();
+            let added = lines.len();
+            buffer.replaceLines(at, at, lines);
+            buffer.curs_y = at as i32;
+            buffer.curs_x = firstNonBlank(buffer.currRow());
+            added
+        }
+        RangeKind::Charwise => {
+            let mut lines = vec![String::new()];
+            for _ in 0..count {
+                for (i, piece) in register.text.iter().enumerate() {
+                    if i > 0 {
+                        lines.push(String::new());
+                    }
+                    if let Some(last) = lines.last_mut() {
+                        last.push_str(piece);
+                    }
+                }
+            }
+            let split = byteIdx(&row, x);
+            let added = lines.len() - 1;
+            //cursor ends on the last character put, or the first one if it spans lines
+            let curs_x = if added == 0 { x + lineLen(&lines[0]) - 1 } else { x };
+            lines[0].insert_str(0, &row[..split]);
+            if let Some(last) = lines.last_mut() {
+                last.push_str(&row[split..]);
+            }
+            buffer.replaceLines(y, y + 1, lines);
+            buffer.curs_y = y as i32;
+            buffer.curs_x = curs_x.max(0);
+            added
+        }
+        RangeKind::Blockwise => {
+            //each line of the block goes into the same column of the lines below the cursor
+            let width = register.text.iter().map(|l| lineLen(l)).max().unwrap_or(0) as usize;
+            let end = (y + register.text.len()).min(buffer.content.len());
+            let mut added = 0;
+            let lines: Vec<String> = register
+                .text
+                .iter()
+                .enumerate()
+                .map(|(i, piece)| {
+                    let line = match buffer.content.get(y + i) {
+                        Some(line) => line.clone(),
+                        None => {
+                            added += 1;
+                            String::new()
+                        }
+                    };
+                    let len = lineLen(&line) as usize;
+                    let col = x as usize;
+                    let split = byteIdx(&line, x);
+                    let mut new = line[..split].to_string();
+                    new.push_str(&" ".repeat(col.saturating_sub(len)));
+                    for _ in 0..count {
+                        new.push_str(&format!("{:<width$}", piece, width = width));
+                    }
+                    new.push_str(&line[split..]);
+                    if split == line.len() {
+                        new.truncate(new.trim_end().len());
+                    }
+                    new
+                })
+                .collect();
+            buffer.replaceLines(y, end, lines);
+            buffer.curs_x = x;
+            added
+        }
+    }
+}
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+// standard base64 with padding
+fn base64(bytes: &[u8]) -> String {
+    let mut out = String::new();
+    for chunk in bytes.chunks(3) {
+        let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(BASE64[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+// escape sequence that asks the terminal to put text on the system clipboard.
+// Works over ssh since it goes through the terminal rather than a local clipboard tool
+pub(crate) fn osc52(register: &Register, in_tmux: bool) -> String {
+    let mut text = register.text.join("\n");
+    if register.kind == RangeKind::Linewise {
+        text.push('\n');
+    }
+    let seq = format!("\x1b]52;c;{}\x07", base64(text.as_bytes()));
+    if in_tmux {
+        //tmux only passes it on to the outer terminal when wrapped up like this
+        format!("\x1bPtmux;{}\x1b\\", seq.replace('\x1b', "\x1b\x1b"))
+    } else {
+        seq
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::lines;
+
+    fn register(text: &[&str], kind: RangeKind) -> Register {
+        Register { text: lines(text), kind }
+    }
+
+    fn buffer(text: &[&str], y: i32, x: i32) -> Buffer {
+        let mut buffer = Buffer::new();
+        buffer.content = lines(text);
+        (buffer.curs_y, buffer.curs_x) = (y, x);
+        buffer
+    }
+
+    #[test]
+    fn storesLikeVim() {
+        let mut registers = Registers::new();
+        registers.store(None, register(&["yanked"], RangeKind::Charwise), true);
+        registers.store(None, register(&["word"], RangeKind::Charwise), false);
+        registers.store(None, register(&["line"], RangeKind::Linewise), false);
+        registers.store(Some('_'), register(&["gone"], RangeKind::Linewise), false);
+        let text = |name| registers.get(name).map(|r: &Register| r.text[0].as_str());
+        assert_eq!(text(Some('0')), Some("yanked"));
+        assert_eq!(text(Some('-')), Some("word"));
+        assert_eq!(text(Some('1')), Some("line"));
+        assert_eq!(text(None), Some("line"));
+        assert_eq!(text(Some('2')), None);
+    }
+
+    #[test]
+    fn uppercaseAppends() {
+        let mut registers = Registers::new();
+        registers.store(Some('A'), register(&["one"], RangeKind::Charwise), true);
+        registers.store(Some('A'), register(&[" two", "three"], RangeKind::Charwise), true);
+        assert_eq!(registers.get(Some('a')).unwrap().text, ["one two", "three"]);
+        registers.store(Some('A'), register(&["four"], RangeKind::Linewise), true);
+        let a = registers.get(Some('a')).unwrap();
+        assert_eq!((a.text.len(), a.kind), (3, RangeKind::Linewise));
+        //a macro leaves the unnamed register alone
+        registers.storeMacro('q', String::from("dd"));
+        assert_eq!(registers.get(None).unwrap().text.len(), 3);
+    }
+
+    #[test]
+    fn putsCharwise() {
+        let mut buf = buffer(&["abc"], 0, 0);
+        putRegister(&mut buf, &register(&["XY"], RangeKind::Charwise), false, 2);
+        assert_eq!(buf.content, ["aXYXYbc"]);
+        assert_eq!(buf.curs_x, 4);
+        let mut buf = buffer(&["abc"], 0, 1);
+        let added = putRegister(&mut buf, &register(&["1", "2"], RangeKind::Charwise), true, 1);
+        assert_eq!((added, buf.content.clone()), (1, vec![String::from("a1"), String::from("2bc")]));
+    }
+
+    #[test]
+    fn putsLinewise() {
+        let mut buf = buffer(&["a", "b"], 0, 0);
+        let added = putRegister(&mut buf, &register(&["  x"], RangeKind::Linewise), false, 2);
+        assert_eq!(added, 2);
+        assert_eq!(buf.content, ["a", "  x", "  x", "b"]);
+        assert_eq!((buf.curs_y, buf.curs_x), (1, 2));
+    }
+
+    #[test]
+    fn putsBlockwise() {
+        let mut buf = buffer(&["abc", "d"], 0, 0);
+        let added = putRegister(&mut buf, &register(&["1", "22", "3"], RangeKind::Blockwise), false, 1);
+        assert_eq!(added, 1);
+        assert_eq!(buf.content, ["a1 bc", "d22", " 3"]);
+    }
+
+    #[test]
+    fn encodesBase64() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(&[0xff, 0xfe, 0xfd, 0x00]), "//79AA==");
+    }
+
+    #[test]
+    fn wrapsClipboardForTmux() {
+        let line = register(&["hi"], RangeKind::Linewise);
+        assert_eq!(osc52(&line, false), "\x1b]52;c;aGkK\x07");
+        assert_eq!(osc52(&line, true), "\x1bPtmux;\x1b\x1b]52;c;aGkK\x07\x1b\\");
+    }
+}
@@ -0,0 +1,401 @@
+use crate::buffer::Buffer;
+use crate::input::editorChoose;
+use crate::theme::stateDir;
+use crate::window::SplitDir;
+use crate::Terminal;
+
+use nix::errno::Errno;
+use nix::sys::signal::kill;
+use nix::unistd::{access, AccessFlags, Pid};
+use std::fs;
+use std::fs::OpenOptions;
+use std::io;
+use std::io::ErrorKind::Other;
+use std::io::{Error, Write};
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+//first line of every swap file
+const SWAP_HEADER: &str = "rustwrd swap file";
+//endings tried for the swap file when one is already there - like vim's .swp, .swo, .swn
+const SWAP_ENDINGS: [&str; 6] = ["swp", "swo", "swn", "swm", "swl", "swk"];
+//swap files are written after this many edits even if the user never stops typing
+const SWAP_CHANGES: u64 = 200;
+//how long the keyboard has to be quiet before swap files are written
+pub(crate) const SWAP_IDLE: Duration = Duration::from_secs(2);
+//most lines compared line by line in a diff - bigger changes are shown whole
+const MAX_DIFF_CELLS: usize = 1_000_000;
+
+// the swap file (and lock) kept for a buffer while it's open
+pub(crate) struct Swap {
+    file: String,         //the file it's for - the buffer may get renamed
+    path: String,         //the swap file itself
+    lock: Option<String>, //lockfile we hold - None if another instance has one
+    written: u64,         //buffer.changes when the swap file was last written
+}
+
+// what's in a swap file
+struct SwapContents {
+    pid: u32,
+    cursor: (i32, i32),
+    content: Vec<String>,
+}
+
+// where the swap and lock files for `fp` go, without their ending - `dir/.name.` next to the file,
+// or in the state directory when we can't write there
+fn swapPrefix(fp: &str) -> Option<String> {
+    let path = Path::new(fp);
+    let name = path.file_name()?.to_string_lossy().to_string();
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+        _ => Path::new(".").to_path_buf(),
+    };
+    if access(&dir, AccessFlags::W_OK).is_ok() {
+        return Some(format!("{}/.{}.", dir.display(), name));
+    }
+    //the whole path goes in the name with % for / so files with the same name don't clash
+    let full = fs::canonicalize(&dir).ok()?.join(&name);
+    let state = format!("{}/rustwrd/swap", stateDir()?);
+    fs::create_dir_all(&state).ok()?;
+    Some(format!("{}/{}.", state, full.to_string_lossy().replace('/', "%")))
+}
+
+// is there still a process with this pid
+fn processAlive(pid: u32) -> bool {
+    match kill(Pid::from_raw(pid as i32), None) {
+        Ok(()) => true,
+        Err(e) => e == Errno::EPERM, //it's there, it just isn't ours
+    }
+}
+
+fn lockOwner(lock: &str) -> Option<u32> {
+    fs::read_to_string(lock).ok()?.trim().parse().ok()
+}
+
+// take the lock for a file. Err is the pid of another instance that has it
+fn takeLock(lock: &str) -> Result<(), u32> {
+    if let Some(pid) = lockOwner(lock) {
+        if pid != std::process::id() && processAlive(pid) {
+            return Err(pid);
+        }
+        //left behind by an instance that's gone
+        let _ = fs::remove_file(lock);
+    }
+    match OpenOptions::new().write(true).create_new(true).open(lock) {
+        Ok(mut file) => {
+            let _ = writeln!(file, "{}", std::process::id());
+            Ok(())
+        }
+        //someone got there first
+        Err(_) => Err(lockOwner(lock).unwrap_or(0)),
+    }
+}
+
+// swap files other instances (or crashes) left for `fp`
+fn existingSwaps(fp: &str) -> Vec<String> {
+    match swapPrefix(fp) {
+        Some(prefix) => SWAP_ENDINGS
+            .iter()
+            .map(|ending| format!("{}{}", prefix, ending))
+            .filter(|path| Path::new(path).exists())
+            .collect(),
+        None => Vec::new(),
+    }
+}
+
+// start keeping a swap file for `buffer`. Returns the pid of another instance editing the same file
+fn openSwap(buffer: &mut Buffer) -> Option<u32> {
+    let prefix = swapPrefix(&buffer.fp)?;
+    let lock = format!("{}lock", prefix);
+    let (lock, other) = match takeLock(&lock) {
+        Ok(()) => (Some(lock), None),
+        Err(pid) => (None, Some(pid)),
+    };
+    //the first ending nobody is using. The first one belongs to whoever holds the lock
+    let path = SWAP_ENDINGS
+        .iter()
+        .skip(other.map_or(0, |_| 1))
+        .map(|ending| format!("{}{}", prefix, ending))
+        .find(|path| !Path::new(path).exists())?;
+    buffer.swap = Some(Swap { file: buffer.fp.clone(), path, lock, written: buffer.changes });
+    other
+}
+
+fn writeSwap(buffer: &mut Buffer) {
+    let (curs_y, curs_x) = (buffer.curs_y, buffer.curs_x);
+    let changes = buffer.changes;
+    let swap = match buffer.swap.as_mut() {
+        Some(swap) => swap,
+        None => return,
+    };
+    let text = format!(
+        "{}\npid {}\nfile {}\ncursor {} {}\n\n{}",
+        SWAP_HEADER,
+        std::process::id(),
+        swap.file,
+        curs_y,
+        curs_x,
+        buffer.content.join("\n")
+    );
+    //write it somewhere else first so a crash halfway through doesn't wreck the old one
+    let tmp = format!("{}.tmp", swap.path);
+    if fs::write(&tmp, text).and_then(|_| fs::rename(&tmp, &swap.path)).is_ok() {
+        swap.written = changes;
+    }
+}
+
+// stop keeping a swap file - the buffer was closed or the editor is exiting normally
+pub(crate) fn removeSwap(buffer: &mut Buffer) {
+    if let Some(swap) = buffer.swap.take() {
+        let _ = fs::remove_file(&swap.path);
+        if let Some(lock) = swap.lock {
+            let _ = fs::remove_file(lock);
+        }
+    }
+}
+
+fn readSwap(path: &str) -> io::Result<SwapContents> {
+    let text = fs::read_to_string(path).map_err(|e| Error::new(Other, format!("{}: {}", path, e)))?;
+    let (header, body) = match text.split_once("\n\n") {
+        Some(parts) if parts.0.starts_with(SWAP_HEADER) => parts,
+        _ => return Err(Error::new(Other, format!("{}: not a swap file", path))),
+    };
+    let mut contents = SwapContents { pid: 0, cursor: (0, 0), content: body.split('\n').map(String::from).collect() };
+    for line in header.lines().skip(1) {
+        match line.split_once(' ') {
+            Some(("pid", pid)) => contents.pid = pid.parse().unwrap_or(0),
+            Some(("cursor", pos)) => {
+                let mut nums = pos.split(' ').map(|n| n.parse().unwrap_or(0));
+                contents.cursor = (nums.next().unwrap_or(0), nums.next().unwrap_or(0));
+            }
+            _ => {}
+        }
+    }
+    Ok(contents)
+}
+
+// bring swap files up to date. When `idle` every buffer changed since its last write is saved,
+// otherwise only ones that have piled up a lot of changes
+pub(crate) fn editorSyncSwaps(terminal: &mut Terminal, idle: bool) {
+    for buffer in terminal.buffers.iter_mut() {
+        //a swap file of part of a large file couldn't recover it
+        if buffer.fp.is_empty() || buffer.large.is_some() {
+            continue;
+        }
+        //a buffer that got a new name needs a new swap file
+        if buffer.swap.as_ref().is_some_and(|swap| swap.file != buffer.fp) {
+            removeSwap(buffer);
+        }
+        if !buffer.isDirty() {
+            //nothing to recover - the file on disk has it all
+            if let Some(swap) = buffer.swap.as_ref().filter(|swap| Path::new(&swap.path).exists()) {
+                let _ = fs::remove_file(&swap.path);
+            }
+            continue;
+        }
+        if buffer.swap.is_none() {
+            openSwap(buffer);
+        }
+        let due = match &buffer.swap {
+            Some(swap) if idle => swap.written != buffer.changes || !Path::new(&swap.path).exists(),
+            Some(swap) => buffer.changes - swap.written >= SWAP_CHANGES,
+            None => false,
+        };
+        if due {
+            writeSwap(buffer);
+        }
+    }
+}
+
+// how long ago something was changed, roughly
+fn age(time: SystemTime) -> String {
+    let secs = time.elapsed().unwrap_or(Duration::ZERO).as_secs();
+    match secs {
+        0..=59 => format!("{}s ago", secs),
+        60..=3599 => format!("{}m ago", secs / 60),
+        3600..=86399 => format!("{}h ago", secs / 3600),
+        _ => format!("{}d ago", secs / 86400),
+    }
+}
+
+// check for swap files left behind for the buffer that was just opened and ask what to do with them,
+// then start the buffer's own swap file
+pub(crate) fn editorRecoverSwap(terminal: &mut Terminal, buf: usize) -> io::Result<()> {
+    let fp = terminal.buffers[buf].fp.clone();
+    if fp.is_empty() {
+        return Ok(());
+    }
+    let file_time = fs::metadata(&fp).and_then(|m| m.modified()).ok();
+    for path in existingSwaps(&fp) {
+        let swap = match readSwap(&path) {
+            Ok(swap) => swap,
+            Err(_) => continue,
+        };
+        let swap_time = fs::metadata(&path).and_then(|m| m.modified()).ok();
+        //an old swap file has nothing the file doesn't
+        if let (Some(file_time), Some(swap_time)) = (file_time, swap_time) {
+            if swap_time < file_time {
+                continue;
+            }
+        }
+        let name = Path::new(&path).file_name().map_or(path.clone(), |n| n.to_string_lossy().to_string());
+        let who = if processAlive(swap.pid) && swap.pid != std::process::id() {
+            format!("pid {} is still editing it", swap.pid)
+        } else {
+            format!("written {}", swap_time.map_or(String::from("?"), age))
+        };
+        let msg = format!("Swap file {} found ({}): [r]ecover, [d]elete, di[f]f or [k]eep it?", name, who);
+        let mut shown_diff = false;
+        loop {
+            match editorChoose(terminal, &msg, "rdfk")? {
+                Some('r') => {
+                    let buffer = &mut terminal.buffers[buf];
+                    let len = buffer.content.len();
+                    buffer.replaceLines(0, len, swap.content.clone());
+                    buffer.curs_y = swap.cursor.0;
+                    buffer.curs_x = swap.cursor.1;
+                    buffer.clampNormal();
+                    let _ = fs::remove_file(&path);
+                    terminal.setMessage(&format!("Recovered {} - write it to keep the changes", fp));
+                    break;
+                }
+                Some('d') => {
+                    let _ = fs::remove_file(&path);
+                    break;
+                }
+                Some('f') if !shown_diff => {
+                    shown_diff = true;
+                    let (old, new) = (format!("{} (on disk)", fp), format!("{} (swap file)", fp));
+                    let diff = diffLines(&old, &new, &terminal.buffers[buf].content, &swap.content);
+                    editorShowDiff(terminal, diff)?;
+                }
+                Some('f') => {}
+                _ => break, //keep it and open the file as it is on disk
+            }
+        }
+    }
+    if let Some(pid) = openSwap(&mut terminal.buffers[buf]) {
+        terminal.setMessage(&format!("{} is being edited by another instance (pid {})", fp, pid));
+    }
+    if terminal.buffers[buf].isDirty() {
+        writeSwap(&mut terminal.buffers[buf]);
+    }
+    Ok(())
+}
+
+// open a diff in a scratch buffer in a new window below the current one
+pub(crate) fn editorShowDiff(terminal: &mut Terminal, lines: Vec<String>) -> io::Result<()> {
+    //split first, so there's no buffer left behind when there isn't room
+    terminal.editorSplitWindow(SplitDir::Horizontal)?;
+    let mut diff = terminal.newBuffer();
+    diff.content = lines;
+    terminal.addBuffer(diff);
+    Ok(())
+}
+
+// lines that differ between two versions of a file, with two lines of context around each change
+pub(crate) fn diffLines(old_name: &str, new_name: &str, old: &[String], new: &[String]) -> Vec<String> {
+    //lines both start and end with don't need comparing
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..].iter().rev().zip(new[prefix..].iter().rev()).take_while(|(a, b)| a == b).count();
+    let (a, b) = (&old[prefix..old.len() - suffix], &new[prefix..new.len() - suffix]);
+
+    //' ' kept, '-' only in the old one, '+' only in the new one
+    let mut ops: Vec<(char, &String)> = old[..prefix].iter().map(|l| (' ', l)).collect();
+    if a.len() * b.len() <= MAX_DIFF_CELLS {
+        //longest common subsequence of what's left
+        let mut lcs = vec![vec![0u32; b.len() + 1]; a.len() + 1];
+        for i in (0..a.len()).rev() {
+            for j in (0..b.len()).rev() {
+                lcs[i][j] = if a[i] == b[j] { lcs[i + 1][j + 1] + 1 } else { lcs[i + 1][j].max(lcs[i][j + 1]) };
+            }
+        }
+        let (mut i, mut j) = (0, 0);
+        while i < a.len() || j < b.len() {
+            if i < a.len() && j < b.len() && a[i] == b[j] {
+                ops.push((' ', &a[i]));
+                i += 1;
+                j += 1;
+            } else if i < a.len() && (j == b.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+                ops.push(('-', &a[i]));
+                i += 1;
+            } else {
+                ops.push(('+', &b[j]));
+                j += 1;
+            }
+        }
+    } else {
+        ops.extend(a.iter().map(|l| ('-', l)));
+        ops.extend(b.iter().map(|l| ('+', l)));
+    }
+    ops.extend(old[old.len() - suffix..].iter().map(|l| (' ', l)));
+
+    let mut lines = vec![format!("--- {}", old_name), format!("+++ {}", new_name)];
+    let changed: Vec<usize> = ops.iter().enumerate().filter(|(_, (op, _))| *op != ' ').map(|(i, _)| i).collect();
+    if changed.is_empty() {
+        lines.push(String::from("No differences"));
+        return lines;
+    }
+    let mut near = vec![false; ops.len()];
+    for c in changed {
+        for flag in near[c.saturating_sub(2)..(c + 3).min(ops.len())].iter_mut() {
+            *flag = true;
+        }
+    }
+    let mut skipped = false;
+    for (i, (op, line)) in ops.iter().enumerate() {
+        if near[i] {
+            if skipped {
+                lines.push(String::from("..."));
+                skipped = false;
+            }
+            lines.push(format!("{} {}", op, line));
+        } else {
+            skipped = true;
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::lines;
+
+    #[test]
+    fn showsChangesWithContext() {
+        let old = lines(&["1", "2", "3", "4", "5", "6", "7", "8", "9", "10"]);
+        let diff = diffLines("old", "new", &old, &lines(&["1", "2", "3", "4", "x", "6", "7", "8", "9", "10", "11"]));
+        let expected = ["--- old", "+++ new", "...", "  3", "  4", "- 5", "+ x", "  6", "  7", "...", "  9", "  10", "+ 11"];
+        assert_eq!(diff, expected);
+    }
+
+    #[test]
+    fn findsLinesThatMoved() {
+        let diff = diffLines("a", "b", &lines(&["a", "b", "c"]), &lines(&["b", "c", "a"]));
+        assert_eq!(&diff[2..], ["- a", "  b", "  c", "+ a"]);
+        let same = lines(&["a", "b"]);
+        assert_eq!(&diffLines("a", "b", &same, &same)[2..], ["No differences"]);
+    }
+
+    #[test]
+    fn bigChangesAreShownWhole() {
+        //too many lines to line up against each other - everything in between is taken out and put back
+        let old: Vec<String> = (0..2000).map(|i| i.to_string()).collect();
+        let mut new = old.clone();
+        new[0] = String::from("first");
+        new[1999] = String::from("last");
+        let diff = diffLines("a", "b", &old, &new);
+        assert_eq!(diff.iter().filter(|line| line.starts_with('-')).count(), 2001);
+        assert_eq!(diff.iter().filter(|line| line.starts_with('+')).count(), 2001);
+    }
+
+    #[test]
+    fn agesAreRough() {
+        let ago = |secs| age(SystemTime::now() - Duration::from_secs(secs));
+        assert_eq!(ago(5), "5s ago");
+        assert_eq!(ago(125), "2m ago");
+        assert_eq!(ago(7200), "2h ago");
+        assert_eq!(ago(3 * 86400), "3d ago");
+    }
+}
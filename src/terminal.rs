@@ -1,34 +1,113 @@
 #![allow(non_camel_case_types)]
 
-use crate::input::editorReadKey;
+use crate::browse::editorBrowse;
+use crate::buffer::{isWritable, Buffer};
+use crate::command::Change;
+use crate::finder::Finder;
+use crate::disk::{editorCheckOverwrite, FOCUS_EVENTS_OFF, FOCUS_EVENTS_ON};
+use crate::keymap::Keymap;
+use crate::large::{isLarge, openLarge};
+use crate::mark::{isFileMark, Jump, JumpList};
+use crate::mode::{Mode, DEFAULT_CURSOR_SHAPE};
+use crate::motion::{FindChar, Pos};
+use crate::options::Options;
+use crate::register::{osc52, Register, Registers};
+use crate::swap::{editorRecoverSwap, removeSwap};
+use crate::theme::Theme;
+use crate::undo::{loadUndo, saveUndo};
+use crate::visual::{BlockInsert, Selection, VisualKind};
+use crate::window::{Layout, Rect, SplitDir, Window};
 
 use nix::libc::{
     c_ushort, exit, ioctl, perror, winsize, EAGAIN, ISTRIP, STDIN_FILENO, STDOUT_FILENO, TIOCGWINSZ,
 };
 use nix::sys::termios;
+use nix::unistd::{dup2, isatty};
 use nix::sys::termios::SpecialCharacterIndices::{VMIN, VTIME};
+use std::collections::{HashMap, VecDeque};
 use std::ffi::c_int;
 use std::fs;
 use std::io;
 use std::io::ErrorKind::Other;
 use std::io::{stdin, stdout, BufRead, BufReader, Error, ErrorKind, Read, Write};
 use std::os::fd::AsRawFd;
+use std::path::Path;
 
 use std::fs::{read, File};
-use std::thread::sleep;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 pub(crate) struct Terminal {
     /*==============Terminal Stuff=================*/
     pub(crate) orig_termios: termios::Termios,
     pub(crate) screen_rows: c_int, //number of rows in terminal window
     pub(crate) screen_cols: c_int, //number of columms in terminal window
-    pub(crate) curs_x: c_int,      //horizontal position of the cursor
-    pub(crate) curs_y: c_int,      //vertical position of the cursor
     /*==============Text processing===============*/
-    pub(crate) content: Vec<String>, //the text content we are working on
-    pub(crate) v_offset: i32, // vertical scrolling padding
-    pub(crate) fp: String //keep track of file we're editing if we are
+    pub(crate) buffers: Vec<Buffer>, //every file we have open
+    /*==============Windows===============*/
+    pub(crate) windows: Vec<Window>, //views onto the buffers
+    pub(crate) curr_win: usize,      //index of the focused window
+    pub(crate) layout: Layout,       //where each window sits on the screen
+    /*==============Modes===============*/
+    pub(crate) mode: Mode,       //decides what key presses do
+    pub(crate) cmdline: String,  //what's been typed after `:` in command line mode
+    pub(crate) pending: Vec<i32>, //keys of a normal mode command we haven't finished typing
+    pub(crate) last_find: Option<FindChar>, //last f/t/F/T so ; and , can repeat it
+    pub(crate) visual_anchor: Pos,          //where the visual selection started
+    pub(crate) visual_eol: bool,            //block selection was stretched with `$`
+    pub(crate) block_insert: Option<BlockInsert>, //I/A in block mode waiting for insert mode to end
+    pub(crate) registers: Registers,        //text that was yanked or deleted
+    pub(crate) last_change: Option<Change>, //what `.` repeats
+    pub(crate) recording: Option<(char, Vec<i32>)>, //register a macro is being recorded into, and the keys so far
+    pub(crate) macro_queue: VecDeque<i32>,  //keys of macros being played back
+    pub(crate) last_macro: Option<char>,    //register @@ plays
+    pub(crate) jumps: JumpList,             //where Ctrl-O goes back to
+    pub(crate) file_marks: HashMap<char, (String, Pos)>, //A-Z marks in files that have been closed
+    pub(crate) keymap: Keymap,              //keys bound to commands, from the keymap files
+    pub(crate) last_pattern: Option<String>, //last pattern searched for or substituted
+    pub(crate) search_forward: bool,        //which way n searches - N goes the other way
+    pub(crate) options: Options,            //settings changed with :set
+    /*==============Display===============*/
+    pub(crate) theme: Theme, //colors used when drawing each part of the screen
+    pub(crate) status_msg: String, //message shown under the status bar
+    pub(crate) status_time: Instant, //when the message was set - they go away after a while
+    pub(crate) finder: Option<Finder>, //the file finder, while it's open
+}
+
+//how long a message stays in the message bar
+const MESSAGE_TIMEOUT: Duration = Duration::from_secs(5);
+
+// whether two paths name the same file - `a.txt` and `./a.txt` are the same
+fn sameFile(a: &str, b: &str) -> bool {
+    match (fs::canonicalize(a), fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+// everything piped in on stdin, as lines
+pub(crate) fn readPipe() -> io::Result<Vec<String>> {
+    let mut bytes = Vec::new();
+    stdin().lock().read_to_end(&mut bytes)?;
+    let text = String::from_utf8_lossy(&bytes);
+    let mut lines: Vec<String> = text.lines().map(String::from).collect();
+    if lines.is_empty() {
+        lines.push(String::new());
+    }
+    Ok(lines)
+}
+
+// make sure keys come from a terminal. When stdin is a pipe or a file, the terminal we were started from
+// takes its place, so the rest of the editor can keep reading keys from stdin
+pub(crate) fn attachTty() -> io::Result<()> {
+    if isatty(STDIN_FILENO).unwrap_or(false) {
+        return Ok(());
+    }
+    let tty = match fs::OpenOptions::new().read(true).write(true).open("/dev/tty") {
+        Ok(tty) => tty,
+        Err(e) => return Err(Error::new(Other, format!("No terminal to read keys from: {}", e))),
+    };
+    dup2(tty.as_raw_fd(), STDIN_FILENO)?;
+    Ok(())
 }
 
 impl Terminal {
@@ -164,57 +243,489 @@ impl Terminal {
     }
 
     pub(crate) fn initEditor(&mut self) -> io::Result<()> {
-        self.curs_x = 0;
-        self.curs_y = 0;
-        self.v_offset = 0;
-        self.content.push(String::from(" "));
-        self.fp = String::new();
+        self.buffers = vec![self.newBuffer()];
+        self.windows = vec![Window::new(0)];
+        self.curr_win = 0;
+        self.layout = Layout::Leaf(0);
+        //regaining focus is a good time to look for files changed behind our back
+        stdout().write_all(FOCUS_EVENTS_ON)?;
         let mut rows = self.screen_rows;
         let mut cols = self.screen_cols;
         match self.getWindowSize(&mut rows, &mut cols) {
             Ok(_c) => {
                 self.screen_rows = rows;
                 self.screen_cols = cols;
-                self.screen_rows -= 1; //for our status bar
+                self.screen_rows -= 1; //for our message bar - each window has its own status bar
                 Ok(())
             }
-            Err(e) => return Err(Error::new(Other, e)),
+            Err(e) => Err(Error::new(Other, e)),
+        }
+    }
+
+    // index of the buffer shown in the focused window
+    pub(crate) fn currBuf(&self) -> usize {
+        self.windows[self.curr_win].buf
+    }
+
+    pub(crate) fn buf(&self) -> &Buffer {
+        &self.buffers[self.currBuf()]
+    }
+
+    pub(crate) fn bufMut(&mut self) -> &mut Buffer {
+        let i = self.currBuf();
+        &mut self.buffers[i]
+    }
+
+    // while a window is focused its cursor lives in its buffer, so copy it back out
+    pub(crate) fn storeCursor(&mut self) {
+        let buffer = &self.buffers[self.currBuf()];
+        let window = &mut self.windows[self.curr_win];
+        window.curs_x = buffer.curs_x;
+        window.curs_y = buffer.curs_y;
+        window.v_offset = buffer.v_offset;
+        window.h_offset = buffer.h_offset;
+    }
+
+    // ...and hand the focused window's cursor to its buffer
+    pub(crate) fn loadCursor(&mut self) {
+        let window = &self.windows[self.curr_win];
+        let buffer = &mut self.buffers[window.buf];
+        buffer.curs_x = window.curs_x;
+        buffer.curs_y = window.curs_y;
+        buffer.v_offset = window.v_offset;
+        buffer.h_offset = window.h_offset;
+        buffer.clampCursor();
+    }
+
+    /*==============Windows===============*/
+    // every window with the area of the screen it takes up, plus the separators between them
+    pub(crate) fn windowRects(&self) -> (Vec<(usize, Rect)>, Vec<Rect>) {
+        let area = Rect { x: 0, y: 0, width: self.screen_cols, height: self.screen_rows };
+        let mut windows = Vec::new();
+        let mut separators = Vec::new();
+        self.layout.rects(area, &mut windows, &mut separators);
+        (windows, separators)
+    }
+
+    // rows of text the focused window can show
+    pub(crate) fn winRows(&self) -> i32 {
+        let (rects, _) = self.windowRects();
+        rects
+            .iter()
+            .find(|(win, _)| *win == self.curr_win)
+            .map_or(self.screen_rows - 1, |(_, rect)| rect.textRows())
+    }
+
+    fn focusWindow(&mut self, win: usize) {
+        self.editorExitVisual();
+        self.storeCursor();
+        self.curr_win = win;
+        self.loadCursor();
+    }
+
+    // split the focused window in two - both halves show the same buffer
+    pub(crate) fn editorSplitWindow(&mut self, dir: SplitDir) -> io::Result<()> {
+        let (rects, _) = self.windowRects();
+        let rect = rects.iter().find(|(win, _)| *win == self.curr_win).map(|(_, r)| *r);
+        let too_small = match (rect, dir) {
+            (Some(r), SplitDir::Horizontal) => r.height < 4,
+            (Some(r), SplitDir::Vertical) => r.width < 3,
+            (None, _) => true,
+        };
+        if too_small {
+            return Err(Error::new(Other, "Not enough room"));
+        }
+
+        self.storeCursor();
+        let curr = &self.windows[self.curr_win];
+        let new = Window {
+            buf: curr.buf,
+            curs_x: curr.curs_x,
+            curs_y: curr.curs_y,
+            v_offset: curr.v_offset,
+            h_offset: curr.h_offset,
+        };
+        self.windows.push(new);
+        let new_win = self.windows.len() - 1;
+        self.layout.split(self.curr_win, new_win, dir);
+        self.curr_win = new_win;
+        Ok(())
+    }
+
+    pub(crate) fn editorCloseWindow(&mut self) -> io::Result<()> {
+        if self.windows.len() == 1 {
+            return Err(Error::new(Other, "Cannot close last window"));
+        }
+        self.storeCursor();
+        let closing = self.curr_win;
+        self.windows.remove(closing);
+        self.layout.remove(closing);
+        //focus whichever window took over the space
+        self.curr_win = closing.min(self.windows.len() - 1);
+        self.loadCursor();
+        Ok(())
+    }
+
+    // close every window but the focused one
+    pub(crate) fn editorOnlyWindow(&mut self) {
+        self.storeCursor();
+        let window = self.windows.swap_remove(self.curr_win);
+        self.windows = vec![window];
+        self.curr_win = 0;
+        self.layout = Layout::Leaf(0);
+        self.loadCursor();
+    }
+
+    // move focus around the windows in screen order - delta is +1 for next, -1 for previous
+    pub(crate) fn editorCycleWindow(&mut self, delta: i32) {
+        let (rects, _) = self.windowRects();
+        let order: Vec<usize> = rects.iter().map(|(win, _)| *win).collect();
+        let pos = order.iter().position(|w| *w == self.curr_win).unwrap_or(0) as i32;
+        let next = order[(pos + delta).rem_euclid(order.len() as i32) as usize];
+        self.focusWindow(next);
+    }
+
+    pub(crate) fn editorResizeWindow(&mut self, dir: SplitDir, delta: i32) {
+        let area = Rect { x: 0, y: 0, width: self.screen_cols, height: self.screen_rows };
+        if !self.layout.resize(self.curr_win, dir, delta, area) {
+            self.setMessage("No window to resize against");
+        }
+    }
+
+    pub(crate) fn editorEqualizeWindows(&mut self) {
+        self.layout.equalize();
+    }
+
+    pub(crate) fn setMessage(&mut self, msg: &str) {
+        self.status_msg = String::from(msg);
+        self.status_time = Instant::now();
+    }
+
+    /*==============Visual mode===============*/
+    pub(crate) fn editorEnterVisual(&mut self, kind: VisualKind) {
+        let buffer = self.buf();
+        self.visual_anchor = Pos { y: buffer.curs_y, x: buffer.curs_x };
+        self.visual_eol = false;
+        self.mode = Mode::Visual(kind);
+    }
+
+    // the selection being made in the focused window, if we're in visual mode
+    pub(crate) fn currSelection(&self) -> Option<Selection> {
+        match self.mode {
+            Mode::Visual(kind) => {
+                let buffer = self.buf();
+                //the anchor could be left past the end if lines went away underneath it
+                let last = buffer.content.len() as i32 - 1;
+                let anchor = Pos { y: self.visual_anchor.y.min(last), x: self.visual_anchor.x };
+                let cursor = Pos { y: buffer.curs_y, x: buffer.curs_x };
+                Some(Selection { anchor, cursor, kind, to_eol: self.visual_eol })
+            }
+            _ => None,
+        }
+    }
+
+    // back to normal mode, remembering the selection for gv
+    pub(crate) fn editorExitVisual(&mut self) {
+        if let Some(selection) = self.currSelection() {
+            self.bufMut().last_visual = Some(selection);
+            self.mode = Mode::Normal;
+        }
+    }
+
+    /*==============Marks===============*/
+    pub(crate) fn setMark(&mut self, name: char) {
+        let pos = Pos { y: self.buf().curs_y, x: self.buf().curs_x };
+        let name = if name == '`' { '\'' } else { name };
+        //a file mark can only be in one buffer
+        if isFileMark(name) {
+            for buffer in self.buffers.iter_mut() {
+                buffer.marks.remove(&name);
+            }
+            self.file_marks.remove(&name);
+        }
+        self.bufMut().marks.insert(name, pos);
+    }
+
+    // buffer and position of a mark - a-z are in the current buffer, A-Z can be in any of them
+    pub(crate) fn findMark(&self, name: char) -> Option<(usize, Pos)> {
+        let curr = self.currBuf();
+        let (buf, pos) = match name {
+            '<' | '>' => {
+                let selection = self.buf().last_visual?;
+                let (start, end) = if selection.anchor <= selection.cursor {
+                    (selection.anchor, selection.cursor)
+                } else {
+                    (selection.cursor, selection.anchor)
+                };
+                (curr, if name == '<' { start } else { end })
+            }
+            '`' => (curr, *self.buf().marks.get(&'\'')?),
+            c if isFileMark(c) => {
+                let buf = self.buffers.iter().position(|b| b.marks.contains_key(&c))?;
+                (buf, self.buffers[buf].marks[&c])
+            }
+            c => (curr, *self.buf().marks.get(&c)?),
+        };
+        let content = &self.buffers[buf].content;
+        let y = pos.y.clamp(0, content.len() as i32 - 1);
+        Some((buf, Pos { y, x: pos.x }))
+    }
+
+    // open the file a closed file mark is in again, which gives the mark back to its buffer
+    pub(crate) fn editorReopenMark(&mut self, name: char) -> io::Result<()> {
+        let Some((fp, _)) = self.file_marks.get(&name).cloned() else {
+            return Ok(());
+        };
+        self.editorOpenFile(&fp)?;
+        self.restoreFileMarks(self.currBuf());
+        Ok(())
+    }
+
+    // hand the file marks kept for a buffer's file back to it - while a file is open its marks
+    // live in its buffer, so they move with the text when lines are added or deleted
+    fn restoreFileMarks(&mut self, buf: usize) {
+        let fp = &self.buffers[buf].fp;
+        if fp.is_empty() {
+            return;
         }
+        let names: Vec<char> =
+            self.file_marks.iter().filter(|(_, (path, _))| sameFile(path, fp)).map(|(&name, _)| name).collect();
+        for name in names {
+            if let Some((_, pos)) = self.file_marks.remove(&name) {
+                self.buffers[buf].marks.insert(name, pos);
+            }
+        }
+    }
+
+    // remember where the cursor is before jumping somewhere far away
+    pub(crate) fn pushJump(&mut self) {
+        let pos = Pos { y: self.buf().curs_y, x: self.buf().curs_x };
+        self.jumps.push(Jump { buf: self.currBuf(), pos });
+        self.bufMut().marks.insert('\'', pos);
     }
 
-    // read from file
+    // go to a spot in any buffer
+    pub(crate) fn goTo(&mut self, buf: usize, pos: Pos) {
+        if buf != self.currBuf() {
+            self.showBuffer(buf);
+        }
+        let buffer = self.bufMut();
+        buffer.curs_y = pos.y;
+        buffer.curs_x = pos.x;
+        buffer.clampCursor();
+    }
+
+    // keep yanked or deleted text - the + and * registers also go to the system clipboard
+    pub(crate) fn storeRegister(&mut self, name: Option<char>, register: Register, yank: bool) -> io::Result<()> {
+        if matches!(name, Some('+' | '*')) {
+            let in_tmux = std::env::var_os("TMUX").is_some();
+            stdout().write_all(osc52(&register, in_tmux).as_bytes())?;
+            stdout().flush()?;
+        }
+        self.registers.store(name, register, yank);
+        Ok(())
+    }
+
+    // message to draw - empty once it's been up for long enough
+    pub(crate) fn currMessage(&self) -> &str {
+        if self.status_time.elapsed() < MESSAGE_TIMEOUT {
+            self.status_msg.as_str()
+        } else {
+            ""
+        }
+    }
+
+    // an empty buffer with the options new buffers start with
+    pub(crate) fn newBuffer(&self) -> Buffer {
+        let mut buffer = Buffer::new();
+        buffer.options = self.options.buffer;
+        buffer
+    }
+
+    // open a file in a new buffer and show it in the focused window
     pub(crate) fn editorOpenFile(&mut self, fp: &str) -> io::Result<()> {
-        match File::open(fp) {
-            Ok(file) => {
-                self.content.pop(); // by default has empty first line - get rid of it when reading from a file
-                let bufreader = BufReader::new(file);
-                for line in bufreader.lines() {
-                    let text = line.unwrap().replace('\t', "    ").to_owned();
-                    self.content.push(text);
-                }
-                self.fp = String::from(fp);
+        if Path::new(fp).is_dir() {
+            return editorBrowse(self, fp);
+        }
+        if let Some(i) = self.buffers.iter().position(|b| b.fp == fp) {
+            if i != self.currBuf() {
+                self.pushJump();
+                self.showBuffer(i); //already open
+            }
+            return Ok(());
+        }
+        if isLarge(fp, self.options.largefile) {
+            let mut buffer = openLarge(fp)?;
+            buffer.options = self.options.buffer;
+            buffer.options.readonly = !isWritable(fp);
+            let utf8 = buffer.large.as_ref().unwrap().utf8;
+            self.addBuffer(buffer);
+            if utf8 {
+                self.setMessage(&format!("\"{}\" is large - only the part being looked at is loaded", fp));
+            } else {
+                self.setMessage(&format!("\"{}\" is large and not UTF-8 - it can only be looked at", fp));
+            }
+            return Ok(());
+        }
+        let mut buffer = Buffer::open(fp, self.options.legacyencoding)?;
+        buffer.options = self.options.buffer;
+        buffer.options.readonly = !isWritable(fp);
+        if self.options.undofile {
+            if let Some(history) = loadUndo(fp, &buffer.content) {
+                buffer.restoreUndo(history);
             }
-            Err(e) => return Err(Error::new(Other, e)),
         }
+        self.addBuffer(buffer);
+        editorRecoverSwap(self, self.currBuf())
+    }
+
+    // text piped in on stdin goes in a buffer without a name - :w file gives it one
+    pub(crate) fn editorOpenPiped(&mut self, content: Vec<String>) {
+        let mut buffer = self.newBuffer();
+        buffer.content = content;
+        self.addBuffer(buffer);
+    }
+
+    // show a buffer that was just loaded
+    pub(crate) fn addBuffer(&mut self, buffer: Buffer) {
+        //the empty buffer we start with gets replaced by the first file
+        let scratch = self.buf();
+        let shown_elsewhere = self.windows.iter().filter(|w| w.buf == self.currBuf()).count() > 1;
+        if scratch.fp.is_empty() && !scratch.isDirty() && scratch.content == [String::new()] && !shown_elsewhere {
+            let i = self.currBuf();
+            self.buffers[i] = buffer;
+        } else {
+            self.pushJump();
+            self.buffers.push(buffer);
+            self.showBuffer(self.buffers.len() - 1);
+        }
+        self.restoreFileMarks(self.currBuf());
+    }
+
+    // switch the focused window over to another buffer - buffers remember where their cursor was
+    fn showBuffer(&mut self, buf: usize) {
+        self.editorExitVisual();
+        self.windows[self.curr_win].buf = buf;
+        self.buffers[buf].clampCursor();
+    }
+
+    // errors if the current buffer is read-only - checked before anything that would change its text.
+    // A directory listing never changes, since its lines have to stay in step with its entries
+    pub(crate) fn checkModifiable(&self) -> io::Result<()> {
+        if self.buf().listing.is_some() {
+            return Err(Error::new(Other, "Directory listings can't be changed"));
+        }
+        if self.buf().large.as_ref().is_some_and(|large| !large.utf8) {
+            return Err(Error::new(Other, "Large files can only be changed if they're UTF-8"));
+        }
+        if self.buf().options.readonly {
+            return Err(Error::new(Other, "Buffer is read-only (:setlocal noreadonly to change it)"));
+        }
+        Ok(())
+    }
+
+    // write the current buffer to its file - unless `force` is set, a file changed by another program since
+    // it was read is only overwritten if the user says so
+    pub(crate) fn editorWriteFile(&mut self, force: bool) -> io::Result<()> {
+        //asking may open a diff window, so hold on to which buffer this is
+        let buf = self.currBuf();
+        if !force {
+            if self.buffers[buf].options.readonly {
+                return Err(Error::new(Other, "File is read-only (add ! to override)"));
+            }
+            editorCheckOverwrite(self)?;
+        }
+        let written = self.buffers[buf].write()?;
+        let buffer = &self.buffers[buf];
+        let lines = buffer.totalLines().map_or(String::from("?"), |n| n.to_string());
+        let mut msg = format!("\"{}\" {} lines, {} bytes written", buffer.fp, lines, written);
+        //undo doesn't survive writing a large file anyway
+        if self.options.undofile && buffer.large.is_none() {
+            if let Err(e) = saveUndo(&buffer.fp, &buffer.content, &buffer.undo) {
+                msg.push_str(&format!(" - undo history not saved: {}", e));
+            }
+        }
+        self.setMessage(&msg);
         Ok(())
     }
 
-    pub(crate) fn editorWriteFile(&mut self, fp: String) -> io::Result<()> {
-        match File::create(fp) {
-            Ok(mut file) => {
-                file.write_all(self.content.join("\r\n").as_bytes()).expect("Invalid Write");
-                stdout().write_all(b"\r\n\r\n\t\tSaving File, Please Wait")?;
-                stdout().flush()?;
-                sleep(Duration::from_secs(2));
+    // cycle through the open buffers - delta is +1 for next, -1 for previous
+    pub(crate) fn editorSwitchBuffer(&mut self, delta: i32) {
+        let count = self.buffers.len() as i32;
+        let next = (self.currBuf() as i32 + delta).rem_euclid(count) as usize;
+        if next != self.currBuf() {
+            self.pushJump();
+        }
+        self.showBuffer(next);
+        let msg = format!("Buffer {}: {}", next + 1, self.buf().name());
+        self.setMessage(&msg);
+    }
+
+    // one line summary of every buffer, the current one in brackets
+    pub(crate) fn editorListBuffers(&self) -> String {
+        let mut entries = Vec::new();
+        for (i, buffer) in self.buffers.iter().enumerate() {
+            let dirty = if buffer.isDirty() { "+" } else { "" };
+            let entry = format!("{}:{}{}", i + 1, buffer.name(), dirty);
+            if i == self.currBuf() {
+                entries.push(format!("[{}]", entry));
+            } else {
+                entries.push(entry);
+            }
+        }
+        entries.join(" ")
+    }
+
+    // close the current buffer - refuses to throw away unsaved changes unless forced.
+    // Any window showing it moves on to another buffer
+    pub(crate) fn editorCloseBuffer(&mut self, force: bool) -> io::Result<()> {
+        if !force && self.buf().isDirty() {
+            return Err(Error::new(
+                Other,
+                format!("{} has unsaved changes", self.buf().name()),
+            ));
+        }
+        self.storeCursor();
+        let closing = self.currBuf();
+        removeSwap(&mut self.buffers[closing]);
+        //file marks outlive the buffer, so 'A can open the file again
+        let fp = self.buffers[closing].fp.clone();
+        if !fp.is_empty() {
+            for (&name, &pos) in self.buffers[closing].marks.iter().filter(|(&name, _)| isFileMark(name)) {
+                self.file_marks.insert(name, (fp.clone(), pos));
+            }
+        }
+        self.buffers.remove(closing);
+        self.jumps.removeBuffer(closing);
+        if self.buffers.is_empty() {
+            let scratch = self.newBuffer();
+            self.buffers.push(scratch);
+        }
+        let replacement = closing.min(self.buffers.len() - 1);
+        for window in self.windows.iter_mut() {
+            if window.buf == closing {
+                window.buf = replacement;
+                window.curs_x = self.buffers[replacement].curs_x;
+                window.curs_y = self.buffers[replacement].curs_y;
+                window.v_offset = self.buffers[replacement].v_offset;
+                window.h_offset = self.buffers[replacement].h_offset;
+            } else if window.buf > closing {
+                window.buf -= 1;
             }
-            Err(e) => return Err(Error::new(Other, e)),
         }
+        self.loadCursor();
         Ok(())
     }
+
+    pub(crate) fn hasUnsavedBuffers(&self) -> bool {
+        self.buffers.iter().any(|b| b.isDirty())
+    }
 }
 
 impl Drop for Terminal {
     fn drop(&mut self) {
+        let _ = stdout().write_all(DEFAULT_CURSOR_SHAPE);
+        let _ = stdout().write_all(FOCUS_EVENTS_OFF);
         println!("Program Ending\r\n");
         Terminal::disableRawMode(self).unwrap();
     }
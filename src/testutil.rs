@@ -0,0 +1,5 @@
+// helpers shared by the unit tests
+
+pub(crate) fn lines(text: &[&str]) -> Vec<String> {
+    text.iter().map(|line| line.to_string()).collect()
+}
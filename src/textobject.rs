@@ -0,0 +1,313 @@
+use crate::buffer::lineLen;
+use crate::motion::Pos;
+use crate::operator::{RangeKind, TextRange};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum ObjectKind {
+    Word { big: bool },                // w W
+    Quote(char),                       // " ' `
+    Bracket { open: char, close: char }, // ( [ { <
+    Paragraph,                         // p
+}
+
+// `inner` objects leave out the surrounding whitespace/quotes/brackets, `a` objects take them too
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) struct TextObject {
+    pub(crate) kind: ObjectKind,
+    pub(crate) inner: bool,
+}
+
+// the object named by the key after `i` or `a`
+pub(crate) fn objectFromChar(c: char, inner: bool) -> Option<TextObject> {
+    let kind = match c {
+        'w' => ObjectKind::Word { big: false },
+        'W' => ObjectKind::Word { big: true },
+        '"' | '\'' | '`' => ObjectKind::Quote(c),
+        '(' | ')' | 'b' => ObjectKind::Bracket { open: '(', close: ')' },
+        '[' | ']' => ObjectKind::Bracket { open: '[', close: ']' },
+        '{' | '}' | 'B' => ObjectKind::Bracket { open: '{', close: '}' },
+        '<' | '>' => ObjectKind::Bracket { open: '<', close: '>' },
+        'p' => ObjectKind::Paragraph,
+        _ => return None,
+    };
+    Some(TextObject { kind, inner })
+}
+
+fn lineChars(content: &[String], y: i32) -> Vec<char> {
+    content.get(y as usize).map_or(Vec::new(), |line| line.chars().collect())
+}
+
+fn wordClass(c: char, big: bool) -> u8 {
+    if c.is_whitespace() {
+        0
+    } else if big || !(c.is_alphanumeric() || c == '_') {
+        1
+    } else {
+        2
+    }
+}
+
+fn wordObject(content: &[String], pos: Pos, big: bool, inner: bool) -> Option<TextRange> {
+    let line = lineChars(content, pos.y);
+    if line.is_empty() {
+        return None;
+    }
+    let x = (pos.x.max(0) as usize).min(line.len() - 1);
+    let run = |from: usize| -> (usize, usize) {
+        let cls = wordClass(line[from], big);
+        let mut a = from;
+        while a > 0 && wordClass(line[a - 1], big) == cls {
+            a -= 1;
+        }
+        let mut b = from + 1;
+        while b < line.len() && wordClass(line[b], big) == cls {
+            b += 1;
+        }
+        (a, b)
+    };
+    let (mut a, mut b) = run(x);
+    if !inner {
+        if wordClass(line[x], big) == 0 {
+            //on whitespace `aw` is the whitespace plus the word after it
+            if b < line.len() {
+                b = run(b).1;
+            }
+        } else if b < line.len() && wordClass(line[b], big) == 0 {
+            b = run(b).1; //trailing whitespace
+        } else if a > 0 && wordClass(line[a - 1], big) == 0 {
+            a = run(a - 1).0; //no trailing whitespace, so take the leading
+        }
+    }
+    Some(TextRange {
+        start: Pos { y: pos.y, x: a as i32 },
+        end: Pos { y: pos.y, x: b as i32 },
+        kind: RangeKind::Charwise,
+    })
+}
+
+fn quoteObject(content: &[String], pos: Pos, quote: char, inner: bool) -> Option<TextRange> {
+    let line = lineChars(content, pos.y);
+    //pair up the quotes on the line, skipping escaped ones
+    let mut quotes = Vec::new();
+    let mut i = 0;
+    while i < line.len() {
+        if line[i] == '\\' {
+            i += 2;
+            continue;
+        }
+        if line[i] == quote {
+            quotes.push(i);
+        }
+        i += 1;
+    }
+    let x = pos.x.max(0) as usize;
+    let (open, close) = quotes
+        .chunks(2)
+        .filter(|pair| pair.len() == 2)
+        .map(|pair| (pair[0], pair[1]))
+        .find(|(_, close)| x <= *close)?; //the pair we're in, or else the next one along
+    let (mut a, mut b) = if inner { (open + 1, close) } else { (open, close + 1) };
+    if !inner {
+        let trailing = line[b..].iter().take_while(|c| c.is_whitespace()).count();
+        if trailing > 0 {
+            b += trailing;
+        } else {
+            a -= line[..a].iter().rev().take_while(|c| c.is_whitespace()).count();
+        }
+    }
+    Some(TextRange {
+        start: Pos { y: pos.y, x: a as i32 },
+        end: Pos { y: pos.y, x: b as i32 },
+        kind: RangeKind::Charwise,
+    })
+}
+
+fn step(content: &[String], pos: Pos, forward: bool) -> Option<Pos> {
+    if forward {
+        if pos.x < lineLen(&content[pos.y as usize]) {
+            Some(Pos { y: pos.y, x: pos.x + 1 })
+        } else if pos.y + 1 < content.len() as i32 {
+            Some(Pos { y: pos.y + 1, x: 0 })
+        } else {
+            None
+        }
+    } else if pos.x > 0 {
+        Some(Pos { y: pos.y, x: pos.x - 1 })
+    } else if pos.y > 0 {
+        Some(Pos { y: pos.y - 1, x: lineLen(&content[pos.y as usize - 1]) })
+    } else {
+        None
+    }
+}
+
+fn charAt(content: &[String], pos: Pos) -> Option<char> {
+    content[pos.y as usize].chars().nth(pos.x as usize)
+}
+
+fn bracketObject(content: &[String], pos: Pos, open: char, close: char, inner: bool, count: usize) -> Option<TextRange> {
+    //walk back to the unmatched opening bracket - count of them for nested ones
+    let mut start = pos;
+    let mut depth = 0;
+    let mut found = 0;
+    loop {
+        match charAt(content, start) {
+            Some(c) if c == open => {
+                if depth == 0 {
+                    found += 1;
+                    if found == count {
+                        break;
+                    }
+                } else {
+                    depth -= 1;
+                }
+            }
+            //a closing bracket under the cursor belongs to the object
+            Some(c) if c == close && start != pos => depth += 1,
+            _ => {}
+        }
+        start = step(content, start, false)?;
+    }
+    //then forward to its match
+    let mut end = start;
+    let mut depth = 0;
+    loop {
+        match charAt(content, end) {
+            Some(c) if c == open => depth += 1,
+            Some(c) if c == close => {
+                depth -= 1;
+                if depth == 0 {
+                    break;
+                }
+            }
+            _ => {}
+        }
+        end = step(content, end, true)?;
+    }
+    if inner {
+        let a = step(content, start, true)?;
+        let before_close: String = content[end.y as usize].chars().take(end.x as usize).collect();
+        //brackets on lines of their own leave whole lines in between
+        if a.x == lineLen(&content[a.y as usize]) && end.y > a.y + 1 && before_close.trim().is_empty() {
+            return Some(TextRange {
+                start: Pos { y: a.y + 1, x: 0 },
+                end: Pos { y: end.y - 1, x: 0 },
+                kind: RangeKind::Linewise,
+            });
+        }
+        Some(TextRange { start: a, end, kind: RangeKind::Charwise })
+    } else {
+        Some(TextRange { start, end: Pos { y: end.y, x: end.x + 1 }, kind: RangeKind::Charwise })
+    }
+}
+
+fn paragraphObject(content: &[String], pos: Pos, inner: bool, count: usize) -> Option<TextRange> {
+    let blank = |y: i32| content[y as usize].trim().is_empty();
+    let last = content.len() as i32 - 1;
+    let kind = blank(pos.y);
+    let mut a = pos.y;
+    while a > 0 && blank(a - 1) == kind {
+        a -= 1;
+    }
+    let mut b = pos.y;
+    let mut runs = if inner { count } else { count.saturating_mul(2) };
+    loop {
+        let run_kind = blank(b);
+        while b < last && blank(b + 1) == run_kind {
+            b += 1;
+        }
+        runs -= 1;
+        if runs == 0 || b == last {
+            break;
+        }
+        b += 1;
+    }
+    //`ap` with no blank lines after takes the ones before instead
+    if !inner && !kind && (b == last && !blank(b)) {
+        while a > 0 && blank(a - 1) {
+            a -= 1;
+        }
+    }
+    Some(TextRange { start: Pos { y: a, x: 0 }, end: Pos { y: b, x: 0 }, kind: RangeKind::Linewise })
+}
+
+// the range a text object covers around the cursor
+pub(crate) fn selectObject(content: &[String], pos: Pos, object: TextObject, count: usize) -> Option<TextRange> {
+    let count = count.max(1);
+    match object.kind {
+        ObjectKind::Word { big } => wordObject(content, pos, big, object.inner),
+        ObjectKind::Quote(quote) => quoteObject(content, pos, quote, object.inner),
+        ObjectKind::Bracket { open, close } => bracketObject(content, pos, open, close, object.inner, count),
+        ObjectKind::Paragraph => paragraphObject(content, pos, object.inner, count),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::lines;
+
+    // the text a charwise object on one line covers
+    fn select(line: &str, x: i32, c: char, inner: bool, count: usize) -> Option<String> {
+        let content = lines(&[line]);
+        let range = selectObject(&content, Pos { y: 0, x }, objectFromChar(c, inner)?, count)?;
+        assert_eq!((range.kind, range.start.y, range.end.y), (RangeKind::Charwise, 0, 0));
+        Some(line.chars().skip(range.start.x as usize).take((range.end.x - range.start.x) as usize).collect())
+    }
+
+    // first and last line a linewise object covers
+    fn linewise(content: &[String], y: i32, c: char, inner: bool, count: usize) -> Option<(i32, i32)> {
+        let range = selectObject(content, Pos { y, x: 0 }, objectFromChar(c, inner)?, count)?;
+        assert_eq!(range.kind, RangeKind::Linewise);
+        Some((range.start.y, range.end.y))
+    }
+
+    #[test]
+    fn words() {
+        assert_eq!(select("foo bar.baz", 5, 'w', true, 1).as_deref(), Some("bar"));
+        assert_eq!(select("foo bar.baz", 5, 'w', false, 1).as_deref(), Some(" bar"));
+        assert_eq!(select("foo bar baz", 5, 'w', false, 1).as_deref(), Some("bar "));
+        assert_eq!(select("foo bar", 5, 'w', false, 1).as_deref(), Some(" bar"));
+        assert_eq!(select("foo bar.baz", 5, 'W', true, 1).as_deref(), Some("bar.baz"));
+        assert_eq!(select("a   b", 2, 'w', false, 1).as_deref(), Some("   b"));
+        assert_eq!(select("", 0, 'w', true, 1), None);
+    }
+
+    #[test]
+    fn quotes() {
+        let line = r#"x = "a \" b" + "c""#;
+        assert_eq!(select(line, 6, '"', true, 1).as_deref(), Some(r#"a \" b"#));
+        assert_eq!(select(line, 6, '"', false, 1).as_deref(), Some(r#""a \" b" "#));
+        //before any quotes picks the next pair
+        assert_eq!(select(line, 0, '"', true, 1).as_deref(), Some(r#"a \" b"#));
+        assert_eq!(select("'open", 2, '\'', true, 1), None);
+    }
+
+    #[test]
+    fn brackets() {
+        let line = "f(a, (b), [c])";
+        assert_eq!(select(line, 6, ')', true, 1).as_deref(), Some("b"));
+        assert_eq!(select(line, 6, 'b', true, 2).as_deref(), Some("a, (b), [c]"));
+        assert_eq!(select(line, 5, '(', false, 1).as_deref(), Some("(b)"));
+        //on a closing bracket, the pair it closes
+        assert_eq!(select(line, 12, '[', false, 1).as_deref(), Some("[c]"));
+        assert_eq!(select(line, 7, ')', true, 1).as_deref(), Some("b"));
+        assert_eq!(select(line, 13, ')', true, 1).as_deref(), Some("a, (b), [c]"));
+        assert_eq!(select(line, 0, '(', true, 1), None);
+        assert_eq!(select("()", 0, '(', true, 1).as_deref(), Some(""));
+        //brackets on lines of their own leave the lines between
+        let content = lines(&["fn x() {", "    a;", "    b;", "}"]);
+        assert_eq!(linewise(&content, 1, '{', true, 1), Some((1, 2)));
+    }
+
+    #[test]
+    fn paragraphs() {
+        let content = lines(&["a", "b", "", "", "c", "", "d"]);
+        assert_eq!(linewise(&content, 0, 'p', true, 1), Some((0, 1)));
+        assert_eq!(linewise(&content, 0, 'p', false, 1), Some((0, 3)));
+        assert_eq!(linewise(&content, 0, 'p', false, 2), Some((0, 5)));
+        assert_eq!(linewise(&content, 2, 'p', true, 1), Some((2, 3)));
+        //the last paragraph has no blank lines after, so takes the ones before
+        assert_eq!(linewise(&content, 6, 'p', false, 1), Some((5, 6)));
+        assert_eq!(linewise(&content, 0, 'p', false, usize::MAX), Some((0, 6)));
+    }
+}
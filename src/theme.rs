@@ -0,0 +1,476 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::io::Error;
+use std::io::ErrorKind::Other;
+
+//Theme the editor ships with - used when no other theme is picked
+const DEFAULT_THEME: &str = include_str!("themes/default.theme");
+
+// semantic faces - the renderer asks for these and never for raw colors
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum Face {
+    Normal,
+    NonText, //the prefix drawn on lines past the end of the file
+    StatusBar,
+    StatusBarInactive, //status bar of windows that don't have focus
+    Separator,         //line between side by side windows
+    LineNumber,
+    SearchMatch,
+    Selection,
+    Keyword,
+    String,
+    Comment,
+    Number,
+}
+
+impl Face {
+    const ALL: [Face; 12] = [
+        Face::Normal,
+        Face::NonText,
+        Face::StatusBar,
+        Face::StatusBarInactive,
+        Face::Separator,
+        Face::LineNumber,
+        Face::SearchMatch,
+        Face::Selection,
+        Face::Keyword,
+        Face::String,
+        Face::Comment,
+        Face::Number,
+    ];
+
+    // name used for the face in theme files
+    fn name(self) -> &'static str {
+        match self {
+            Face::Normal => "normal",
+            Face::NonText => "nontext",
+            Face::StatusBar => "statusbar",
+            Face::StatusBarInactive => "statusbarnc",
+            Face::Separator => "separator",
+            Face::LineNumber => "linenr",
+            Face::SearchMatch => "search",
+            Face::Selection => "selection",
+            Face::Keyword => "keyword",
+            Face::String => "string",
+            Face::Comment => "comment",
+            Face::Number => "number",
+        }
+    }
+
+    fn fromName(name: &str) -> Option<Face> {
+        Face::ALL.iter().copied().find(|face| face.name() == name)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum Color {
+    Default,
+    Ansi(u8),    //one of the 16 basic colors
+    Indexed(u8), //256 color palette
+    Rgb(u8, u8, u8),
+}
+
+// how many colors the terminal we're running in can show
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub(crate) enum ColorSupport {
+    NoColor,
+    Ansi16,
+    Ansi256,
+    TrueColor,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) struct Style {
+    pub(crate) fg: Color,
+    pub(crate) bg: Color,
+    pub(crate) bold: bool,
+    pub(crate) italic: bool,
+    pub(crate) underline: bool,
+    pub(crate) reverse: bool,
+}
+
+impl Style {
+    const PLAIN: Style = Style {
+        fg: Color::Default,
+        bg: Color::Default,
+        bold: false,
+        italic: false,
+        underline: false,
+        reverse: false,
+    };
+}
+
+pub(crate) struct Theme {
+    styles: [Style; Face::ALL.len()],
+    support: ColorSupport,
+}
+
+const COLOR_NAMES: [&str; 8] = [
+    "black", "red", "green", "yellow", "blue", "magenta", "cyan", "white",
+];
+
+// xterm's default rgb values for the 16 basic colors
+const ANSI_RGB: [(u8, u8, u8); 16] = [
+    (0, 0, 0),
+    (205, 0, 0),
+    (0, 205, 0),
+    (205, 205, 0),
+    (0, 0, 238),
+    (205, 0, 205),
+    (0, 205, 205),
+    (229, 229, 229),
+    (127, 127, 127),
+    (255, 0, 0),
+    (0, 255, 0),
+    (255, 255, 0),
+    (92, 92, 255),
+    (255, 0, 255),
+    (0, 255, 255),
+    (255, 255, 255),
+];
+
+// steps of the 6x6x6 color cube in the 256 color palette
+const CUBE_STEPS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+impl Theme {
+    pub(crate) fn builtin() -> Theme {
+        Theme::parse(DEFAULT_THEME).expect("built in theme should always parse")
+    }
+
+    // resolve a theme name to a file - either a path or <config>/rustwrd/themes/<name>.theme
+    pub(crate) fn find(name: &str) -> io::Result<Theme> {
+        if name == "default" {
+            return Ok(Theme::builtin());
+        }
+        if name.contains('/') {
+            return Theme::load(name);
+        }
+        match configDir() {
+            Some(dir) => Theme::load(&format!("{}/rustwrd/themes/{}.theme", dir, name)),
+            None => Err(Error::new(Other, format!("Unknown theme: {}", name))),
+        }
+    }
+
+    pub(crate) fn load(fp: &str) -> io::Result<Theme> {
+        match fs::read_to_string(fp) {
+            Ok(text) => Theme::parse(&text),
+            Err(e) => Err(Error::new(Other, format!("{}: {}", fp, e))),
+        }
+    }
+
+    pub(crate) fn parse(text: &str) -> io::Result<Theme> {
+        let mut theme = Theme {
+            styles: [Style::PLAIN; Face::ALL.len()],
+            support: detectColorSupport(),
+        };
+
+        for (num, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (name, spec) = match line.split_once('=') {
+                Some(parts) => parts,
+                None => {
+                    return Err(Error::new(
+                        Other,
+                        format!("theme line {}: expected <face> = <style>", num + 1),
+                    ))
+                }
+            };
+            let face = match Face::fromName(name.trim()) {
+                Some(face) => face,
+                None => {
+                    return Err(Error::new(
+                        Other,
+                        format!("theme line {}: unknown face `{}`", num + 1, name.trim()),
+                    ))
+                }
+            };
+            match parseStyle(spec) {
+                Ok(style) => theme.styles[face as usize] = style,
+                Err(e) => {
+                    return Err(Error::new(Other, format!("theme line {}: {}", num + 1, e)))
+                }
+            }
+        }
+        Ok(theme)
+    }
+
+    pub(crate) fn style(&self, face: Face) -> Style {
+        self.styles[face as usize]
+    }
+
+    // escape sequence that switches the terminal over to the given face
+    pub(crate) fn sgr(&self, face: Face) -> String {
+        let style = self.style(face);
+        let mut codes = vec![String::from("0")];
+        if style.bold {
+            codes.push(String::from("1"));
+        }
+        if style.italic {
+            codes.push(String::from("3"));
+        }
+        if style.underline {
+            codes.push(String::from("4"));
+        }
+        if style.reverse {
+            codes.push(String::from("7"));
+        } else if self.support == ColorSupport::NoColor && style.bg != Color::Default {
+            //without colors the only way to keep a background visible is to invert
+            codes.push(String::from("7"));
+        }
+        if let Some(fg) = colorCode(degrade(style.fg, self.support), false) {
+            codes.push(fg);
+        }
+        if let Some(bg) = colorCode(degrade(style.bg, self.support), true) {
+            codes.push(bg);
+        }
+        format!("\x1b[{}m", codes.join(";"))
+    }
+}
+
+fn parseStyle(spec: &str) -> Result<Style, String> {
+    let mut style = Style::PLAIN;
+    for word in spec.split_whitespace() {
+        match word {
+            "bold" => style.bold = true,
+            "italic" => style.italic = true,
+            "underline" => style.underline = true,
+            "reverse" => style.reverse = true,
+            _ => {
+                if let Some(color) = word.strip_prefix("fg:") {
+                    style.fg = parseColor(color)?;
+                } else if let Some(color) = word.strip_prefix("bg:") {
+                    style.bg = parseColor(color)?;
+                } else {
+                    return Err(format!("unknown attribute `{}`", word));
+                }
+            }
+        }
+    }
+    Ok(style)
+}
+
+fn parseColor(word: &str) -> Result<Color, String> {
+    if word == "default" {
+        return Ok(Color::Default);
+    }
+    if let Some(hex) = word.strip_prefix('#') {
+        if hex.len() == 6 {
+            if let Ok(value) = u32::from_str_radix(hex, 16) {
+                return Ok(Color::Rgb((value >> 16) as u8, (value >> 8) as u8, value as u8));
+            }
+        }
+        return Err(format!("invalid color `{}`", word));
+    }
+    if let Ok(index) = word.parse::<u8>() {
+        return Ok(Color::Indexed(index));
+    }
+    let (bright, name) = match word.strip_prefix("bright-") {
+        Some(name) => (8, name),
+        None => (0, word),
+    };
+    match COLOR_NAMES.iter().position(|n| *n == name) {
+        Some(i) => Ok(Color::Ansi(i as u8 + bright)),
+        None => Err(format!("invalid color `{}`", word)),
+    }
+}
+
+// work out color support from the environment - NO_COLOR always wins
+pub(crate) fn detectColorSupport() -> ColorSupport {
+    if env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty()) {
+        return ColorSupport::NoColor;
+    }
+    let colorterm = env::var("COLORTERM").unwrap_or_default();
+    if colorterm == "truecolor" || colorterm == "24bit" {
+        return ColorSupport::TrueColor;
+    }
+    let term = env::var("TERM").unwrap_or_default();
+    if term.is_empty() || term == "dumb" {
+        ColorSupport::NoColor
+    } else if term.contains("256color") {
+        ColorSupport::Ansi256
+    } else {
+        ColorSupport::Ansi16
+    }
+}
+
+// turn a color into the closest one the terminal can show
+fn degrade(color: Color, support: ColorSupport) -> Color {
+    match (color, support) {
+        (_, ColorSupport::NoColor) => Color::Default,
+        (Color::Rgb(r, g, b), ColorSupport::Ansi256) => Color::Indexed(rgbToIndexed(r, g, b)),
+        (Color::Rgb(r, g, b), ColorSupport::Ansi16) => Color::Ansi(nearestAnsi(r, g, b)),
+        (Color::Indexed(i), ColorSupport::Ansi16) => {
+            let (r, g, b) = indexedToRgb(i);
+            Color::Ansi(nearestAnsi(r, g, b))
+        }
+        _ => color,
+    }
+}
+
+fn colorCode(color: Color, background: bool) -> Option<String> {
+    let base = if background { 40 } else { 30 };
+    match color {
+        Color::Default => None,
+        Color::Ansi(i) if i < 8 => Some(format!("{}", base + i as u32)),
+        Color::Ansi(i) => Some(format!("{}", base + 60 + (i as u32 - 8))),
+        Color::Indexed(i) => Some(format!("{};5;{}", base + 8, i)),
+        Color::Rgb(r, g, b) => Some(format!("{};2;{};{};{}", base + 8, r, g, b)),
+    }
+}
+
+fn distance(a: (u8, u8, u8), b: (u8, u8, u8)) -> u32 {
+    let dr = a.0 as i32 - b.0 as i32;
+    let dg = a.1 as i32 - b.1 as i32;
+    let db = a.2 as i32 - b.2 as i32;
+    (dr * dr + dg * dg + db * db) as u32
+}
+
+fn nearestAnsi(r: u8, g: u8, b: u8) -> u8 {
+    let mut best = 0;
+    for (i, rgb) in ANSI_RGB.iter().enumerate() {
+        if distance(*rgb, (r, g, b)) < distance(ANSI_RGB[best], (r, g, b)) {
+            best = i;
+        }
+    }
+    best as u8
+}
+
+fn nearestStep(v: u8) -> usize {
+    let mut best = 0;
+    for (i, step) in CUBE_STEPS.iter().enumerate() {
+        if (*step as i32 - v as i32).abs() < (CUBE_STEPS[best] as i32 - v as i32).abs() {
+            best = i;
+        }
+    }
+    best
+}
+
+// pick whichever of the color cube or the grayscale ramp is closer
+fn rgbToIndexed(r: u8, g: u8, b: u8) -> u8 {
+    let (ri, gi, bi) = (nearestStep(r), nearestStep(g), nearestStep(b));
+    let cube = (CUBE_STEPS[ri], CUBE_STEPS[gi], CUBE_STEPS[bi]);
+
+    let avg = (r as u32 + g as u32 + b as u32) / 3;
+    let gray_i = if avg < 8 { 0 } else { ((avg - 8) / 10).min(23) };
+    let gray_v = (8 + gray_i * 10) as u8;
+
+    if distance((gray_v, gray_v, gray_v), (r, g, b)) < distance(cube, (r, g, b)) {
+        232 + gray_i as u8
+    } else {
+        16 + (36 * ri + 6 * gi + bi) as u8
+    }
+}
+
+fn indexedToRgb(i: u8) -> (u8, u8, u8) {
+    match i {
+        0..=15 => ANSI_RGB[i as usize],
+        16..=231 => {
+            let i = i as usize - 16;
+            (CUBE_STEPS[i / 36], CUBE_STEPS[(i / 6) % 6], CUBE_STEPS[i % 6])
+        }
+        _ => {
+            let v = 8 + (i - 232) * 10;
+            (v, v, v)
+        }
+    }
+}
+
+// $XDG_CONFIG_HOME falling back to ~/.config
+pub(crate) fn configDir() -> Option<String> {
+    match env::var("XDG_CONFIG_HOME") {
+        Ok(dir) if !dir.is_empty() => Some(dir),
+        _ => env::var("HOME").ok().map(|home| format!("{}/.config", home)),
+    }
+}
+
+// $XDG_STATE_HOME falling back to ~/.local/state
+pub(crate) fn stateDir() -> Option<String> {
+    match env::var("XDG_STATE_HOME") {
+        Ok(dir) if !dir.is_empty() => Some(dir),
+        _ => env::var("HOME").ok().map(|home| format!("{}/.local/state", home)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parsesColors() {
+        assert_eq!(parseColor("default"), Ok(Color::Default));
+        assert_eq!(parseColor("#ff8000"), Ok(Color::Rgb(255, 128, 0)));
+        assert_eq!(parseColor("42"), Ok(Color::Indexed(42)));
+        assert_eq!(parseColor("red"), Ok(Color::Ansi(1)));
+        assert_eq!(parseColor("bright-blue"), Ok(Color::Ansi(12)));
+        assert!(parseColor("#fff").is_err());
+        assert!(parseColor("256").is_err());
+        assert!(parseColor("purple").is_err());
+    }
+
+    #[test]
+    fn parsesStyles() {
+        let style = parseStyle("fg:black bg:#5c6370 bold underline").unwrap();
+        assert_eq!(style.fg, Color::Ansi(0));
+        assert_eq!(style.bg, Color::Rgb(0x5c, 0x63, 0x70));
+        assert!(style.bold && style.underline);
+        assert!(!style.italic && !style.reverse);
+        assert!(parseStyle("blink").is_err());
+        assert!(parseStyle("fg:nope").is_err());
+    }
+
+    #[test]
+    fn parsesThemes() {
+        let theme = Theme::parse(DEFAULT_THEME).unwrap();
+        assert!(theme.style(Face::Selection).reverse);
+        assert!(theme.style(Face::Comment).italic);
+
+        let theme = Theme::parse("# comment\n\nkeyword = fg:red\n").unwrap();
+        assert_eq!(theme.style(Face::Keyword).fg, Color::Ansi(1));
+        assert_eq!(theme.style(Face::Normal), Style::PLAIN);
+
+        let e = Theme::parse("normal = fg:red\nkeyword fg:red")
+            .err()
+            .unwrap();
+        assert!(e.to_string().starts_with("theme line 2"));
+        assert!(Theme::parse("cursor = bold").is_err());
+        assert!(Theme::parse("normal = fg:red shiny").is_err());
+    }
+
+    #[test]
+    fn degradesColors() {
+        assert_eq!(rgbToIndexed(255, 0, 0), 196);
+        assert_eq!(rgbToIndexed(128, 128, 128), 244);
+        assert_eq!(indexedToRgb(196), (255, 0, 0));
+        assert_eq!(indexedToRgb(244), (128, 128, 128));
+        assert_eq!(nearestAnsi(250, 10, 0), 9);
+
+        let rgb = Color::Rgb(255, 0, 0);
+        assert_eq!(degrade(rgb, ColorSupport::TrueColor), rgb);
+        assert_eq!(degrade(rgb, ColorSupport::Ansi256), Color::Indexed(196));
+        assert_eq!(degrade(rgb, ColorSupport::Ansi16), Color::Ansi(9));
+        assert_eq!(
+            degrade(Color::Indexed(196), ColorSupport::Ansi16),
+            Color::Ansi(9)
+        );
+        assert_eq!(degrade(rgb, ColorSupport::NoColor), Color::Default);
+    }
+
+    #[test]
+    fn buildsEscapes() {
+        let mut theme =
+            Theme::parse("statusbar = fg:black bg:white bold\nnumber = fg:#d19a66").unwrap();
+        theme.support = ColorSupport::TrueColor;
+        assert_eq!(theme.sgr(Face::StatusBar), "\x1b[0;1;30;47m");
+        assert_eq!(theme.sgr(Face::Number), "\x1b[0;38;2;209;154;102m");
+        assert_eq!(theme.sgr(Face::Normal), "\x1b[0m");
+
+        //without colors a background turns into reverse video
+        theme.support = ColorSupport::NoColor;
+        assert_eq!(theme.sgr(Face::StatusBar), "\x1b[0;1;7m");
+        assert_eq!(theme.sgr(Face::Number), "\x1b[0m");
+    }
+}
@@ -0,0 +1,348 @@
+use crate::theme::stateDir;
+
+use std::fs;
+use std::io;
+use std::io::Error;
+use std::io::ErrorKind::Other;
+
+//first line of every undo file
+const UNDO_HEADER: &str = "rustwrd undo 1";
+
+// one change to a buffer: the lines starting at `line` went from `old` to `new`
+#[derive(Clone, Debug)]
+pub(crate) struct Edit {
+    pub(crate) line: usize,
+    pub(crate) old: Vec<String>,
+    pub(crate) new: Vec<String>,
+}
+
+// everything undone/redone by a single key press
+#[derive(Clone, Debug)]
+pub(crate) struct UndoStep {
+    pub(crate) id: u64,
+    pub(crate) edits: Vec<Edit>,
+    pub(crate) cursor: (i32, i32), //where the cursor was before the step (x, y)
+}
+
+#[derive(Default)]
+pub(crate) struct UndoHistory {
+    pub(crate) undo_stack: Vec<UndoStep>,
+    pub(crate) redo_stack: Vec<UndoStep>,
+    pending: Vec<Edit>,       //edits made since the last seal
+    pending_cursor: (i32, i32),
+    pub(crate) next_id: u64, //ids are never reused so we can tell states apart
+}
+
+impl UndoHistory {
+    pub(crate) fn record(&mut self, edit: Edit, cursor: (i32, i32)) {
+        if self.pending.is_empty() {
+            self.pending_cursor = cursor;
+        }
+        self.pending.push(edit);
+    }
+
+    // close off the current step - everything recorded since the last seal undoes together
+    pub(crate) fn seal(&mut self) {
+        if self.pending.is_empty() {
+            return;
+        }
+        self.next_id += 1;
+        self.undo_stack.push(UndoStep {
+            id: self.next_id,
+            edits: std::mem::take(&mut self.pending),
+            cursor: self.pending_cursor,
+        });
+        self.redo_stack.clear();
+    }
+
+    pub(crate) fn isSealed(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    // id of the state the buffer is currently in, 0 being the state it was loaded in
+    pub(crate) fn state(&self) -> u64 {
+        self.undo_stack.last().map_or(0, |step| step.id)
+    }
+
+    pub(crate) fn popUndo(&mut self) -> Option<UndoStep> {
+        self.seal();
+        let step = self.undo_stack.pop()?;
+        self.redo_stack.push(step.clone());
+        Some(step)
+    }
+
+    pub(crate) fn popRedo(&mut self) -> Option<UndoStep> {
+        self.seal();
+        let step = self.redo_stack.pop()?;
+        self.undo_stack.push(step.clone());
+        Some(step)
+    }
+
+    // lines the edits since the last seal left behind
+    pub(crate) fn pendingLines(&self) -> Vec<usize> {
+        let mut lines: Vec<usize> = self.pending.iter().flat_map(|edit| edit.line..edit.line + edit.new.len()).collect();
+        lines.sort();
+        lines.dedup();
+        lines
+    }
+
+    // drop the steps that touch lines outside `start..end`, which are about to go - and every step before
+    // them, since those can't be undone without them
+    pub(crate) fn forgetOutside(&mut self, start: usize, end: usize) {
+        self.seal();
+        let outside = |step: &UndoStep| {
+            step.edits.iter().any(|edit| edit.line < start || edit.line + edit.old.len().max(edit.new.len()) > end)
+        };
+        if let Some(i) = self.undo_stack.iter().rposition(outside) {
+            self.undo_stack.drain(..=i);
+        }
+        if self.redo_stack.iter().any(outside) {
+            self.redo_stack.clear();
+        }
+    }
+
+    // move every step down `delta` lines, for when lines go in or out above all of them
+    pub(crate) fn shift(&mut self, delta: i64) {
+        self.seal();
+        for step in self.undo_stack.iter_mut().chain(self.redo_stack.iter_mut()) {
+            step.cursor.1 = (step.cursor.1 as i64 + delta) as i32;
+            for edit in step.edits.iter_mut() {
+                edit.line = (edit.line as i64 + delta) as usize;
+            }
+        }
+    }
+}
+
+// FNV-1a, a quick hash for telling whether text is still what it was
+fn fnv(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, b| (hash ^ *b as u64).wrapping_mul(0x100000001b3))
+}
+
+pub(crate) fn bytesHash(bytes: &[u8]) -> u64 {
+    fnv(0xcbf29ce484222325, bytes)
+}
+
+// hash of the lines of a file - tells whether it's still the text an undo file was saved for
+fn contentHash(content: &[String]) -> u64 {
+    let mut hash = bytesHash(b"");
+    for (i, line) in content.iter().enumerate() {
+        if i > 0 {
+            hash = fnv(hash, b"\n");
+        }
+        hash = fnv(hash, line.as_bytes());
+    }
+    hash
+}
+
+// where the undo history of `fp` is kept - its full path with % for / under the state directory
+fn undoPath(fp: &str) -> Option<String> {
+    let full = fs::canonicalize(fp).ok()?;
+    Some(format!("{}/rustwrd/undo/{}", stateDir()?, full.to_string_lossy().replace('/', "%")))
+}
+
+fn writeStep(out: &mut String, step: &UndoStep) {
+    out.push_str(&format!("step {} {} {} {}\n", step.id, step.cursor.0, step.cursor.1, step.edits.len()));
+    for edit in &step.edits {
+        out.push_str(&format!("edit {} {} {}\n", edit.line, edit.old.len(), edit.new.len()));
+        //every line of text gets a | so empty ones can't be mistaken for anything else
+        for line in edit.old.iter().chain(edit.new.iter()) {
+            out.push('|');
+            out.push_str(line);
+            out.push('\n');
+        }
+    }
+}
+
+// numbers after a keyword - `step 3 0 12 1` gives [3, 0, 12, 1]
+fn fields(line: Option<&str>, keyword: &str) -> Option<Vec<i64>> {
+    let rest = line?.strip_prefix(keyword)?.strip_prefix(' ')?;
+    rest.split(' ').map(|n| n.parse().ok()).collect()
+}
+
+// a number that has to be 0 or more - anything else means the file was damaged
+fn unsigned<T: TryFrom<i64>>(n: i64) -> Option<T> {
+    if n < 0 {
+        return None;
+    }
+    T::try_from(n).ok()
+}
+
+fn readStep<'a>(lines: &mut impl Iterator<Item = &'a str>) -> Option<UndoStep> {
+    let step = fields(lines.next(), "step")?;
+    let (id, x, y, count) = (*step.first()?, *step.get(1)?, *step.get(2)?, *step.get(3)?);
+    let (id, x, y, count): (u64, i32, i32, usize) = (unsigned(id)?, unsigned(x)?, unsigned(y)?, unsigned(count)?);
+    let mut edits = Vec::new();
+    for _ in 0..count {
+        let edit = fields(lines.next(), "edit")?;
+        let (line, old_len, new_len) = (*edit.first()?, *edit.get(1)?, *edit.get(2)?);
+        let (line, old_len, new_len): (usize, usize, usize) = (unsigned(line)?, unsigned(old_len)?, unsigned(new_len)?);
+        let mut text = |len: usize| -> Option<Vec<String>> {
+            (0..len).map(|_| lines.next()?.strip_prefix('|').map(String::from)).collect()
+        };
+        let old = text(old_len)?;
+        let new = text(new_len)?;
+        edits.push(Edit { line, old, new });
+    }
+    Some(UndoStep { id, edits, cursor: (x, y) })
+}
+
+// do the edits line up with text of `len` lines - undoing back from it, and redoing forward.
+// Buffers always keep one line, even when every line was deleted
+fn fitsContent(history: &UndoHistory, len: usize) -> bool {
+    let mut undone = len;
+    for step in history.undo_stack.iter().rev() {
+        for edit in step.edits.iter().rev() {
+            if edit.line.saturating_add(edit.new.len()) > undone {
+                return false;
+            }
+            undone = undone - edit.new.len() + edit.old.len();
+        }
+        undone = undone.max(1);
+    }
+    let mut redone = len;
+    for step in history.redo_stack.iter().rev() {
+        for edit in &step.edits {
+            if edit.line.saturating_add(edit.old.len()) > redone {
+                return false;
+            }
+            redone = redone - edit.old.len() + edit.new.len();
+        }
+        redone = redone.max(1);
+    }
+    true
+}
+
+// save the history of a file that was just written with `content`
+pub(crate) fn saveUndo(fp: &str, content: &[String], history: &UndoHistory) -> io::Result<()> {
+    let path = match undoPath(fp) {
+        Some(path) => path,
+        None => return Err(Error::new(Other, "No state directory")),
+    };
+    //a file with nothing to undo doesn't need one - and an old one would be wrong now
+    if history.undo_stack.is_empty() && history.redo_stack.is_empty() {
+        let _ = fs::remove_file(&path);
+        return Ok(());
+    }
+    let mut out = format!("{}\nhash {:016x}\nnext {}\n", UNDO_HEADER, contentHash(content), history.next_id);
+    out.push_str(&format!("undo {}\n", history.undo_stack.len()));
+    for step in &history.undo_stack {
+        writeStep(&mut out, step);
+    }
+    out.push_str(&format!("redo {}\n", history.redo_stack.len()));
+    for step in &history.redo_stack {
+        writeStep(&mut out, step);
+    }
+    if let Some(dir) = std::path::Path::new(&path).parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(&path, out)
+}
+
+// the history saved for `fp`, as long as it was saved for exactly the text the file has now
+pub(crate) fn loadUndo(fp: &str, content: &[String]) -> Option<UndoHistory> {
+    let text = fs::read_to_string(undoPath(fp)?).ok()?;
+    let mut lines = text.lines();
+    if lines.next()? != UNDO_HEADER {
+        return None;
+    }
+    let hash = lines.next()?.strip_prefix("hash ")?;
+    if u64::from_str_radix(hash, 16).ok()? != contentHash(content) {
+        return None; //changed outside the editor since
+    }
+    let next_id = unsigned(*fields(lines.next(), "next")?.first()?)?;
+    let mut history = UndoHistory { next_id, ..Default::default() };
+    for _ in 0..*fields(lines.next(), "undo")?.first()? {
+        history.undo_stack.push(readStep(&mut lines)?);
+    }
+    for _ in 0..*fields(lines.next(), "redo")?.first()? {
+        history.redo_stack.push(readStep(&mut lines)?);
+    }
+    //a damaged or hand edited file could point past the end of the text
+    if !fitsContent(&history, content.len()) {
+        return None;
+    }
+    Some(history)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::lines;
+
+    fn step(id: u64, edits: Vec<Edit>) -> UndoStep {
+        UndoStep { id, edits, cursor: (2, 1) }
+    }
+
+    #[test]
+    fn stepsRoundTrip() {
+        let edits = vec![
+            Edit { line: 1, old: lines(&["two"]), new: lines(&["", "|bar", "three"]) },
+            Edit { line: 0, old: Vec::new(), new: lines(&["zero"]) },
+        ];
+        let mut out = String::new();
+        writeStep(&mut out, &step(7, edits));
+        let read = readStep(&mut out.lines()).unwrap();
+        assert_eq!((read.id, read.cursor), (7, (2, 1)));
+        assert_eq!(read.edits.len(), 2);
+        assert_eq!(read.edits[0].new, lines(&["", "|bar", "three"]));
+        assert_eq!((read.edits[1].line, read.edits[1].old.len()), (0, 0));
+    }
+
+    #[test]
+    fn damagedStepsAreRejected() {
+        let read = |text: &str| readStep(&mut text.lines()).map(|step| step.id);
+        assert_eq!(read("step 1 0 0 1\nedit 0 1 0\n|a"), Some(1));
+        assert_eq!(read("step 1 0 -5 1\nedit 0 1 0\n|a"), None);
+        assert_eq!(read("step 1 0 0 1\nedit -1 1 0\n|a"), None);
+        assert_eq!(read("step 1 0 0 1\nedit 0 2 0\n|a"), None);
+        assert_eq!(read("step 1 0 0 1\nedit 0 1 0\na"), None);
+        assert_eq!(read("step 1 0 99999999999 0"), None);
+        assert_eq!(read("step 1 0 0"), None);
+    }
+
+    #[test]
+    fn editsHaveToFitTheText() {
+        //"a" became "a b c" by adding two lines after it
+        let mut history = UndoHistory::default();
+        history.undo_stack.push(step(1, vec![Edit { line: 1, old: Vec::new(), new: lines(&["b", "c"]) }]));
+        assert!(fitsContent(&history, 3));
+        assert!(!fitsContent(&history, 2));
+        //and a step that was undone puts back a line after the last
+        history.redo_stack.push(step(2, vec![Edit { line: 3, old: Vec::new(), new: lines(&["d"]) }]));
+        assert!(fitsContent(&history, 3));
+        history.redo_stack.push(step(3, vec![Edit { line: 3, old: lines(&["x"]), new: Vec::new() }]));
+        assert!(!fitsContent(&history, 3));
+    }
+
+    #[test]
+    fn stepsUndoAndRedo() {
+        let mut history = UndoHistory::default();
+        history.record(Edit { line: 0, old: lines(&["a"]), new: lines(&["b"]) }, (0, 0));
+        history.record(Edit { line: 1, old: Vec::new(), new: lines(&["c"]) }, (5, 5));
+        assert!(!history.isSealed());
+        assert_eq!(history.pendingLines(), vec![0, 1]);
+        let undone = history.popUndo().unwrap();
+        assert_eq!((undone.id, undone.edits.len(), undone.cursor), (1, 2, (0, 0)));
+        assert_eq!(history.state(), 0);
+        assert_eq!(history.popRedo().unwrap().id, 1);
+        assert_eq!(history.state(), 1);
+        //a new change after undoing drops what could have been redone
+        history.popUndo();
+        history.record(Edit { line: 0, old: Vec::new(), new: lines(&["d"]) }, (0, 0));
+        history.seal();
+        assert!(history.redo_stack.is_empty());
+        assert_eq!(history.state(), 2);
+    }
+
+    #[test]
+    fn forgettingKeepsStepsInside() {
+        let mut history = UndoHistory::default();
+        for (id, line) in [(1, 0), (2, 50), (3, 10)] {
+            history.undo_stack.push(step(id, vec![Edit { line, old: lines(&["a"]), new: lines(&["b"]) }]));
+        }
+        history.forgetOutside(5, 40);
+        assert_eq!(history.undo_stack.iter().map(|step| step.id).collect::<Vec<_>>(), vec![3]);
+        history.shift(-5);
+        assert_eq!(history.undo_stack[0].edits[0].line, 5);
+    }
+}
@@ -0,0 +1,129 @@
+use crate::buffer::{byteIdx, lineLen, Buffer};
+use crate::motion::Pos;
+use crate::operator::{RangeKind, TextRange};
+
+// which visual mode - v, V or Ctrl-V
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum VisualKind {
+    Char,
+    Line,
+    Block,
+}
+
+// a visual selection between two points, either of which can come first
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) struct Selection {
+    pub(crate) anchor: Pos, //where the selection started
+    pub(crate) cursor: Pos, //the end that moves
+    pub(crate) kind: VisualKind,
+    pub(crate) to_eol: bool, //block selection after `$` - goes to the end of every line
+}
+
+impl Selection {
+    pub(crate) fn top(&self) -> i32 {
+        self.anchor.y.min(self.cursor.y)
+    }
+
+    pub(crate) fn bottom(&self) -> i32 {
+        self.anchor.y.max(self.cursor.y)
+    }
+
+    // the text a selection covers, as a range operators can work on
+    pub(crate) fn range(&self, content: &[String]) -> TextRange {
+        let (start, end) = if self.anchor <= self.cursor { (self.anchor, self.cursor) } else { (self.cursor, self.anchor) };
+        match self.kind {
+            VisualKind::Line => TextRange {
+                start: Pos { y: start.y, x: 0 },
+                end: Pos { y: end.y, x: 0 },
+                kind: RangeKind::Linewise,
+            },
+            VisualKind::Block => {
+                let left = self.anchor.x.min(self.cursor.x);
+                let right = if self.to_eol { i32::MAX } else { self.anchor.x.max(self.cursor.x) + 1 };
+                TextRange {
+                    start: Pos { y: start.y, x: left },
+                    end: Pos { y: end.y, x: right },
+                    kind: RangeKind::Blockwise,
+                }
+            }
+            VisualKind::Char => {
+                //selecting past the end of a line takes in the line break
+                let len = lineLen(&content[end.y as usize]);
+                let end = if end.x + 1 > len && (end.y as usize) + 1 < content.len() {
+                    Pos { y: end.y + 1, x: 0 }
+                } else {
+                    Pos { y: end.y, x: (end.x + 1).min(len) }
+                };
+                TextRange { start, end, kind: RangeKind::Charwise }
+            }
+        }
+    }
+
+    // columns of line `y` that are selected, if any. Empty lines still get one column so they show up
+    pub(crate) fn columns(&self, y: i32, line: &str) -> Option<(i32, i32)> {
+        if y < self.top() || y > self.bottom() {
+            return None;
+        }
+        let len = lineLen(line).max(1);
+        let (start, end) = if self.anchor <= self.cursor { (self.anchor, self.cursor) } else { (self.cursor, self.anchor) };
+        match self.kind {
+            VisualKind::Line => Some((0, len)),
+            VisualKind::Block => {
+                let left = self.anchor.x.min(self.cursor.x);
+                let right = if self.to_eol { len } else { self.anchor.x.max(self.cursor.x) + 1 };
+                Some((left, right))
+            }
+            VisualKind::Char => {
+                let a = if y == start.y { start.x } else { 0 };
+                let b = if y == end.y { end.x + 1 } else { len };
+                Some((a, b))
+            }
+        }
+    }
+}
+
+// I or A in block mode. What gets typed on the first line is copied to the others when insert mode ends
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) struct BlockInsert {
+    pub(crate) top: i32,
+    pub(crate) bottom: i32,
+    pub(crate) col: i32,        //column the text went in at on the first line
+    pub(crate) append: bool,    //A pads short lines out, I leaves them alone
+    pub(crate) to_eol: bool,    //A after `$` adds to the end of every line
+    pub(crate) start_len: i32,  //length of the first line before typing started
+}
+
+// copy the text typed on a block's first line onto the rest of its lines
+pub(crate) fn applyBlockInsert(buffer: &mut Buffer, block: &BlockInsert) {
+    //nothing happens if the typing went onto another line
+    if buffer.curs_y != block.top {
+        return;
+    }
+    let first = &buffer.content[block.top as usize];
+    let typed_len = lineLen(first) - block.start_len;
+    if typed_len <= 0 {
+        return;
+    }
+    let typed: String = first.chars().skip(block.col as usize).take(typed_len as usize).collect();
+    let (top, bottom) = (block.top as usize + 1, block.bottom as usize + 1);
+    let lines: Vec<String> = buffer.content[top..bottom]
+        .iter()
+        .map(|line| {
+            let len = lineLen(line);
+            if block.to_eol {
+                format!("{}{}", line, typed)
+            } else if len < block.col {
+                if block.append {
+                    format!("{}{}{}", line, " ".repeat((block.col - len) as usize), typed)
+                } else {
+                    line.clone()
+                }
+            } else {
+                let mut line = line.clone();
+                line.insert_str(byteIdx(&line, block.col), &typed);
+                line
+            }
+        })
+        .collect();
+    buffer.replaceLines(top, bottom, lines);
+}
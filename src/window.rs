@@ -0,0 +1,255 @@
+// a view onto one of the buffers
+pub(crate) struct Window {
+    pub(crate) buf: usize,     //index of the buffer being shown
+    pub(crate) curs_x: i32,    //cursor - only up to date while the window isn't focused
+    pub(crate) curs_y: i32,
+    pub(crate) v_offset: i32,  // vertical scrolling padding
+    pub(crate) h_offset: i32,  //first screen column shown when lines don't wrap
+}
+
+// area of the screen, in 0 based cells
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) struct Rect {
+    pub(crate) x: i32,
+    pub(crate) y: i32,
+    pub(crate) width: i32,
+    pub(crate) height: i32,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum SplitDir {
+    Horizontal, //windows stacked on top of each other
+    Vertical,   //windows side by side
+}
+
+// how the screen is carved up between the windows
+pub(crate) enum Layout {
+    Leaf(usize), //index into the window list
+    Split {
+        dir: SplitDir,
+        ratio: f64, //share of the space given to `first`
+        first: Box<Layout>,
+        second: Box<Layout>,
+    },
+}
+
+//smallest a window can get - one line of text plus its status line
+const MIN_HEIGHT: i32 = 2;
+const MIN_WIDTH: i32 = 1;
+
+impl Window {
+    pub(crate) fn new(buf: usize) -> Window {
+        Window { buf, curs_x: 0, curs_y: 0, v_offset: 0, h_offset: 0 }
+    }
+}
+
+impl Rect {
+    // number of text rows - the last row is the window's status line
+    pub(crate) fn textRows(&self) -> i32 {
+        (self.height - 1).max(0)
+    }
+}
+
+impl Layout {
+    // split an area in two along `dir`, leaving a column for the separator between side by side windows
+    fn divide(dir: SplitDir, ratio: f64, area: Rect) -> (Rect, Rect) {
+        match dir {
+            SplitDir::Horizontal => {
+                let top = ((area.height as f64 * ratio).round() as i32)
+                    .clamp(MIN_HEIGHT, (area.height - MIN_HEIGHT).max(MIN_HEIGHT));
+                (
+                    Rect { height: top, ..area },
+                    Rect { y: area.y + top, height: area.height - top, ..area },
+                )
+            }
+            SplitDir::Vertical => {
+                let usable = area.width - 1;
+                let left = ((usable as f64 * ratio).round() as i32)
+                    .clamp(MIN_WIDTH, (usable - MIN_WIDTH).max(MIN_WIDTH));
+                (
+                    Rect { width: left, ..area },
+                    Rect { x: area.x + left + 1, width: usable - left, ..area },
+                )
+            }
+        }
+    }
+
+    // where every window goes, plus the separator columns between side by side windows
+    pub(crate) fn rects(&self, area: Rect, windows: &mut Vec<(usize, Rect)>, separators: &mut Vec<Rect>) {
+        match self {
+            Layout::Leaf(win) => windows.push((*win, area)),
+            Layout::Split { dir, ratio, first, second } => {
+                let (a, b) = Layout::divide(*dir, *ratio, area);
+                if *dir == SplitDir::Vertical {
+                    separators.push(Rect { x: a.x + a.width, y: area.y, width: 1, height: area.height });
+                }
+                first.rects(a, windows, separators);
+                second.rects(b, windows, separators);
+            }
+        }
+    }
+
+    // put `new` next to `win`, taking half of its space
+    pub(crate) fn split(&mut self, win: usize, new: usize, dir: SplitDir) {
+        match self {
+            Layout::Leaf(w) if *w == win => {
+                *self = Layout::Split {
+                    dir,
+                    ratio: 0.5,
+                    first: Box::new(Layout::Leaf(new)),
+                    second: Box::new(Layout::Leaf(win)),
+                };
+            }
+            Layout::Leaf(_) => {}
+            Layout::Split { first, second, .. } => {
+                first.split(win, new, dir);
+                second.split(win, new, dir);
+            }
+        }
+    }
+
+    // take a window out, giving its space to its neighbour. Windows after it shift down by one
+    pub(crate) fn remove(&mut self, win: usize) {
+        if let Layout::Split { first, second, .. } = self {
+            let sibling = match (first.as_ref(), second.as_ref()) {
+                (Layout::Leaf(w), _) if *w == win => Some(std::mem::replace(second.as_mut(), Layout::Leaf(0))),
+                (_, Layout::Leaf(w)) if *w == win => Some(std::mem::replace(first.as_mut(), Layout::Leaf(0))),
+                _ => None,
+            };
+            match sibling {
+                Some(layout) => *self = layout,
+                None => {
+                    first.remove(win);
+                    second.remove(win);
+                }
+            }
+        }
+        self.renumber(win);
+    }
+
+    fn renumber(&mut self, removed: usize) {
+        match self {
+            Layout::Leaf(w) => {
+                if *w > removed {
+                    *w -= 1;
+                }
+            }
+            Layout::Split { first, second, .. } => {
+                first.renumber(removed);
+                second.renumber(removed);
+            }
+        }
+    }
+
+    fn contains(&self, win: usize) -> bool {
+        match self {
+            Layout::Leaf(w) => *w == win,
+            Layout::Split { first, second, .. } => first.contains(win) || second.contains(win),
+        }
+    }
+
+    // grow (or shrink with a negative delta) a window by a number of cells along `dir`.
+    // Returns false when there's no split in that direction to resize
+    pub(crate) fn resize(&mut self, win: usize, dir: SplitDir, delta: i32, area: Rect) -> bool {
+        if let Layout::Split { dir: split_dir, ratio, first, second } = self {
+            let (a, b) = Layout::divide(*split_dir, *ratio, area);
+            let in_first = first.contains(win);
+            //a split further down that also runs this way is closer to the window
+            let handled = if in_first {
+                first.resize(win, dir, delta, a)
+            } else {
+                second.resize(win, dir, delta, b)
+            };
+            if handled || *split_dir != dir {
+                return handled;
+            }
+            let (size, total) = match dir {
+                SplitDir::Horizontal => (a.height, area.height),
+                SplitDir::Vertical => (a.width, area.width - 1),
+            };
+            if total <= 0 {
+                return false;
+            }
+            let new_size = if in_first { size + delta } else { size - delta };
+            *ratio = (new_size as f64 / total as f64).clamp(0.0, 1.0);
+            return true;
+        }
+        false
+    }
+
+    // share the space evenly again
+    pub(crate) fn equalize(&mut self) {
+        if let Layout::Split { ratio, first, second, .. } = self {
+            *ratio = 0.5;
+            first.equalize();
+            second.equalize();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCREEN: Rect = Rect { x: 0, y: 0, width: 81, height: 24 };
+
+    fn rects(layout: &Layout, area: Rect) -> (Vec<(usize, Rect)>, Vec<Rect>) {
+        let (mut windows, mut separators) = (Vec::new(), Vec::new());
+        layout.rects(area, &mut windows, &mut separators);
+        windows.sort_by_key(|(win, _)| *win);
+        (windows, separators)
+    }
+
+    #[test]
+    fn splitsShareTheScreen() {
+        let mut layout = Layout::Leaf(0);
+        layout.split(0, 1, SplitDir::Vertical);
+        let (windows, separators) = rects(&layout, SCREEN);
+        //the new window goes first, and one column is left for the separator
+        assert_eq!(windows[1].1, Rect { x: 0, y: 0, width: 40, height: 24 });
+        assert_eq!(windows[0].1, Rect { x: 41, y: 0, width: 40, height: 24 });
+        assert_eq!(separators, [Rect { x: 40, y: 0, width: 1, height: 24 }]);
+
+        layout.split(0, 2, SplitDir::Horizontal);
+        let (windows, _) = rects(&layout, SCREEN);
+        assert_eq!(windows[2].1, Rect { x: 41, y: 0, width: 40, height: 12 });
+        assert_eq!(windows[0].1, Rect { x: 41, y: 12, width: 40, height: 12 });
+        assert_eq!(windows[0].1.textRows(), 11);
+    }
+
+    #[test]
+    fn windowsNeverGetTooSmall() {
+        let (top, bottom) = Layout::divide(SplitDir::Horizontal, 0.0, SCREEN);
+        assert_eq!((top.height, bottom.height), (MIN_HEIGHT, 24 - MIN_HEIGHT));
+        let (left, right) = Layout::divide(SplitDir::Vertical, 1.0, SCREEN);
+        assert_eq!((left.width, right.width), (80 - MIN_WIDTH, MIN_WIDTH));
+    }
+
+    #[test]
+    fn removingGivesTheSpaceBack() {
+        let mut layout = Layout::Leaf(0);
+        layout.split(0, 1, SplitDir::Horizontal);
+        layout.split(1, 2, SplitDir::Vertical);
+        layout.remove(1);
+        //window 2 is now 1, and has the top half to itself
+        let (windows, separators) = rects(&layout, SCREEN);
+        assert_eq!(windows, [(0, Rect { y: 12, height: 12, ..SCREEN }), (1, Rect { height: 12, ..SCREEN })]);
+        assert!(separators.is_empty());
+        layout.remove(0);
+        assert!(matches!(layout, Layout::Leaf(0)));
+    }
+
+    #[test]
+    fn resizesTheNearestSplit() {
+        let mut layout = Layout::Leaf(0);
+        layout.split(0, 1, SplitDir::Horizontal);
+        assert!(layout.resize(1, SplitDir::Horizontal, 4, SCREEN));
+        assert_eq!(rects(&layout, SCREEN).0[1].1.height, 16);
+        //growing the bottom window shrinks the top one
+        assert!(layout.resize(0, SplitDir::Horizontal, 2, SCREEN));
+        assert_eq!(rects(&layout, SCREEN).0[1].1.height, 14);
+        assert!(!layout.resize(0, SplitDir::Vertical, 2, SCREEN));
+        layout.equalize();
+        assert_eq!(rects(&layout, SCREEN).0[1].1.height, 12);
+    }
+}